@@ -1,13 +1,14 @@
+use std::{io, mem, os::fd::AsRawFd};
+
 use netlink_packet_core::{NetlinkMessage, NLM_F_ACK, NLM_F_REQUEST};
 use netlink_sys::{protocols::NETLINK_CONNECTOR, Socket, SocketAddr};
-use w1_netlink::proto::{
-    connector::NlConnectorMessage,
-    message::{MasterId, TargetId, W1MessageType, W1NetlinkMessage},
+use w1_netlink::{
+    client::decode_datagram,
+    proto::{connector::NlConnectorMessage, message::W1NetlinkMessage},
 };
 
 fn main() {
-    let msg =
-        W1NetlinkMessage::<MasterId>::new(W1MessageType::ListMasters, TargetId::master_id(0), []);
+    let msg = W1NetlinkMessage::ListMasters(None);
     let cmsg = NlConnectorMessage::new(0, [msg]);
 
     let mut nl_msg = NetlinkMessage::from(cmsg);
@@ -29,6 +30,22 @@ fn main() {
     socket.connect(&kernel_addr).unwrap();
     println!("connected.");
 
+    // without the w1 core loaded nothing ever answers, give up after a second
+    let timeout = libc::timeval {
+        tv_sec: 1,
+        tv_usec: 0,
+    };
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            (&timeout as *const libc::timeval).cast(),
+            mem::size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+
     let n_sent = socket.send(&msg[..], 0).unwrap();
     assert_eq!(n_sent, msg.len());
     println!("sent.");
@@ -36,12 +53,16 @@ fn main() {
     // buffer for receiving the response
     let mut buf = vec![0; 4096];
     loop {
-        let n_received = socket.recv(&mut &mut buf[..], 0).unwrap();
+        let n_received = match socket.recv(&mut &mut buf[..], 0) {
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                println!("no reply, is the w1 core loaded?");
+                return;
+            }
+            Err(err) => panic!("{err}"),
+        };
         println!("received {:#04X?}", &buf[..n_received]);
-        let resp = NetlinkMessage::<NlConnectorMessage<W1NetlinkMessage<MasterId>>>::deserialize(
-            &buf[0..n_received],
-        )
-        .unwrap();
+        let resp = decode_datagram(&buf[0..n_received]).unwrap();
         println!("resp: {:?}", resp);
         if buf[4] == 2 && buf[5] == 0 {
             println!("the kernel responded with an error");
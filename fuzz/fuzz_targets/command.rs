@@ -0,0 +1,12 @@
+//! Single `w1_netlink_cmd` structures.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use w1_netlink::proto::{command::W1NetlinkCommand, Deserializable};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, read)) = W1NetlinkCommand::deserialize(data) {
+        assert!(read <= data.len());
+    }
+});
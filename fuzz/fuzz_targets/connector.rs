@@ -0,0 +1,19 @@
+//! Connector messages, the first two bytes select the netlink message type.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netlink_packet_core::{NetlinkDeserializable, NetlinkHeader};
+use w1_netlink::proto::{connector::NlConnectorMessage, message::W1NetlinkMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((message_type, payload)) = data.split_first_chunk() else {
+        return;
+    };
+    let header = NetlinkHeader {
+        length: (payload.len() + 16) as u32,
+        message_type: u16::from_ne_bytes(*message_type),
+        ..Default::default()
+    };
+    let _ = NlConnectorMessage::<W1NetlinkMessage>::deserialize(&header, payload);
+});
@@ -0,0 +1,9 @@
+//! Whole netlink datagrams as received from the socket.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = w1_netlink::client::decode_datagram(data);
+});
@@ -0,0 +1,12 @@
+//! `w1_netlink_msg` structures including their commands.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use w1_netlink::proto::{message::W1NetlinkMessage, Deserializable};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, read)) = W1NetlinkMessage::deserialize(data) {
+        assert!(read <= data.len());
+    }
+});
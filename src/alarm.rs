@@ -0,0 +1,255 @@
+//! Alarm thresholds and alarm search for a whole bus.
+//!
+//! [`AlarmScan::configure`] programs the thresholds of every alarm capable
+//! slave found on a master. [`AlarmScan::scan`] then starts a conversion on
+//! all of them at once, runs an alarm search and reads back the devices that
+//! answered to tell which limit they crossed.
+
+use std::{collections::BTreeMap, thread, time::Duration};
+
+use crate::{
+    device::{
+        convert,
+        ds2450::{self, AlarmLimits, Channel, Ds2450},
+        thermometer::{self, Temperature},
+    },
+    proto::command::SlaveId,
+    transport::{Error, Transport},
+};
+
+/// TL and TH of DS18B20 style thermometers in °C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThermometerLimits {
+    pub low: i8,
+    pub high: i8,
+}
+
+/// Alarm settings of a single device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceLimits {
+    Thermometer(ThermometerLimits),
+    Adc([AlarmLimits; 4]),
+}
+
+/// Thresholds per device family with overrides for individual devices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmConfig {
+    thermometer: Option<ThermometerLimits>,
+    adc: Option<[AlarmLimits; 4]>,
+    devices: BTreeMap<SlaveId, DeviceLimits>,
+}
+
+impl AlarmConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits for all thermometers.
+    pub fn with_thermometers(self, low: i8, high: i8) -> Self {
+        Self {
+            thermometer: Some(ThermometerLimits { low, high }),
+            ..self
+        }
+    }
+
+    /// Limits for all channels of all DS2450s.
+    pub fn with_adc(self, limits: [AlarmLimits; 4]) -> Self {
+        Self {
+            adc: Some(limits),
+            ..self
+        }
+    }
+
+    /// Limits for `id`, taking precedence over the family settings.
+    pub fn with_device(mut self, id: SlaveId, limits: DeviceLimits) -> Self {
+        self.devices.insert(id, limits);
+        self
+    }
+
+    /// Limits applying to `id`, if any.
+    pub fn limits(&self, id: SlaveId) -> Option<DeviceLimits> {
+        if let Some(limits) = self.devices.get(&id) {
+            return Some(*limits);
+        }
+        match id.family() {
+            family if thermometer::is_thermometer(family) => {
+                self.thermometer.map(DeviceLimits::Thermometer)
+            }
+            ds2450::FAMILY => self.adc.map(DeviceLimits::Adc),
+            _ => None,
+        }
+    }
+}
+
+/// Limit crossed by a measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bound {
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlarmReport {
+    Temperature {
+        id: SlaveId,
+        temperature: Temperature,
+        limits: ThermometerLimits,
+        bound: Bound,
+    },
+    /// A DS2450 channel out of range, one report per channel.
+    Voltage {
+        id: SlaveId,
+        channel: Channel,
+        raw: u16,
+        volts: f32,
+        bound: Bound,
+    },
+    /// A device answering the alarm search that has no decoder here.
+    Other(SlaveId),
+}
+
+impl AlarmReport {
+    pub fn id(&self) -> SlaveId {
+        match self {
+            AlarmReport::Temperature { id, .. } | AlarmReport::Voltage { id, .. } => *id,
+            AlarmReport::Other(id) => *id,
+        }
+    }
+}
+
+/// Alarm workflow for the bus of a single master.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlarmScan {
+    master: u32,
+    conversion_time: Duration,
+}
+
+impl AlarmScan {
+    pub fn new(master: u32) -> Self {
+        Self {
+            master,
+            conversion_time: convert::conversion_time(),
+        }
+    }
+
+    /// Time to wait between starting the conversions and the alarm search.
+    pub fn with_conversion_time(self, conversion_time: Duration) -> Self {
+        Self {
+            conversion_time,
+            ..self
+        }
+    }
+
+    pub fn master(&self) -> u32 {
+        self.master
+    }
+
+    /// Programs the limits of `config` into every matching device on the bus
+    /// and returns the devices configured. Thermometers keep their resolution,
+    /// the thresholds are not copied to their EEPROM.
+    pub fn configure(
+        &self,
+        t: &mut impl Transport,
+        config: &AlarmConfig,
+    ) -> Result<Vec<SlaveId>, Error> {
+        let mut configured = Vec::new();
+        for id in t.search(self.master, false)? {
+            match (id.family(), config.limits(id)) {
+                (family, Some(DeviceLimits::Thermometer(limits)))
+                    if thermometer::is_thermometer(family) =>
+                {
+                    let resolution = thermometer::read_scratchpad(t, id)?.resolution();
+                    thermometer::write_scratchpad(t, id, limits.high, limits.low, resolution)?;
+                }
+                (ds2450::FAMILY, Some(DeviceLimits::Adc(limits))) => {
+                    let adc = Ds2450::new(id);
+                    for channel in Channel::ALL {
+                        adc.set_alarm(t, channel, limits[channel.index()])?;
+                    }
+                }
+                (_, Some(_)) => return Err(Error::Device("alarm limits do not match device")),
+                (_, None) => continue,
+            }
+            configured.push(id);
+        }
+        Ok(configured)
+    }
+
+    /// Starts a conversion on all thermometers and A/D converters at once and
+    /// waits for it to complete.
+    pub fn convert(&self, t: &mut impl Transport) -> Result<(), Error> {
+        self.start_conversion(t)?;
+        thread::sleep(self.conversion_time);
+        Ok(())
+    }
+
+    /// Starts a conversion on all thermometers and A/D converters at once
+    /// without waiting for it, see [`convert::start`].
+    pub fn start_conversion(&self, t: &mut impl Transport) -> Result<(), Error> {
+        convert::start(t, self.master)
+    }
+
+    /// Converts, runs an alarm search and reports the devices out of range.
+    pub fn scan(&self, t: &mut impl Transport) -> Result<Vec<AlarmReport>, Error> {
+        self.convert(t)?;
+        let mut reports = Vec::new();
+        for id in t.search(self.master, true)? {
+            match id.family() {
+                family if thermometer::is_thermometer(family) => {
+                    let pad = thermometer::read_scratchpad(t, id)?;
+                    let limits = ThermometerLimits {
+                        low: pad.alarm_low(),
+                        high: pad.alarm_high(),
+                    };
+                    // the device compares the integer part only
+                    let degrees = pad.temperature().raw() >> 4;
+                    let bound = if degrees >= limits.high.into() {
+                        Bound::High
+                    } else if degrees <= limits.low.into() {
+                        Bound::Low
+                    } else {
+                        continue;
+                    };
+                    reports.push(AlarmReport::Temperature {
+                        id,
+                        temperature: pad.temperature(),
+                        limits,
+                        bound,
+                    });
+                }
+                ds2450::FAMILY => {
+                    let adc = Ds2450::new(id);
+                    let status = adc.read_status(t)?;
+                    let raw = adc.read_conversions(t)?;
+                    for channel in Channel::ALL {
+                        let status = status[channel.index()];
+                        let raw = raw[channel.index()];
+                        let bounds = [
+                            (status.alarm_low() && status.alarm_low_enabled(), Bound::Low),
+                            (
+                                status.alarm_high() && status.alarm_high_enabled(),
+                                Bound::High,
+                            ),
+                        ];
+                        for (_, bound) in bounds.into_iter().filter(|(alarm, _)| *alarm) {
+                            reports.push(AlarmReport::Voltage {
+                                id,
+                                channel,
+                                raw,
+                                volts: status.range().voltage(raw),
+                                bound,
+                            });
+                        }
+                    }
+                }
+                _ => reports.push(AlarmReport::Other(id)),
+            }
+        }
+        Ok(reports)
+    }
+}
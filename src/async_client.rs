@@ -0,0 +1,212 @@
+//! Async client for tokio, talking to the kernel's w1 core over a netlink
+//! socket.
+//!
+//! Requests may run concurrently: a background task receives all replies and
+//! routes them by their sequence number to the request waiting for them.
+//! A request that times out or whose future is dropped unregisters its
+//! sequence number, so replies arriving late are dropped instead of reaching
+//! a later request.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use w1_netlink::{async_client::AsyncW1Client, proto::message::W1NetlinkMessage};
+//! # async fn run() -> Result<(), w1_netlink::transport::Error> {
+//! let client = AsyncW1Client::new()?.with_timeout(Duration::from_secs(1));
+//! let masters = client.transact(W1NetlinkMessage::ListMasters(None)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use netlink_sys::{protocols::NETLINK_CONNECTOR, Socket, SocketAddr};
+use tokio::{
+    io::unix::AsyncFd,
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    client::{decode_messages, encode_request, expected_acks, split_status, Datagram},
+    proto::{connector::NlConnectorMessage, message::W1NetlinkMessage, Serializable},
+    transport::{is_overflow, Error},
+};
+
+/// Large enough for any message generated by the w1 core, which stays below a page.
+const RECV_BUFFER_LEN: usize = 65536;
+
+/// Replies to one connector message, or the error status it carried.
+type Replies = Result<Vec<W1NetlinkMessage>, Error>;
+
+struct Shared {
+    socket: AsyncFd<Socket>,
+    seq: AtomicU32,
+    /// Requests waiting for replies, by sequence number.
+    pending: Mutex<HashMap<u32, UnboundedSender<Replies>>>,
+}
+
+impl Shared {
+    fn next_seq(&self) -> u32 {
+        self.seq.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+    }
+
+    async fn send(&self, buf: &[u8]) -> io::Result<()> {
+        loop {
+            let mut ready = self.socket.writable().await?;
+            match ready.try_io(|socket| socket.get_ref().send(buf, 0)) {
+                Ok(result) => return result.map(|_| ()),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut ready = self.socket.readable().await?;
+            match ready.try_io(|socket| socket.get_ref().recv(&mut &mut *buf, 0)) {
+                Ok(result) => return result.map(|n| n.min(buf.len())),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Passes the connector messages of a datagram to the requests waiting
+    /// for them.
+    fn dispatch(&self, datagram: &[u8]) {
+        let pending = self.pending.lock().unwrap();
+        for cmsg in Datagram::new(datagram) {
+            // a malformed datagram cannot be routed, the requests time out
+            let Ok(cmsg) = cmsg else {
+                break;
+            };
+            // late replies of cancelled requests end here
+            let Some(request) = pending.get(&cmsg.header.seq) else {
+                continue;
+            };
+            let _ = request.send(decode_messages(cmsg));
+        }
+    }
+}
+
+/// Receives until the socket fails. Waiting requests then see their channel
+/// closed.
+async fn receive(shared: Arc<Shared>) {
+    let mut buf = vec![0; RECV_BUFFER_LEN];
+    loop {
+        match shared.recv(&mut buf).await.map_err(Error::Io) {
+            Ok(n) => shared.dispatch(&buf[..n]),
+            // replies were dropped, their requests time out
+            Err(err) if is_overflow(&err) => {}
+            Err(_) => break,
+        }
+    }
+    shared.pending.lock().unwrap().clear();
+}
+
+/// Unregisters a request when it completes or is cancelled.
+struct Registration<'a> {
+    shared: &'a Shared,
+    seq: u32,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.shared.pending.lock().unwrap().remove(&self.seq);
+    }
+}
+
+pub struct AsyncW1Client {
+    shared: Arc<Shared>,
+    receiver: JoinHandle<()>,
+    timeout: Option<Duration>,
+}
+
+impl AsyncW1Client {
+    /// Opens a connector socket, connects it to the kernel and starts
+    /// receiving. Must be called within a tokio runtime.
+    pub fn new() -> Result<Self, Error> {
+        let mut socket = Socket::new(NETLINK_CONNECTOR)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        socket.set_non_blocking(true)?;
+        let shared = Arc::new(Shared {
+            // SAFETY: the socket owns its descriptor and closes it only when
+            // dropped along with the `AsyncFd`
+            socket: unsafe { AsyncFd::register(socket) }.map_err(io::Error::from)?,
+            seq: AtomicU32::new(0),
+            pending: Mutex::new(HashMap::new()),
+        });
+        let receiver = tokio::spawn(receive(shared.clone()));
+        Ok(Self {
+            shared,
+            receiver,
+            timeout: None,
+        })
+    }
+
+    /// Fails requests with [`io::ErrorKind::TimedOut`] if their replies did
+    /// not arrive within `timeout`. No timeout by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends a single message to the w1 core and returns all replies
+    /// carrying data, like [`Transport::transact`](crate::transport::Transport::transact).
+    /// Dropping the future cancels the request.
+    pub async fn transact(&self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.request(msg))
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "no reply from the w1 core")
+                })?,
+            None => self.request(msg).await,
+        }
+    }
+
+    async fn request(&self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        let cmsg = NlConnectorMessage::new(self.shared.next_seq(), [msg]);
+        cmsg.validate()?;
+        let seq = cmsg.header.seq;
+        let mut pending = expected_acks(&cmsg);
+
+        let (sender, mut replies) = mpsc::unbounded_channel();
+        self.shared.pending.lock().unwrap().insert(seq, sender);
+        let _registration = Registration {
+            shared: &self.shared,
+            seq,
+        };
+        self.shared.send(&encode_request(cmsg)).await?;
+
+        let mut data = Vec::new();
+        while pending > 0 {
+            let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "netlink socket failed");
+            for reply in replies.recv().await.ok_or_else(closed)?? {
+                let (acked, reply) = split_status(reply);
+                pending = pending.saturating_sub(acked);
+                data.extend(reply);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Number of requests waiting for replies.
+    pub fn pending(&self) -> usize {
+        self.shared.pending.lock().unwrap().len()
+    }
+}
+
+impl Drop for AsyncW1Client {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
@@ -0,0 +1,269 @@
+//! Splitting of requests exceeding the connector message size limit.
+//!
+//! The kernel drops connector messages above
+//! [`CONNECTOR_MAX_MSG_SIZE`]. A [`Batch`] packs any number of w1 messages
+//! into compliant connector messages with sequential sequence numbers. Long
+//! master command lists are continued in further messages for the same
+//! target and write, read and touch commands with too much data are cut into
+//! chunks. [`Batch::reassemble`] joins the replies to chunked reads and
+//! touches again, so callers see one reply per command they asked for.
+//!
+//! The w1 core releases the bus between messages, so another process may
+//! access it in between. Slave commands are never split: the w1 core resets
+//! the bus and selects the slave at the start of every slave command, which
+//! would end the function command the continuation belongs to. A slave
+//! command exceeding a connector message is refused instead.
+
+use std::mem;
+
+use crate::proto::{
+    check_len,
+    command::W1NetlinkCommand,
+    connector::{NlConnectorMessage, CONNECTOR_MAX_MSG_SIZE},
+    message::W1NetlinkMessage,
+    Serializable, SerializeError,
+};
+
+/// Room for w1 messages in a single connector message.
+pub const MAX_PAYLOAD_LEN: usize =
+    CONNECTOR_MAX_MSG_SIZE - NlConnectorMessage::<W1NetlinkMessage>::HEADER_LEN;
+
+/// Largest data chunk of a command that is sent in a message of its own.
+pub const MAX_DATA_LEN: usize =
+    MAX_PAYLOAD_LEN - W1NetlinkMessage::HEADER_LEN - W1NetlinkCommand::HEADER_LEN;
+
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// Connector messages to send in order.
+    pub requests: Vec<NlConnectorMessage<W1NetlinkMessage>>,
+    /// Number of chunks of every read and touch command, in order.
+    chunks: Vec<usize>,
+}
+
+impl Batch {
+    /// Splits `msgs` into connector messages, numbered from `seq`.
+    pub fn new(
+        seq: u32,
+        msgs: impl IntoIterator<Item = W1NetlinkMessage>,
+    ) -> Result<Self, SerializeError> {
+        let mut packer = Packer {
+            seq,
+            requests: Vec::new(),
+            current: Vec::new(),
+            used: 0,
+        };
+        let mut chunks = Vec::new();
+        for msg in msgs {
+            let (target, cmds) = match msg {
+                W1NetlinkMessage::MasterCommand { target, cmds } if !cmds.is_empty() => {
+                    (target, cmds)
+                }
+                msg @ W1NetlinkMessage::SlaveCommand { .. } => {
+                    check_len("Slave command", msg.buffer_len(), MAX_PAYLOAD_LEN)?;
+                    packer.push(msg);
+                    continue;
+                }
+                msg => {
+                    packer.push(msg);
+                    continue;
+                }
+            };
+
+            let part = |cmds| W1NetlinkMessage::MasterCommand { target, cmds };
+            let mut cmds_part = Vec::new();
+            let mut len = W1NetlinkMessage::HEADER_LEN;
+            for cmd in cmds {
+                // replies without data are taken for status replies
+                let chunked = is_data(&cmd);
+                if chunked {
+                    chunks.push(0);
+                }
+                let mut rest = Some(cmd);
+                while let Some(cmd) = rest.take() {
+                    let room = packer
+                        .room()
+                        .saturating_sub(len + W1NetlinkCommand::HEADER_LEN);
+                    let (chunk, left) = split_command(cmd, room);
+                    rest = left;
+                    let Some(chunk) = chunk else {
+                        // continue in a new connector message
+                        if !cmds_part.is_empty() {
+                            packer.push(part(mem::take(&mut cmds_part)));
+                            len = W1NetlinkMessage::HEADER_LEN;
+                        }
+                        packer.flush();
+                        continue;
+                    };
+                    if chunked {
+                        *chunks.last_mut().unwrap() += 1;
+                    }
+                    len += chunk.buffer_len();
+                    cmds_part.push(chunk);
+                }
+            }
+            packer.push(part(cmds_part));
+        }
+        packer.flush();
+
+        for request in &packer.requests {
+            request.validate()?;
+        }
+        Ok(Self {
+            requests: packer.requests,
+            chunks,
+        })
+    }
+
+    /// Joins the replies to chunked commands. `replies` are the data replies
+    /// to all requests in the order they were sent.
+    pub fn reassemble(&self, replies: Vec<W1NetlinkMessage>) -> Vec<W1NetlinkMessage> {
+        let mut chunks = self.chunks.iter().copied();
+        let mut out: Vec<W1NetlinkMessage> = Vec::new();
+        // reply collecting chunks as message and command index, and the
+        // number of chunks still expected
+        let mut open: Option<(usize, usize, usize)> = None;
+        for reply in replies {
+            out.push(reply);
+            let m = out.len() - 1;
+            let Some(cmds) = commands_mut(&mut out[m]) else {
+                continue;
+            };
+            let mut kept: Vec<W1NetlinkCommand> = Vec::new();
+            for cmd in mem::take(cmds) {
+                match (open, cmd) {
+                    (
+                        Some((om, oc, left)),
+                        W1NetlinkCommand::Read(Some(data)) | W1NetlinkCommand::Touch(data),
+                    ) => {
+                        let first = match om == m {
+                            true => &mut kept[oc],
+                            false => &mut commands_mut(&mut out[om]).unwrap()[oc],
+                        };
+                        if let W1NetlinkCommand::Read(Some(buf)) | W1NetlinkCommand::Touch(buf) =
+                            first
+                        {
+                            buf.extend(data);
+                        }
+                        open = (left > 1).then_some((om, oc, left - 1));
+                    }
+                    (None, cmd) if is_data(&cmd) => {
+                        let n = chunks.next().unwrap_or(1);
+                        if n > 1 {
+                            open = Some((m, kept.len(), n - 1));
+                        }
+                        kept.push(cmd);
+                    }
+                    (_, cmd) => kept.push(cmd),
+                }
+            }
+            let cmds = commands_mut(&mut out[m]).unwrap();
+            *cmds = kept;
+        }
+        out.retain(|msg| match msg {
+            W1NetlinkMessage::MasterCommand { cmds, .. }
+            | W1NetlinkMessage::SlaveCommand { cmds, .. } => !cmds.is_empty(),
+            _ => true,
+        });
+        out
+    }
+}
+
+struct Packer {
+    seq: u32,
+    requests: Vec<NlConnectorMessage<W1NetlinkMessage>>,
+    current: Vec<W1NetlinkMessage>,
+    used: usize,
+}
+
+impl Packer {
+    fn room(&self) -> usize {
+        MAX_PAYLOAD_LEN - self.used
+    }
+
+    /// Adds `msg` to the current connector message, starting a new one if it
+    /// does not fit.
+    fn push(&mut self, msg: W1NetlinkMessage) {
+        let len = msg.buffer_len();
+        if len > self.room() {
+            self.flush();
+        }
+        self.used += len;
+        self.current.push(msg);
+    }
+
+    fn flush(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        let payload = mem::take(&mut self.current);
+        self.requests
+            .push(NlConnectorMessage::new(self.seq, payload));
+        self.seq = self.seq.wrapping_add(1);
+        self.used = 0;
+    }
+}
+
+/// Takes as much of `cmd` as fits into `room` bytes of data. Commands are only
+/// cut if they do not fit into a connector message of their own, otherwise
+/// `None` asks for a new one. Returns the part to send now and the rest.
+fn split_command(
+    cmd: W1NetlinkCommand,
+    room: usize,
+) -> (Option<W1NetlinkCommand>, Option<W1NetlinkCommand>) {
+    let len = cmd.buffer_len() - W1NetlinkCommand::HEADER_LEN;
+    if len <= room {
+        return (Some(cmd), None);
+    }
+    if len <= MAX_DATA_LEN || room == 0 {
+        return (None, Some(cmd));
+    }
+    let cut = |mut data: Vec<u8>| {
+        let rest = data.split_off(room);
+        (data, rest)
+    };
+    match cmd {
+        W1NetlinkCommand::Write(data) => {
+            let (data, rest) = cut(data);
+            (
+                Some(W1NetlinkCommand::Write(data)),
+                Some(W1NetlinkCommand::Write(rest)),
+            )
+        }
+        W1NetlinkCommand::Read(Some(data)) => {
+            let (data, rest) = cut(data);
+            (
+                Some(W1NetlinkCommand::Read(Some(data))),
+                Some(W1NetlinkCommand::Read(Some(rest))),
+            )
+        }
+        W1NetlinkCommand::Touch(data) => {
+            let (data, rest) = cut(data);
+            (
+                Some(W1NetlinkCommand::Touch(data)),
+                Some(W1NetlinkCommand::Touch(rest)),
+            )
+        }
+        // too large for any message, rejected when validating, an empty
+        // connector message has room for MAX_DATA_LEN
+        cmd => match room == MAX_DATA_LEN {
+            true => (Some(cmd), None),
+            false => (None, Some(cmd)),
+        },
+    }
+}
+
+/// Whether `cmd` is a read or touch carrying data.
+fn is_data(cmd: &W1NetlinkCommand) -> bool {
+    match cmd {
+        W1NetlinkCommand::Read(Some(data)) | W1NetlinkCommand::Touch(data) => !data.is_empty(),
+        _ => false,
+    }
+}
+
+fn commands_mut(msg: &mut W1NetlinkMessage) -> Option<&mut Vec<W1NetlinkCommand>> {
+    match msg {
+        W1NetlinkMessage::MasterCommand { cmds, .. }
+        | W1NetlinkMessage::SlaveCommand { cmds, .. } => Some(cmds),
+        _ => None,
+    }
+}
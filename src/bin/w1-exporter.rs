@@ -0,0 +1,84 @@
+//! Prometheus exporter for all 1-Wire devices known to the kernel's w1 core.
+//!
+//! ```text
+//! $ w1-exporter --listen 0.0.0.0:9724 --interval 30
+//! $ curl -s localhost:9724/metrics | grep temperature
+//! w1_temperature_celsius{master="1",rom="28-0000056c3a1f",family="DS18B20"} 21.3125
+//! ```
+
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use clap::Parser;
+use w1_netlink::{client::W1Client, exporter::Collector, registry::Registry};
+
+#[derive(Parser)]
+#[command(version, about = "Export 1-Wire sensor readings to Prometheus")]
+struct Cli {
+    /// Address to serve `/metrics` on
+    #[arg(short, long, default_value = "0.0.0.0:9724")]
+    listen: String,
+
+    /// Seconds between scrapes of all buses
+    #[arg(short, long, default_value_t = 60)]
+    interval: u64,
+
+    /// Device registry naming the devices, in TOML or YAML
+    #[arg(short, long)]
+    registry: Option<PathBuf>,
+
+    /// Seconds to wait for the replies of a request to the w1 core
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let registry = match cli.registry.as_deref().map(Registry::load).transpose() {
+        Ok(registry) => registry.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("w1-exporter: cannot load registry: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match TcpListener::bind(&cli.listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("w1-exporter: cannot listen on {}: {err}", cli.listen);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut client = match W1Client::new() {
+        Ok(client) => client.with_timeout(Duration::from_secs(cli.timeout)),
+        Err(err) => {
+            eprintln!("w1-exporter: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let page = Arc::new(Mutex::new(String::new()));
+    let scraped = page.clone();
+    let interval = Duration::from_secs(cli.interval);
+    thread::spawn(move || {
+        let mut collector = Collector::new().with_aliases(registry.aliases());
+        loop {
+            if let Err(err) = collector.scrape(&mut client) {
+                eprintln!("w1-exporter: scrape failed: {err}");
+            }
+            *scraped.lock().unwrap() = collector.render();
+            thread::sleep(interval);
+        }
+    });
+
+    w1_netlink::exporter::serve(
+        &listener,
+        || page.lock().unwrap().clone(),
+        |err| eprintln!("w1-exporter: accept failed: {err}"),
+    )
+}
@@ -0,0 +1,201 @@
+//! Publishes 1-Wire devices to an MQTT broker with Home Assistant discovery.
+//!
+//! ```text
+//! $ w1-mqtt --host localhost --interval 30 &
+//! $ mosquitto_sub -v -t 'w1/#'
+//! w1/status online
+//! w1/28-0000056c3a1f/temperature 21.3125
+//! w1/28-0000056c3a1f/status online
+//! $ mosquitto_pub -t w1/3a-000000a1b2c3/output_a/set -m ON
+//! ```
+
+use std::{
+    path::PathBuf,
+    process::ExitCode,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use w1_netlink::{
+    client::W1Client,
+    mqtt::{Bridge, Publish},
+    proto::message::W1NetlinkMessage,
+    registry::Registry,
+    transport::Error,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Publish 1-Wire devices to MQTT and Home Assistant")]
+struct Cli {
+    /// Broker host
+    #[arg(long, default_value = "localhost")]
+    host: String,
+
+    #[arg(short, long, default_value_t = 1883)]
+    port: u16,
+
+    #[arg(short, long)]
+    username: Option<String>,
+
+    #[arg(long, requires = "username")]
+    password: Option<String>,
+
+    #[arg(long, default_value = "w1-mqtt")]
+    client_id: String,
+
+    /// Prefix of state and command topics
+    #[arg(long, default_value = "w1")]
+    base_topic: String,
+
+    /// Home Assistant discovery prefix
+    #[arg(long, default_value = "homeassistant")]
+    discovery_prefix: String,
+
+    /// Seconds between polls of all buses
+    #[arg(short, long, default_value_t = 60)]
+    interval: u64,
+
+    /// Device registry naming the devices, in TOML or YAML
+    #[arg(short, long)]
+    registry: Option<PathBuf>,
+
+    /// Seconds to wait for the replies of a request to the w1 core
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+enum Input {
+    /// The broker accepted a (re)connection.
+    Connected,
+    Command {
+        topic: String,
+        payload: Vec<u8>,
+    },
+    Event(W1NetlinkMessage),
+}
+
+fn publish(client: &Client, msgs: Vec<Publish>) {
+    for msg in msgs {
+        if let Err(err) = client.publish(msg.topic, QoS::AtLeastOnce, msg.retain, msg.payload) {
+            eprintln!("w1-mqtt: {err}");
+        }
+    }
+}
+
+/// Forwards hotplug events of the w1 core.
+fn watch(inputs: Sender<Input>) -> Result<(), Error> {
+    let mut client = W1Client::new()?;
+    client.subscribe()?;
+    loop {
+        for cmsg in client.recv()? {
+            for msg in cmsg.payload {
+                if inputs.send(Input::Event(msg)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let registry = match cli.registry.as_deref().map(Registry::load).transpose() {
+        Ok(registry) => registry.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("w1-mqtt: cannot load registry: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut bridge = Bridge::new()
+        .with_base_topic(&cli.base_topic)
+        .with_discovery_prefix(&cli.discovery_prefix)
+        .with_aliases(registry.aliases());
+    let mut w1 = match W1Client::new() {
+        Ok(client) => client.with_timeout(Duration::from_secs(cli.timeout)),
+        Err(err) => {
+            eprintln!("w1-mqtt: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut options = MqttOptions::new(&cli.client_id, &cli.host, cli.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        bridge.availability_topic(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &cli.username {
+        options.set_credentials(username, cli.password.as_deref().unwrap_or_default());
+    }
+    let (client, mut connection) = Client::new(options, 64);
+
+    let (inputs, received) = mpsc::channel();
+    let events = inputs.clone();
+    thread::spawn(move || {
+        if let Err(err) = watch(events) {
+            eprintln!("w1-mqtt: no hotplug events: {err}");
+        }
+    });
+    thread::spawn(move || {
+        for event in connection.iter() {
+            let input = match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => Input::Connected,
+                Ok(Event::Incoming(Packet::Publish(msg))) => Input::Command {
+                    topic: msg.topic,
+                    payload: msg.payload.to_vec(),
+                },
+                Ok(_) => continue,
+                Err(err) => {
+                    // the next iteration reconnects
+                    eprintln!("w1-mqtt: {err}");
+                    thread::sleep(Duration::from_secs(5));
+                    continue;
+                }
+            };
+            if inputs.send(input).is_err() {
+                break;
+            }
+        }
+    });
+
+    let interval = Duration::from_secs(cli.interval);
+    let mut next_poll = Instant::now();
+    loop {
+        match received.recv_timeout(next_poll.saturating_duration_since(Instant::now())) {
+            Ok(Input::Connected) => {
+                if let Err(err) = client.subscribe(bridge.command_filter(), QoS::AtLeastOnce) {
+                    eprintln!("w1-mqtt: {err}");
+                }
+                publish(
+                    &client,
+                    vec![Publish {
+                        topic: bridge.availability_topic(),
+                        payload: "online".to_string(),
+                        retain: true,
+                    }],
+                );
+                publish(&client, bridge.announcements());
+            }
+            Ok(Input::Command { topic, payload }) => {
+                match bridge.command(&mut w1, &topic, &payload) {
+                    Ok(msgs) => publish(&client, msgs),
+                    Err(err) => eprintln!("w1-mqtt: {topic}: {err}"),
+                }
+            }
+            Ok(Input::Event(msg)) => publish(&client, bridge.handle_event(&msg)),
+            Err(RecvTimeoutError::Timeout) => {
+                match bridge.poll(&mut w1) {
+                    Ok(msgs) => publish(&client, msgs),
+                    Err(err) => eprintln!("w1-mqtt: poll failed: {err}"),
+                }
+                next_poll = Instant::now() + interval;
+            }
+            Err(RecvTimeoutError::Disconnected) => return ExitCode::FAILURE,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Prints the w1 traffic in an `nlmon` capture, one connector message per line:
+//!
+//! ```text
+//! $ w1-pcap w1.pcapng
+//! 1700000000.000100 > seq=2 ack=1 SLAVE_CMD 28-0000056c3a1f: RESET; WRITE 55 28 1f 3a 6c 05 00 00 ef be; READ 9
+//! 1700000000.012400 < seq=2 ack=3 SLAVE_CMD 28-0000056c3a1f: READ 9 -> 50 05 4b 46 7f ff 0c 10 1c [crc ok]
+//! ```
+//!
+//! `>` marks requests to the kernel and `<` messages to userspace.
+
+use std::{env, process::ExitCode};
+
+use w1_netlink::capture::{self, Direction};
+
+fn main() -> ExitCode {
+    let Some(path) = env::args_os().nth(1) else {
+        eprintln!("usage: w1-pcap <capture.pcap|capture.pcapng>");
+        return ExitCode::FAILURE;
+    };
+    let frames = match capture::read(&path) {
+        Ok(frames) => frames,
+        Err(err) => {
+            eprintln!("{}: {err}", path.to_string_lossy());
+            return ExitCode::FAILURE;
+        }
+    };
+    for frame in frames {
+        let time = format!(
+            "{}.{:06}",
+            frame.timestamp.as_secs(),
+            frame.timestamp.subsec_micros()
+        );
+        let direction = match frame.direction {
+            Direction::ToKernel => ">",
+            Direction::ToUser => "<",
+            Direction::Unknown(_) => "?",
+        };
+        match frame.messages {
+            Ok(msgs) => {
+                for msg in msgs {
+                    // requests carry placeholder read buffers
+                    match frame.direction {
+                        Direction::ToKernel => println!("{time} {direction} {msg:#}"),
+                        _ => println!("{time} {direction} {msg}"),
+                    }
+                }
+            }
+            Err(err) => println!("{time} {direction} error: {err}"),
+        }
+    }
+    ExitCode::SUCCESS
+}
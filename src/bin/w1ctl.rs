@@ -0,0 +1,430 @@
+//! Command line access to the kernel's w1 core over netlink.
+//!
+//! ```text
+//! $ w1ctl slaves 1
+//! ROM              FAMILY
+//! 28-0000056c3a1f  DS18B20
+//! $ w1ctl --registry devices.toml check
+//! ROM              FINDING
+//! 28-0000056c3a1f  missing device 28-0000056c3a1f
+//! $ w1ctl read 28-0000056c3a1f 9 --write be
+//! 50 05 4b 46 7f ff 0c 10 1c
+//! $ w1ctl -o json temp 28-0000056c3a1f
+//! [{"rom":"28-0000056c3a1f","celsius":21.3125}]
+//! ```
+//!
+//! Commands on a slave reset the bus and select the slave first, commands on
+//! a master work on the bus as it is. With a device registry, slaves are
+//! listed with their names and `watch` reports unknown and missing devices.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use w1_netlink::{
+    client::W1Client,
+    device::{family_name, thermometer},
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        message::W1NetlinkMessage,
+    },
+    registry::{Finding, Registry},
+    topology::Topology,
+    transport::{read, read_data, touch, write, Error, Transport},
+};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Control 1-Wire buses through the kernel's w1 netlink interface"
+)]
+struct Cli {
+    #[arg(short, long, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+
+    /// Device registry in TOML or YAML
+    #[arg(short, long, global = true)]
+    registry: Option<PathBuf>,
+
+    /// Seconds to wait for the replies of a request to the w1 core
+    #[arg(long, default_value_t = 10, global = true)]
+    timeout: u64,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the bus masters
+    Masters,
+    /// List the slaves registered with a master
+    Slaves { master: u32 },
+    /// Search the bus of a master
+    Search {
+        /// Only devices with an alarm condition
+        #[arg(long)]
+        alarm: bool,
+        master: u32,
+    },
+    /// Reset the bus of a master
+    Reset { master: u32 },
+    /// Read bytes
+    Read {
+        target: Target,
+        len: usize,
+        /// Hex bytes to write first, e.g. `be`
+        #[arg(short, long)]
+        write: Option<Bytes>,
+    },
+    /// Write hex bytes, e.g. `cc 44` or `cc44`
+    Write {
+        target: Target,
+        #[arg(required = true)]
+        data: Vec<Bytes>,
+    },
+    /// Write hex bytes and print the bits sampled in the same time slots
+    Touch {
+        target: Target,
+        #[arg(required = true)]
+        data: Vec<Bytes>,
+    },
+    /// Register a slave with a master
+    AddSlave { master: u32, rom: SlaveId },
+    /// Unregister a slave from a master
+    RemoveSlave { master: u32, rom: SlaveId },
+    /// Convert and read the temperature of thermometers
+    Temp {
+        #[arg(required = true)]
+        roms: Vec<SlaveId>,
+    },
+    /// Print hotplug events until interrupted
+    Watch,
+    /// Search all buses and compare the devices found with the registry
+    Check,
+}
+
+/// A master id or a slave ROM.
+#[derive(Clone, Copy)]
+enum Target {
+    Master(u32),
+    Slave(SlaveId),
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('-') {
+            return s.parse().map(Target::Slave).map_err(|err| format!("{err}"));
+        }
+        s.parse()
+            .map(Target::Master)
+            .map_err(|_| format!("expected a master id or slave ROM, got {s}"))
+    }
+}
+
+/// Hex bytes, separated by spaces, colons or commas or not at all.
+#[derive(Clone)]
+struct Bytes(Vec<u8>);
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Bytes)
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let digits: String = s
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !matches!(c, ' ' | ':' | ','))
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in {s}"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| format!("invalid hex {s}")))
+        .collect()
+}
+
+fn concat(data: Vec<Bytes>) -> Vec<u8> {
+    data.into_iter().flat_map(|bytes| bytes.0).collect()
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            let sep = if i > 0 { " " } else { "" };
+            write!(f, "{sep}{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Slave {
+    rom: SlaveId,
+    family: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+impl Slave {
+    fn new(rom: SlaveId, registry: Option<&Registry>) -> Self {
+        Self {
+            rom,
+            family: family_name(rom.family()),
+            name: name(registry, rom),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Reading {
+    rom: SlaveId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    celsius: f32,
+}
+
+fn name(registry: Option<&Registry>, rom: SlaveId) -> Option<String> {
+    registry?.name(rom).map(str::to_string)
+}
+
+#[derive(Serialize)]
+struct Data {
+    data: String,
+}
+
+/// Prints rows as aligned columns or as a JSON array.
+fn print<T: Serialize>(
+    output: Output,
+    items: &[T],
+    header: &[&str],
+    row: impl Fn(&T) -> Vec<String>,
+) {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string(items).unwrap()),
+        Output::Table => {
+            let rows: Vec<Vec<String>> = items.iter().map(row).collect();
+            let widths: Vec<usize> = (0..header.len())
+                .map(|col| {
+                    rows.iter()
+                        .map(|row| row[col].len())
+                        .chain([header[col].len()])
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+            let header = header.iter().map(|h| h.to_string()).collect();
+            for row in [header].iter().chain(&rows) {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect();
+                println!("{}", line.join("  ").trim_end());
+            }
+        }
+    }
+}
+
+fn print_slaves(output: Output, ids: Vec<SlaveId>, registry: Option<&Registry>) {
+    let slaves: Vec<Slave> = ids.into_iter().map(|id| Slave::new(id, registry)).collect();
+    let header: &[&str] = match registry {
+        Some(_) => &["ROM", "FAMILY", "NAME"],
+        None => &["ROM", "FAMILY"],
+    };
+    print(output, &slaves, header, |slave| {
+        let mut row = vec![
+            slave.rom.to_string(),
+            slave.family.unwrap_or("-").to_string(),
+        ];
+        if registry.is_some() {
+            row.push(slave.name.clone().unwrap_or_else(|| "-".to_string()));
+        }
+        row
+    });
+}
+
+fn print_data(output: Output, data: &[u8]) {
+    match output {
+        Output::Table => println!("{}", Hex(data)),
+        Output::Json => {
+            let data = Data {
+                data: data.iter().map(|b| format!("{b:02x}")).collect(),
+            };
+            println!("{}", serde_json::to_string(&data).unwrap());
+        }
+    }
+}
+
+/// Runs `cmds` on the target, selecting a slave with a reset first.
+fn run(
+    client: &mut W1Client,
+    target: Target,
+    mut cmds: Vec<W1NetlinkCommand>,
+) -> Result<Vec<W1NetlinkCommand>, Error> {
+    match target {
+        Target::Master(master) => client.master_command(master, cmds),
+        Target::Slave(id) => {
+            cmds.insert(0, W1NetlinkCommand::Reset);
+            client.slave_command(id, cmds)
+        }
+    }
+}
+
+fn print_finding(output: Output, finding: &Finding) {
+    match output {
+        Output::Table => eprintln!("w1ctl: {finding}"),
+        Output::Json => println!("{}", serde_json::to_string(finding).unwrap()),
+    }
+}
+
+fn execute(cli: Cli, registry: Option<Registry>) -> Result<(), Error> {
+    let output = cli.output;
+    let registry = registry.as_ref();
+    let mut client = W1Client::new()?.with_timeout(Duration::from_secs(cli.timeout));
+    match cli.command {
+        Command::Masters => {
+            let masters = client.list_masters()?;
+            print(output, &masters, &["MASTER"], |id| vec![id.to_string()]);
+        }
+        Command::Slaves { master } => {
+            print_slaves(output, client.list_slaves(master)?, registry);
+        }
+        Command::Search { alarm, master } => {
+            print_slaves(output, client.search(master, alarm)?, registry);
+        }
+        Command::Reset { master } => {
+            client.master_command(master, vec![W1NetlinkCommand::Reset])?;
+        }
+        Command::Read {
+            target,
+            len,
+            write: data,
+        } => {
+            let mut cmds: Vec<_> = data.into_iter().map(|data| write(data.0)).collect();
+            cmds.push(read(len));
+            let data = read_data(run(&mut client, target, cmds)?);
+            print_data(output, data.first().map(Vec::as_slice).unwrap_or_default());
+        }
+        Command::Write { target, data } => {
+            run(&mut client, target, vec![write(concat(data))])?;
+        }
+        Command::Touch { target, data } => {
+            let data = read_data(run(&mut client, target, vec![touch(concat(data))])?);
+            print_data(output, data.first().map(Vec::as_slice).unwrap_or_default());
+        }
+        Command::AddSlave { master, rom } => {
+            client.master_command(master, vec![W1NetlinkCommand::SlaveAdd(rom)])?;
+        }
+        Command::RemoveSlave { master, rom } => {
+            client.master_command(master, vec![W1NetlinkCommand::SlaveRemove(rom)])?;
+        }
+        Command::Temp { roms } => {
+            let mut readings = Vec::new();
+            for rom in roms {
+                if !thermometer::is_thermometer(rom.family()) {
+                    return Err(Error::Device("not a thermometer"));
+                }
+                let resolution = thermometer::read_scratchpad(&mut client, rom)?.resolution();
+                thermometer::convert(&mut client, rom)?;
+                thread::sleep(resolution.conversion_time());
+                let pad = thermometer::read_scratchpad(&mut client, rom)?;
+                readings.push(Reading {
+                    rom,
+                    name: name(registry, rom),
+                    celsius: pad.temperature().celsius(),
+                });
+            }
+            print(output, &readings, &["ROM", "CELSIUS"], |reading| {
+                vec![reading.rom.to_string(), format!("{:.4}", reading.celsius)]
+            });
+        }
+        Command::Watch => {
+            // the subscribed socket only receives, lookups use the other
+            // client, and waits for events forever
+            let mut events = W1Client::new()?;
+            let mut topology = Topology::new();
+            if registry.is_some() {
+                topology.seed(&mut client)?;
+            }
+            events.subscribe()?;
+            loop {
+                for cmsg in events.recv()? {
+                    for msg in cmsg.payload {
+                        if !matches!(
+                            msg,
+                            W1NetlinkMessage::MasterEvent { .. }
+                                | W1NetlinkMessage::SlaveEvent { .. }
+                        ) {
+                            continue;
+                        }
+                        match output {
+                            Output::Table => println!("{msg}"),
+                            Output::Json => println!("{}", serde_json::to_string(&msg).unwrap()),
+                        }
+                        let Some(registry) = registry else {
+                            continue;
+                        };
+                        for change in topology.handle_event(&mut client, &msg)? {
+                            if let Some(finding) = registry.check_change(&change) {
+                                print_finding(output, &finding);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Command::Check => {
+            let Some(registry) = registry else {
+                return Err(Error::Device("check needs a registry, pass --registry"));
+            };
+            let mut topology = Topology::new();
+            topology.reconcile(&mut client, Instant::now())?;
+            let findings = registry.check(&topology.snapshot());
+            print(output, &findings, &["ROM", "FINDING"], |finding| {
+                vec![finding.id().to_string(), finding.to_string()]
+            });
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let registry = match cli.registry.as_deref().map(Registry::load).transpose() {
+        Ok(registry) => registry,
+        Err(err) => {
+            eprintln!("w1ctl: cannot load registry: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match execute(cli, registry) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("w1ctl: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Decoding of w1 traffic captured on an `nlmon` device.
+//!
+//! `nlmon` mirrors every netlink message on the host, so captures taken with
+//! `tcpdump -i nlmon0 -w w1.pcapng` contain all netlink families. [`packets`]
+//! reads pcap and pcapng files, [`timeline`] keeps the `NETLINK_CONNECTOR`
+//! frames addressed to the w1 core and decodes them.
+//!
+//! Frames start with the 16 byte Linux cooked header, which tells the netlink
+//! protocol and whether the message went to the kernel or to userspace.
+
+use std::{fs, io, path::Path, time::Duration};
+
+use netlink_sys::protocols::NETLINK_CONNECTOR;
+
+use crate::{
+    client::decode_datagram,
+    proto::{
+        connector::{NlConnectorMessage, NlConnectorType},
+        message::W1NetlinkMessage,
+    },
+    transport,
+};
+
+/// `LINKTYPE_LINUX_SLL`, used by older libpcap versions for `nlmon`.
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+/// `LINKTYPE_NETLINK`, a cooked header followed by netlink messages.
+pub const LINKTYPE_NETLINK: u32 = 253;
+
+const COOKED_HEADER_LEN: usize = 16;
+/// `struct nlmsghdr` in front of the connector message.
+const NETLINK_HEADER_LEN: usize = 16;
+
+/// Packet types set by the netlink tap.
+const PACKET_USER: u16 = 6;
+const PACKET_KERNEL: u16 = 7;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_IF_TSRESOL: u16 = 9;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Not a pcap or pcapng file")]
+    UnknownFormat,
+
+    #[error("Capture truncated at offset {0}")]
+    Truncated(usize),
+
+    #[error("Unsupported link type {0}, expected a capture on an nlmon device")]
+    LinkType(u32),
+
+    #[error("Packet refers to unknown interface {0}")]
+    Interface(u32),
+}
+
+/// A packet as stored in the capture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet<'a> {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    pub link_type: u32,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ToKernel,
+    ToUser,
+    /// A packet type not set by the netlink tap.
+    Unknown(u16),
+}
+
+/// A w1 netlink datagram seen in a capture.
+#[derive(Debug)]
+pub struct Frame {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    pub direction: Direction,
+    /// Connector messages of the datagram, or why they could not be decoded.
+    pub messages: Result<Vec<NlConnectorMessage<W1NetlinkMessage>>, transport::Error>,
+}
+
+/// Reads the capture at `path` and decodes its w1 frames, see [`timeline`].
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Frame>, Error> {
+    timeline(&fs::read(path)?)
+}
+
+/// Decodes the w1 frames of a pcap or pcapng capture in the order they were
+/// captured. Other netlink traffic is skipped, frames that fail to decode are
+/// kept with their error.
+pub fn timeline(capture: &[u8]) -> Result<Vec<Frame>, Error> {
+    let mut frames = Vec::new();
+    for packet in packets(capture)? {
+        if packet.link_type != LINKTYPE_NETLINK && packet.link_type != LINKTYPE_LINUX_SLL {
+            return Err(Error::LinkType(packet.link_type));
+        }
+        let Some((direction, datagram)) = w1_datagram(packet.data) else {
+            continue;
+        };
+        frames.push(Frame {
+            timestamp: packet.timestamp,
+            direction,
+            messages: decode_datagram(datagram),
+        });
+    }
+    Ok(frames)
+}
+
+/// Splits off the cooked header if the packet is a connector datagram for
+/// the w1 core.
+fn w1_datagram(data: &[u8]) -> Option<(Direction, &[u8])> {
+    let header = data.get(..COOKED_HEADER_LEN)?;
+    let protocol = u16::from_be_bytes([header[14], header[15]]);
+    if i32::from(protocol) != NETLINK_CONNECTOR as i32 {
+        return None;
+    }
+    let direction = match u16::from_be_bytes([header[0], header[1]]) {
+        PACKET_KERNEL => Direction::ToKernel,
+        PACKET_USER => Direction::ToUser,
+        other => Direction::Unknown(other),
+    };
+    // the connector serves other users as well, tell them apart by idx and val
+    let datagram = &data[COOKED_HEADER_LEN..];
+    let cn = datagram.get(NETLINK_HEADER_LEN..NETLINK_HEADER_LEN + 8)?;
+    let idx = u32::from_ne_bytes(cn[..4].try_into().unwrap());
+    let val = u32::from_ne_bytes(cn[4..].try_into().unwrap());
+    (idx == W1NetlinkMessage::idx() && val == W1NetlinkMessage::val())
+        .then_some((direction, datagram))
+}
+
+/// Reads all packets of a pcap or pcapng capture.
+pub fn packets(capture: &[u8]) -> Result<Vec<Packet<'_>>, Error> {
+    let magic = Reader::new(capture, false).u32(0)?;
+    if magic == PCAPNG_SECTION_HEADER {
+        return pcapng(capture);
+    }
+    // the magic number tells byte order and timestamp resolution
+    let (big, nanos) = match (magic, magic.swap_bytes()) {
+        (PCAP_MAGIC_MICROS, _) => (false, false),
+        (PCAP_MAGIC_NANOS, _) => (false, true),
+        (_, PCAP_MAGIC_MICROS) => (true, false),
+        (_, PCAP_MAGIC_NANOS) => (true, true),
+        _ => return Err(Error::UnknownFormat),
+    };
+    let r = Reader::new(capture, big);
+    let link_type = r.u32(20)? & 0xffff;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset < capture.len() {
+        let seconds = u64::from(r.u32(offset)?);
+        let fraction = r.u32(offset + 4)?;
+        let len = r.u32(offset + 8)? as usize;
+        let data = r.bytes(offset + 16, len)?;
+        let fraction = match nanos {
+            true => Duration::from_nanos(fraction.into()),
+            false => Duration::from_micros(fraction.into()),
+        };
+        packets.push(Packet {
+            timestamp: Duration::from_secs(seconds) + fraction,
+            link_type,
+            data,
+        });
+        offset += 16 + len;
+    }
+    Ok(packets)
+}
+
+struct Interface {
+    link_type: u32,
+    /// Timestamp units per second.
+    resolution: u64,
+}
+
+fn pcapng(capture: &[u8]) -> Result<Vec<Packet<'_>>, Error> {
+    let mut packets = Vec::new();
+    let mut interfaces = Vec::new();
+    let mut r = Reader::new(capture, false);
+    let mut offset = 0;
+    while offset < capture.len() {
+        let block_type = r.u32(offset)?;
+        if block_type == PCAPNG_SECTION_HEADER {
+            // every section may use a different byte order
+            r.big = match r.u32(offset + 8)? {
+                PCAPNG_BYTE_ORDER_MAGIC => r.big,
+                magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => !r.big,
+                _ => return Err(Error::UnknownFormat),
+            };
+            interfaces.clear();
+        }
+        let len = r.u32(offset + 4)? as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(Error::Truncated(offset));
+        }
+        let body = offset + 8;
+        let body_len = len - 12;
+        r.bytes(body, body_len)?;
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => interfaces.push(Interface {
+                link_type: r.u16(body)?.into(),
+                resolution: if_tsresol(&r, body + 8, body + body_len)?,
+            }),
+            PCAPNG_ENHANCED_PACKET => {
+                let id = r.u32(body)?;
+                let interface = interfaces.get(id as usize).ok_or(Error::Interface(id))?;
+                let ticks = u64::from(r.u32(body + 4)?) << 32 | u64::from(r.u32(body + 8)?);
+                let captured = r.u32(body + 12)? as usize;
+                let resolution = interface.resolution;
+                packets.push(Packet {
+                    timestamp: Duration::from_secs(ticks / resolution)
+                        + Duration::from_nanos(
+                            ((ticks % resolution) as u128 * 1_000_000_000 / resolution as u128)
+                                as u64,
+                        ),
+                    link_type: interface.link_type,
+                    data: r.bytes(body + 20, captured)?,
+                });
+            }
+            // without a timestamp, and for the first interface only
+            PCAPNG_SIMPLE_PACKET => {
+                let interface = interfaces.first().ok_or(Error::Interface(0))?;
+                let captured = (r.u32(body)? as usize).min(body_len - 4);
+                packets.push(Packet {
+                    timestamp: Duration::ZERO,
+                    link_type: interface.link_type,
+                    data: r.bytes(body + 4, captured)?,
+                });
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+    Ok(packets)
+}
+
+/// Finds the `if_tsresol` option of an interface, microseconds if missing.
+fn if_tsresol(r: &Reader<'_>, mut offset: usize, end: usize) -> Result<u64, Error> {
+    while offset + 4 <= end {
+        let code = r.u16(offset)?;
+        let len = usize::from(r.u16(offset + 2)?);
+        if code == 0 {
+            break;
+        }
+        if code == PCAPNG_IF_TSRESOL && len == 1 {
+            let value = r.bytes(offset + 4, 1)?[0];
+            let exponent = u32::from(value & 0x7f);
+            // negative powers of two or ten
+            let base: u64 = if value & 0x80 != 0 { 2 } else { 10 };
+            return base.checked_pow(exponent).ok_or(Error::UnknownFormat);
+        }
+        offset += 4 + len.div_ceil(4) * 4;
+    }
+    Ok(1_000_000)
+}
+
+/// Reads integers in the byte order of the capture.
+struct Reader<'a> {
+    data: &'a [u8],
+    big: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], big: bool) -> Self {
+        Self { data, big }
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], Error> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::Truncated(offset))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, Error> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(match self.big {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(match self.big {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+}
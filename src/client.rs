@@ -0,0 +1,372 @@
+//! Blocking client talking to the kernel's w1 core over a netlink socket.
+//!
+//! Without a [timeout](W1Client::with_timeout) a request waits for its
+//! replies forever, e.g. when the w1 core is not loaded. Replies arriving
+//! after their request timed out carry an old sequence number and are
+//! skipped by later requests.
+
+use std::{
+    io, mem,
+    os::fd::AsRawFd,
+    time::{Duration, Instant},
+};
+
+use netlink_packet_core::{NetlinkBuffer, NetlinkMessage, NLMSG_DONE, NLM_F_REQUEST};
+use netlink_sys::{protocols::NETLINK_CONNECTOR, Socket, SocketAddr};
+
+use crate::{
+    batch::Batch,
+    proto::{
+        command::W1NetlinkCommand,
+        connector::{self, NlConnectorMessage, NlConnectorMessageRef, NlConnectorType},
+        message::{self, W1NetlinkMessage},
+        Serializable,
+    },
+    transport::{Error, Transport},
+};
+
+/// Large enough for any message generated by the w1 core, which stays below a page.
+const RECV_BUFFER_LEN: usize = 65536;
+
+pub struct W1Client {
+    socket: Socket,
+    seq: u32,
+    buffer: Vec<u8>,
+    timeout: Option<Duration>,
+    /// Receive timeout currently set on the socket.
+    rcvtimeo: Option<Duration>,
+}
+
+impl W1Client {
+    /// Opens a connector socket and connects it to the kernel.
+    pub fn new() -> Result<Self, Error> {
+        let mut socket = Socket::new(NETLINK_CONNECTOR)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        Ok(Self {
+            socket,
+            seq: 0,
+            buffer: vec![0; RECV_BUFFER_LEN],
+            timeout: None,
+            rcvtimeo: None,
+        })
+    }
+
+    /// Fails requests with [`io::ErrorKind::TimedOut`] if their replies did
+    /// not arrive within `timeout`, and [`recv`](Self::recv) if nothing
+    /// arrived. No timeout by default.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Joins the multicast group of the w1 core to receive hotplug events
+    /// with [`recv`](Self::recv).
+    pub fn subscribe(&mut self) -> Result<(), Error> {
+        self.socket.add_membership(W1NetlinkMessage::idx())?;
+        Ok(())
+    }
+
+    /// Sends `msg` and returns the connector sequence number used. Messages
+    /// exceeding a length field or the connector limit are rejected before
+    /// anything is sent.
+    pub fn send(&mut self, msg: W1NetlinkMessage) -> Result<u32, Error> {
+        let cmsg = NlConnectorMessage::new(self.seq.wrapping_add(1), [msg]);
+        cmsg.validate()?;
+        self.send_connector(cmsg)
+    }
+
+    /// Sends a connector message with the sequence number it carries.
+    fn send_connector(&mut self, cmsg: NlConnectorMessage<W1NetlinkMessage>) -> Result<u32, Error> {
+        self.seq = cmsg.header.seq;
+        self.socket.send(&encode_request(cmsg), 0)?;
+        Ok(self.seq)
+    }
+
+    /// Sends `msgs` split into as many connector messages as needed, waiting
+    /// for the replies to each before sending the next. Returns the replies
+    /// carrying data in order, with chunked reads joined again.
+    pub fn transact_all(
+        &mut self,
+        msgs: impl IntoIterator<Item = W1NetlinkMessage>,
+    ) -> Result<Vec<W1NetlinkMessage>, Error> {
+        let mut batch = Batch::new(self.seq.wrapping_add(1), msgs)?;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut replies = Vec::new();
+        for cmsg in mem::take(&mut batch.requests) {
+            let mut pending = expected_acks(&cmsg);
+            let seq = self.send_connector(cmsg)?;
+
+            while pending > 0 {
+                let n = self.recv_until(deadline)?;
+                // replies to other requests are skipped before decoding, so
+                // their error statuses do not fail this one
+                for cmsg in Datagram::new(&self.buffer[..n]) {
+                    let cmsg = cmsg?;
+                    if cmsg.header.seq != seq {
+                        continue;
+                    }
+                    for reply in decode_messages(cmsg)? {
+                        let (acked, reply) = split_status(reply);
+                        pending = pending.saturating_sub(acked);
+                        replies.extend(reply);
+                    }
+                }
+            }
+        }
+        Ok(batch.reassemble(replies))
+    }
+
+    /// Receives one datagram and decodes all connector messages in it.
+    pub fn recv(&mut self) -> Result<Vec<NlConnectorMessage<W1NetlinkMessage>>, Error> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let n = self.recv_until(deadline)?;
+        decode_datagram(&self.buffer[..n])
+    }
+
+    /// Receives one datagram into the client's buffer without decoding it.
+    pub fn recv_ref(&mut self) -> Result<Datagram<'_>, Error> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let n = self.recv_until(deadline)?;
+        Ok(Datagram::new(&self.buffer[..n]))
+    }
+
+    /// Receives one datagram into the buffer, giving up at `deadline`.
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<usize, Error> {
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return Err(timed_out()),
+            },
+            None => None,
+        };
+        self.set_rcvtimeo(timeout)?;
+        match self.socket.recv(&mut &mut self.buffer[..], 0) {
+            Ok(n) => Ok(n.min(self.buffer.len())),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock && timeout.is_some() => {
+                Err(timed_out())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sets `SO_RCVTIMEO` unless the socket has it already.
+    fn set_rcvtimeo(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        if self.rcvtimeo == timeout {
+            return Ok(());
+        }
+        // a zero timeval disables the timeout, so wait at least a microsecond
+        let tv = match timeout {
+            Some(timeout) => libc::timeval {
+                tv_sec: timeout.as_secs().try_into().unwrap_or(libc::time_t::MAX),
+                tv_usec: timeout
+                    .subsec_micros()
+                    .max(u32::from(timeout.as_secs() == 0)) as _,
+            },
+            None => libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+        };
+        // SAFETY: the socket is open and `tv` is a valid timeval of the given size
+        let ret = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                (&tv as *const libc::timeval).cast(),
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        self.rcvtimeo = timeout;
+        Ok(())
+    }
+}
+
+fn timed_out() -> Error {
+    io::Error::new(io::ErrorKind::TimedOut, "no reply from the w1 core").into()
+}
+
+/// Encodes a request, asking the w1 core for a status reply per command.
+pub(crate) fn encode_request(mut cmsg: NlConnectorMessage<W1NetlinkMessage>) -> Vec<u8> {
+    cmsg.header.ack = 1;
+    let seq = cmsg.header.seq;
+
+    let mut nl_msg = NetlinkMessage::from(cmsg);
+    nl_msg.header.flags = NLM_F_REQUEST;
+    nl_msg.header.sequence_number = seq;
+    nl_msg.finalize();
+
+    let mut buf = vec![0; nl_msg.buffer_len()];
+    nl_msg.serialize(&mut buf[..]);
+    buf
+}
+
+/// Number of status replies to a request with acks enabled. The core
+/// acknowledges every command, or the message itself if it has none.
+pub(crate) fn expected_acks(cmsg: &NlConnectorMessage<W1NetlinkMessage>) -> usize {
+    cmsg.payload
+        .iter()
+        .map(|msg| match msg {
+            W1NetlinkMessage::MasterCommand { cmds, .. }
+            | W1NetlinkMessage::SlaveCommand { cmds, .. } => cmds.len().max(1),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Splits a datagram into its netlink messages and decodes their connector payload.
+///
+/// The connector sends its messages as `NLMSG_DONE`, which [`NetlinkMessage::deserialize`]
+/// would turn into an empty payload, so the netlink header is parsed here. A single
+/// netlink message may hold several connector messages when the w1 core bundles
+/// its replies.
+pub fn decode_datagram(
+    datagram: &[u8],
+) -> Result<Vec<NlConnectorMessage<W1NetlinkMessage>>, Error> {
+    let mut msgs = Vec::new();
+    for cmsg in Datagram::new(datagram) {
+        let cmsg = cmsg?;
+        msgs.push(NlConnectorMessage {
+            header: cmsg.header,
+            payload: decode_messages(cmsg)?,
+        });
+    }
+    Ok(msgs)
+}
+
+/// Decodes the w1 messages of a connector message, failing with
+/// [`Error::Status`] if the w1 core reported an error.
+pub(crate) fn decode_messages(
+    cmsg: NlConnectorMessageRef<'_>,
+) -> Result<Vec<W1NetlinkMessage>, Error> {
+    let mut messages = cmsg.messages();
+    let mut payload = Vec::new();
+    loop {
+        let offset = messages.offset();
+        let Some(msg) = messages.next() else {
+            break;
+        };
+        let msg = msg
+            .and_then(|msg| {
+                msg.try_into_owned()
+                    .map_err(|source| connector::DeserializeError::Inner { offset, source })
+            })
+            .map_err(status)?;
+        payload.push(msg);
+    }
+    Ok(payload)
+}
+
+/// Connector messages in a received datagram, borrowing from it and decoded
+/// while iterating. Iteration ends after the first error.
+#[derive(Debug, Clone)]
+pub struct Datagram<'a> {
+    data: &'a [u8],
+    offset: usize,
+    bundle: &'a [u8],
+}
+
+impl<'a> Datagram<'a> {
+    pub fn new(datagram: &'a [u8]) -> Self {
+        Self {
+            data: datagram,
+            offset: 0,
+            bundle: &[],
+        }
+    }
+
+    fn fail(&mut self, err: impl Into<Error>) -> Option<Result<NlConnectorMessageRef<'a>, Error>> {
+        self.offset = self.data.len();
+        self.bundle = &[];
+        Some(Err(err.into()))
+    }
+}
+
+impl<'a> Iterator for Datagram<'a> {
+    type Item = Result<NlConnectorMessageRef<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bundle.is_empty() {
+            if self.offset >= self.data.len() {
+                return None;
+            }
+            let buf = match NetlinkBuffer::new_checked(&self.data[self.offset..]) {
+                Ok(buf) => buf,
+                Err(err) => return self.fail(err),
+            };
+            if buf.message_type() != NLMSG_DONE {
+                return self.fail(connector::DeserializeError::InvalidMessageType);
+            }
+            self.bundle = buf.payload();
+            // netlink messages are aligned to four bytes
+            self.offset += (buf.length() as usize + 3) & !3;
+        }
+
+        match NlConnectorMessageRef::parse::<W1NetlinkMessage, _>(self.bundle) {
+            Ok((cmsg, len)) => {
+                self.bundle = &self.bundle[len..];
+                Some(Ok(cmsg))
+            }
+            Err(err) => self.fail(status(err)),
+        }
+    }
+}
+
+fn status(err: connector::DeserializeError<message::DeserializeError>) -> Error {
+    match err {
+        connector::DeserializeError::Inner {
+            source: message::DeserializeError::Status(status),
+            ..
+        } => Error::Status(status),
+        err => err.into(),
+    }
+}
+
+/// A reply without data only acknowledges a command.
+fn is_status(cmd: &W1NetlinkCommand) -> bool {
+    cmd.buffer_len() == W1NetlinkCommand::HEADER_LEN
+}
+
+/// Returns the number of commands acknowledged by `reply` and the reply
+/// stripped of those acknowledgements, if any data is left.
+pub(crate) fn split_status(reply: W1NetlinkMessage) -> (usize, Option<W1NetlinkMessage>) {
+    let split = |cmds: Vec<W1NetlinkCommand>| {
+        if cmds.is_empty() {
+            return (1, cmds);
+        }
+        let (status, data): (Vec<_>, Vec<_>) = cmds.into_iter().partition(is_status);
+        (status.len(), data)
+    };
+    match reply {
+        W1NetlinkMessage::MasterCommand { target, cmds } => match split(cmds) {
+            (acked, cmds) if cmds.is_empty() => (acked, None),
+            (acked, cmds) => (
+                acked,
+                Some(W1NetlinkMessage::MasterCommand { target, cmds }),
+            ),
+        },
+        W1NetlinkMessage::SlaveCommand { target, cmds } => match split(cmds) {
+            (acked, cmds) if cmds.is_empty() => (acked, None),
+            (acked, cmds) => (acked, Some(W1NetlinkMessage::SlaveCommand { target, cmds })),
+        },
+        // the master list is not followed by a status reply
+        reply @ W1NetlinkMessage::ListMasters(_) => (1, Some(reply)),
+        reply => (0, Some(reply)),
+    }
+}
+
+impl Transport for W1Client {
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        self.transact_all([msg])
+    }
+}
@@ -0,0 +1,43 @@
+//! CRC algorithms used by 1-Wire devices.
+//!
+//! See Maxim application note 27 ("Understanding and Using Cyclic Redundancy
+//! Checks with Maxim 1-Wire and iButton Products").
+
+/// Dallas/Maxim CRC8 (polynomial x^8 + x^5 + x^4 + 1), as used for ROM ids
+/// and scratchpads.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut b = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ b) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            b >>= 1;
+        }
+    }
+    crc
+}
+
+/// Dallas/Maxim CRC16 (polynomial x^16 + x^15 + x^2 + 1). Devices transmit its
+/// inverted value, least significant byte first.
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0, data)
+}
+
+/// Continues a CRC16 calculation over more data.
+pub fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc = crc >> 1 ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
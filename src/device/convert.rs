@@ -0,0 +1,36 @@
+//! Conversions on all thermometers and A/D converters of a bus at once.
+//!
+//! A single Skip ROM addresses every device on the bus, so the conversions
+//! run in parallel and the bus is free while they complete.
+
+use std::time::Duration;
+
+use crate::{
+    device::{ds2450, rom::SKIP_ROM, thermometer},
+    proto::command::W1NetlinkCommand,
+    transport::{read, write, Error, Transport},
+};
+
+/// Time the slowest conversion started by [`start`] may take, that of a
+/// thermometer at 12 bit resolution.
+pub fn conversion_time() -> Duration {
+    thermometer::Resolution::Bits12
+        .conversion_time()
+        .max(ds2450::CONVERSION_TIME)
+}
+
+/// Starts a conversion on all thermometers and A/D converters of `master`
+/// without waiting for it.
+pub fn start(t: &mut impl Transport, master: u32) -> Result<(), Error> {
+    // the CRC after the DS2450 conversion command is garbled with several
+    // devices answering and therefore ignored
+    let cmds = vec![
+        W1NetlinkCommand::Reset,
+        write([SKIP_ROM, thermometer::CONVERT_T]),
+        W1NetlinkCommand::Reset,
+        write([SKIP_ROM, ds2450::CONVERT, 0x0F, 0x00]),
+        read(2),
+    ];
+    t.master_command(master, cmds)?;
+    Ok(())
+}
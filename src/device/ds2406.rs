@@ -0,0 +1,411 @@
+//! DS2406/DS2407 dual addressable switch with 1 kbit EPROM.
+//!
+//! Programming the EPROM needs a 12 V pulse the w1 core cannot generate, so
+//! only the SRAM status byte at address 7 is writable here.
+
+use super::check_crc16;
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x12;
+
+pub const READ_MEMORY: u8 = 0xF0;
+pub const READ_STATUS: u8 = 0xAA;
+pub const WRITE_STATUS: u8 = 0x55;
+pub const CHANNEL_ACCESS: u8 = 0xF5;
+
+pub const MEMORY_LEN: usize = 128;
+pub const STATUS_LEN: usize = 8;
+/// Address of the SRAM status byte holding flip-flops and search settings.
+pub const STATUS_CONTROL: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Channels {
+    A,
+    B,
+    Both,
+}
+
+impl Channels {
+    fn bits(&self) -> u8 {
+        match self {
+            Channels::A => 0b01,
+            Channels::B => 0b10,
+            Channels::Both => 0b11,
+        }
+    }
+}
+
+/// Interval after which the device sends a CRC16 during channel access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CrcInterval {
+    Disabled,
+    Byte,
+    Bytes8,
+    Bytes32,
+}
+
+impl CrcInterval {
+    pub fn bytes(&self) -> Option<usize> {
+        match self {
+            CrcInterval::Disabled => None,
+            CrcInterval::Byte => Some(1),
+            CrcInterval::Bytes8 => Some(8),
+            CrcInterval::Bytes32 => Some(32),
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self {
+            CrcInterval::Disabled => 0b00,
+            CrcInterval::Byte => 0b01,
+            CrcInterval::Bytes8 => 0b10,
+            CrcInterval::Bytes32 => 0b11,
+        }
+    }
+}
+
+/// First channel control byte of the Channel Access command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelControl {
+    /// Reset the activity latches.
+    pub reset_activity: bool,
+    /// Interleave the bits of both channels.
+    pub interleave: bool,
+    /// Switch between reading and writing after every byte.
+    pub toggle: bool,
+    /// Start by reading, otherwise by writing.
+    pub read: bool,
+    pub channels: Channels,
+    pub crc: CrcInterval,
+}
+
+impl ChannelControl {
+    /// Reads the sensed levels of `channels`.
+    pub fn read(channels: Channels) -> Self {
+        Self {
+            reset_activity: false,
+            interleave: false,
+            toggle: false,
+            read: true,
+            channels,
+            crc: CrcInterval::Disabled,
+        }
+    }
+
+    /// Writes the flip-flops of `channels`.
+    pub fn write(channels: Channels) -> Self {
+        Self {
+            read: false,
+            ..Self::read(channels)
+        }
+    }
+
+    pub fn with_crc(self, crc: CrcInterval) -> Self {
+        Self { crc, ..self }
+    }
+
+    pub fn byte(&self) -> u8 {
+        (self.reset_activity as u8) << 7
+            | (self.interleave as u8) << 6
+            | (self.toggle as u8) << 5
+            | (self.read as u8) << 4
+            | self.channels.bits() << 2
+            | self.crc.bits()
+    }
+
+    /// Whether the `n`th data byte of the access is read.
+    pub fn reads_byte(&self, n: usize) -> bool {
+        self.read ^ (self.toggle && n % 2 == 1)
+    }
+}
+
+/// Channel info byte returned at the start of a Channel Access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelInfo(u8);
+
+impl ChannelInfo {
+    pub fn raw(&self) -> u8 {
+        self.0
+    }
+
+    /// Whether VCC is supplied.
+    pub fn supply(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// Whether the package provides PIO-B.
+    pub fn has_b(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn activity_b(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn activity_a(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn level_b(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn level_a(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// Flip-flop of PIO-B, `false` if the output transistor is on.
+    pub fn flip_flop_b(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Flip-flop of PIO-A, `false` if the output transistor is on.
+    pub fn flip_flop_a(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelAccess {
+    pub info: ChannelInfo,
+    /// Bytes read and written in access order.
+    pub data: Vec<u8>,
+}
+
+/// Signal the conditional search compares against its polarity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SearchSource {
+    ActivityLatch,
+    FlipFlop,
+    Level,
+}
+
+/// Condition for answering an alarm search, stored in status byte 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConditionalSearch {
+    /// `None` never takes part in conditional searches.
+    pub channels: Option<Channels>,
+    pub source: SearchSource,
+    /// Answer if the source is high, otherwise if it is low.
+    pub high: bool,
+}
+
+impl ConditionalSearch {
+    pub fn from_status(status: u8) -> Option<Self> {
+        let source = match status >> 1 & 0b11 {
+            0b01 => SearchSource::ActivityLatch,
+            0b10 => SearchSource::FlipFlop,
+            0b11 => SearchSource::Level,
+            _ => return None,
+        };
+        let channels = match status >> 3 & 0b11 {
+            0b01 => Some(Channels::A),
+            0b10 => Some(Channels::B),
+            0b11 => Some(Channels::Both),
+            _ => None,
+        };
+        Some(Self {
+            channels,
+            source,
+            high: status & 0x01 != 0,
+        })
+    }
+
+    /// The lower five status bits for this condition.
+    pub fn bits(&self) -> u8 {
+        let source = match self.source {
+            SearchSource::ActivityLatch => 0b01,
+            SearchSource::FlipFlop => 0b10,
+            SearchSource::Level => 0b11,
+        };
+        let channels = self.channels.as_ref().map(Channels::bits).unwrap_or(0);
+        channels << 3 | source << 1 | self.high as u8
+    }
+}
+
+/// Status memory, bytes 0 to 6 are EPROM, byte 7 is SRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Status(pub [u8; STATUS_LEN]);
+
+impl Status {
+    pub fn control(&self) -> u8 {
+        self.0[STATUS_CONTROL as usize]
+    }
+
+    pub fn supply(&self) -> bool {
+        self.control() & 0x80 != 0
+    }
+
+    pub fn flip_flop_b(&self) -> bool {
+        self.control() & 0x40 != 0
+    }
+
+    pub fn flip_flop_a(&self) -> bool {
+        self.control() & 0x20 != 0
+    }
+
+    pub fn conditional_search(&self) -> Option<ConditionalSearch> {
+        ConditionalSearch::from_status(self.control())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds2406 {
+    id: SlaveId,
+}
+
+impl Ds2406 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    /// Reads the EPROM from `address` to its end.
+    pub fn read_memory(&self, t: &mut impl Transport, address: u8) -> Result<Vec<u8>, Error> {
+        let len = MEMORY_LEN
+            .checked_sub(address.into())
+            .filter(|len| *len > 0)
+            .ok_or(Error::Device("address out of range"))?;
+        self.read_crc16(t, READ_MEMORY, address, len)
+    }
+
+    pub fn read_status(&self, t: &mut impl Transport) -> Result<Status, Error> {
+        let data = self.read_crc16(t, READ_STATUS, 0, STATUS_LEN)?;
+        Ok(Status(data.try_into().unwrap()))
+    }
+
+    /// Writes status byte 7, setting flip-flops and conditional search at once.
+    pub fn write_control(&self, t: &mut impl Transport, value: u8) -> Result<(), Error> {
+        let header = [WRITE_STATUS, STATUS_CONTROL, 0x00, value];
+        let cmds = vec![W1NetlinkCommand::Reset, write(header), read(3)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let data = data.first().ok_or(Error::NoDevice)?;
+        let (crc, verify) = match data.as_slice() {
+            [lo, hi, verify] => ([*lo, *hi], *verify),
+            _ => return Err(Error::Device("short status write reply")),
+        };
+        check_crc16(&header, crc)?;
+        // bit 7 is read-only
+        if verify & 0x7F != value & 0x7F {
+            return Err(Error::Device("status write not verified"));
+        }
+        Ok(())
+    }
+
+    /// Sets both flip-flops, keeping the conditional search settings.
+    pub fn set_flip_flops(&self, t: &mut impl Transport, a: bool, b: bool) -> Result<(), Error> {
+        let control = self.read_status(t)?.control();
+        let value = control & 0x1F | (b as u8) << 6 | (a as u8) << 5;
+        self.write_control(t, value)
+    }
+
+    /// Configures the conditional search, keeping the flip-flops.
+    pub fn set_conditional_search(
+        &self,
+        t: &mut impl Transport,
+        search: ConditionalSearch,
+    ) -> Result<(), Error> {
+        let control = self.read_status(t)?.control();
+        self.write_control(t, control & 0x60 | search.bits())
+    }
+
+    /// Runs a Channel Access. `data` holds the bytes to write, values at read
+    /// positions are ignored and replaced by the data read. Any CRC16 the
+    /// device sends is verified.
+    pub fn channel_access(
+        &self,
+        t: &mut impl Transport,
+        control: ChannelControl,
+        data: &[u8],
+    ) -> Result<ChannelAccess, Error> {
+        let header = [CHANNEL_ACCESS, control.byte(), 0xFF];
+        let interval = control.crc.bytes();
+
+        let mut cmds = vec![W1NetlinkCommand::Reset, write(header), read(1)];
+        for (i, byte) in data.iter().enumerate() {
+            if control.reads_byte(i) {
+                cmds.push(read(1));
+            } else {
+                cmds.push(write([*byte]));
+            }
+            if interval.filter(|n| (i + 1).is_multiple_of(*n)).is_some() {
+                cmds.push(read(2));
+            }
+        }
+        let mut reads = read_data(t.slave_command(self.id, cmds)?).into_iter();
+        let mut next = |len| {
+            reads
+                .next()
+                .filter(|r| r.len() == len)
+                .ok_or(Error::Device("missing channel access data"))
+        };
+
+        let info = next(1)?[0];
+        let mut crc_data = header.to_vec();
+        crc_data.push(info);
+        let mut result = Vec::with_capacity(data.len());
+        for (i, byte) in data.iter().enumerate() {
+            let byte = if control.reads_byte(i) {
+                next(1)?[0]
+            } else {
+                *byte
+            };
+            result.push(byte);
+            crc_data.push(byte);
+            if interval.filter(|n| (i + 1).is_multiple_of(*n)).is_some() {
+                let crc = next(2)?;
+                check_crc16(&crc_data, [crc[0], crc[1]])?;
+                // subsequent CRCs only cover the data since the last one
+                crc_data.clear();
+            }
+        }
+        Ok(ChannelAccess {
+            info: ChannelInfo(info),
+            data: result,
+        })
+    }
+
+    /// Reads the channel info byte without transferring data.
+    pub fn channel_info(&self, t: &mut impl Transport) -> Result<ChannelInfo, Error> {
+        let control = ChannelControl::read(Channels::A);
+        Ok(self.channel_access(t, control, &[])?.info)
+    }
+
+    fn read_crc16(
+        &self,
+        t: &mut impl Transport,
+        cmd: u8,
+        address: u8,
+        len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let header = [cmd, address, 0x00];
+        let cmds = vec![W1NetlinkCommand::Reset, write(header), read(len + 2)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let data = data.first().ok_or(Error::NoDevice)?;
+        if data.len() != len + 2 {
+            return Err(Error::Device("short memory read"));
+        }
+        if data.iter().all(|b| *b == 0xFF) {
+            return Err(Error::NoDevice);
+        }
+        let (data, crc) = data.split_at(len);
+        let mut crc_data = header.to_vec();
+        crc_data.extend(data);
+        check_crc16(&crc_data, [crc[0], crc[1]])?;
+        Ok(data.to_vec())
+    }
+}
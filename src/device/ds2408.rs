@@ -0,0 +1,133 @@
+//! DS2408 8 channel addressable switch.
+//!
+//! The channel state is read through the PIO registers, which come with a
+//! CRC16, rather than the continuous Channel Access Read stream.
+
+use super::check_crc16;
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x29;
+
+pub const READ_PIO_REGISTERS: u8 = 0xF0;
+pub const CHANNEL_ACCESS_READ: u8 = 0xF5;
+pub const CHANNEL_ACCESS_WRITE: u8 = 0x5A;
+
+/// Address of the PIO logic state, the first of the PIO registers.
+pub const REGISTERS: u8 = 0x88;
+/// Registers from the PIO logic state to the end of the address space.
+pub const REGISTERS_LEN: usize = 8;
+
+/// Confirmation byte sent after a channel write.
+const CONFIRMATION: u8 = 0xAA;
+
+/// PIO registers starting at address 0x88.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Registers([u8; REGISTERS_LEN]);
+
+impl Registers {
+    pub fn raw(&self) -> [u8; REGISTERS_LEN] {
+        self.0
+    }
+
+    /// Pin levels, bit n is PIOn.
+    pub fn levels(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Output latches, a cleared bit turns the output transistor on.
+    pub fn latches(&self) -> u8 {
+        self.0[1]
+    }
+
+    /// Channels that changed level since the activity latches were reset.
+    pub fn activity(&self) -> u8 {
+        self.0[2]
+    }
+
+    pub fn search_mask(&self) -> u8 {
+        self.0[3]
+    }
+
+    pub fn search_polarity(&self) -> u8 {
+        self.0[4]
+    }
+
+    pub fn control(&self) -> u8 {
+        self.0[5]
+    }
+
+    /// Whether VCC is supplied.
+    pub fn supply(&self) -> bool {
+        self.control() & 0x80 != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds2408 {
+    id: SlaveId,
+}
+
+impl Ds2408 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    pub fn read_registers(&self, t: &mut impl Transport) -> Result<Registers, Error> {
+        let header = [READ_PIO_REGISTERS, REGISTERS, 0x00];
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write(header),
+            read(REGISTERS_LEN + 2),
+        ];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let data = data.first().ok_or(Error::NoDevice)?;
+        if data.len() != REGISTERS_LEN + 2 {
+            return Err(Error::Device("short register read"));
+        }
+        if data.iter().all(|b| *b == 0xFF) {
+            return Err(Error::NoDevice);
+        }
+        let (registers, crc) = data.split_at(REGISTERS_LEN);
+        let mut crc_data = header.to_vec();
+        crc_data.extend(registers);
+        check_crc16(&crc_data, [crc[0], crc[1]])?;
+        Ok(Registers(registers.try_into().unwrap()))
+    }
+
+    /// Sets all output latches, bit n for PIOn. Returns the pin levels
+    /// sampled after the write.
+    pub fn write_latches(&self, t: &mut impl Transport, latches: u8) -> Result<u8, Error> {
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write([CHANNEL_ACCESS_WRITE, latches, !latches]),
+            read(2),
+        ];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        match data.first().map(Vec::as_slice) {
+            Some([CONFIRMATION, levels]) => Ok(*levels),
+            Some([0xFF, 0xFF]) => Err(Error::NoDevice),
+            _ => Err(Error::Device("channel write not confirmed")),
+        }
+    }
+
+    /// Sets the latch of a single channel, keeping the others.
+    pub fn set_latch(&self, t: &mut impl Transport, channel: u8, value: bool) -> Result<u8, Error> {
+        if channel >= 8 {
+            return Err(Error::Device("channel out of range"));
+        }
+        let latches = self.read_registers(t)?.latches();
+        let latches = match value {
+            true => latches | 1 << channel,
+            false => latches & !(1 << channel),
+        };
+        self.write_latches(t, latches)
+    }
+}
@@ -0,0 +1,63 @@
+//! DS2413 dual channel addressable switch.
+//!
+//! The PIO commands and the status byte are the same as on the DS28EA00,
+//! only the write command code differs.
+
+pub use super::ds28ea00::PioState;
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x3A;
+
+pub const PIO_ACCESS_READ: u8 = 0xF5;
+pub const PIO_ACCESS_WRITE: u8 = 0x5A;
+
+/// Confirmation byte sent after a PIO write.
+const CONFIRMATION: u8 = 0xAA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds2413 {
+    id: SlaveId,
+}
+
+impl Ds2413 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    pub fn read_pio(&self, t: &mut impl Transport) -> Result<PioState, Error> {
+        let cmds = vec![W1NetlinkCommand::Reset, write([PIO_ACCESS_READ]), read(1)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let state = match data.first().map(Vec::as_slice) {
+            Some([status]) => PioState::from_raw(*status),
+            _ => return Err(Error::Device("expected a single byte")),
+        };
+        if !state.is_valid() {
+            return Err(Error::Device("invalid PIO status"));
+        }
+        Ok(state)
+    }
+
+    /// Sets the output latches, `false` turns the output transistor on and
+    /// pulls the pin low. Returns the resulting PIO status.
+    pub fn write_pio(&self, t: &mut impl Transport, a: bool, b: bool) -> Result<PioState, Error> {
+        let value = 0xFC | (b as u8) << 1 | a as u8;
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write([PIO_ACCESS_WRITE, value, !value]),
+            read(2),
+        ];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        match data.first().map(Vec::as_slice) {
+            Some([CONFIRMATION, status]) => Ok(PioState::from_raw(*status)),
+            Some([0xFF, 0xFF]) => Err(Error::NoDevice),
+            _ => Err(Error::Device("PIO write not confirmed")),
+        }
+    }
+}
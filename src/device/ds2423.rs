@@ -0,0 +1,72 @@
+//! DS2423 4 kbit RAM with counters.
+//!
+//! Each of the last four memory pages has a 32 bit counter, read along with
+//! the page. The counters of pages 14 and 15 count the falling edges on the
+//! inputs A and B, those of pages 12 and 13 the writes to their page.
+
+use std::ops::RangeInclusive;
+
+use super::check_crc16;
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x1D;
+
+pub const READ_MEMORY_COUNTER: u8 = 0xA5;
+
+pub const PAGE_LEN: usize = 32;
+/// Pages with a counter.
+pub const COUNTER_PAGES: RangeInclusive<u8> = 12..=15;
+/// Pages whose counters count the inputs A and B.
+pub const INPUT_PAGES: [u8; 2] = [14, 15];
+
+/// Page data, counter, 4 zero bytes and CRC16 sent for a page.
+const REPLY_LEN: usize = PAGE_LEN + 4 + 4 + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds2423 {
+    id: SlaveId,
+}
+
+impl Ds2423 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    /// Reads the counter of `page`, one of [`COUNTER_PAGES`].
+    pub fn read_counter(&self, t: &mut impl Transport, page: u8) -> Result<u32, Error> {
+        if !COUNTER_PAGES.contains(&page) {
+            return Err(Error::Device("page without counter"));
+        }
+        let [lo, hi] = (u16::from(page) * PAGE_LEN as u16).to_le_bytes();
+        let header = [READ_MEMORY_COUNTER, lo, hi];
+        let cmds = vec![W1NetlinkCommand::Reset, write(header), read(REPLY_LEN)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let data = data.first().ok_or(Error::NoDevice)?;
+        if data.len() != REPLY_LEN {
+            return Err(Error::Device("short counter read"));
+        }
+        if data.iter().all(|byte| *byte == 0xFF) {
+            return Err(Error::NoDevice);
+        }
+        let (data, crc) = data.split_at(REPLY_LEN - 2);
+        let mut crc_data = header.to_vec();
+        crc_data.extend(data);
+        check_crc16(&crc_data, [crc[0], crc[1]])?;
+        Ok(u32::from_le_bytes(
+            data[PAGE_LEN..PAGE_LEN + 4].try_into().unwrap(),
+        ))
+    }
+
+    /// Reads the counters of the inputs A and B.
+    pub fn read_counters(&self, t: &mut impl Transport) -> Result<[u32; 2], Error> {
+        let [a, b] = INPUT_PAGES;
+        Ok([self.read_counter(t, a)?, self.read_counter(t, b)?])
+    }
+}
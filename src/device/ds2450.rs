@@ -0,0 +1,313 @@
+//! DS2450 quad A/D converter.
+//!
+//! The memory consists of four pages of eight bytes: conversion results,
+//! channel control and status, alarm thresholds and calibration. Every
+//! channel owns two bytes in each of the first three pages.
+
+use std::time::Duration;
+
+use super::check_crc16;
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x20;
+
+pub const READ_MEMORY: u8 = 0xAA;
+pub const WRITE_MEMORY: u8 = 0x55;
+pub const CONVERT: u8 = 0x3C;
+
+pub const PAGE_LEN: usize = 8;
+pub const MEMORY_LEN: usize = 32;
+pub const CONVERSION_PAGE: u8 = 0x00;
+pub const CONTROL_PAGE: u8 = 0x08;
+pub const ALARM_PAGE: u8 = 0x10;
+
+/// Time needed to convert all channels at 16 bits.
+pub const CONVERSION_TIME: Duration = Duration::from_micros(4 * (16 * 80 + 160));
+
+/// Status bits in the second control byte of a channel.
+const IR: u8 = 0x01;
+const AEL: u8 = 0x04;
+const AEH: u8 = 0x08;
+const AFL: u8 = 0x10;
+const AFH: u8 = 0x20;
+const POR: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Channel {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [Channel::A, Channel::B, Channel::C, Channel::D];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn mask(&self) -> u8 {
+        1 << self.index()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputRange {
+    V2_56,
+    V5_12,
+}
+
+impl InputRange {
+    pub fn full_scale(&self) -> f32 {
+        match self {
+            InputRange::V2_56 => 2.56,
+            InputRange::V5_12 => 5.12,
+        }
+    }
+
+    /// Converts a conversion result to volts.
+    pub fn voltage(&self, raw: u16) -> f32 {
+        f32::from(raw) / 65536.0 * self.full_scale()
+    }
+
+    /// Alarm threshold closest to `volts`, thresholds compare against the
+    /// most significant byte of a conversion result.
+    pub fn threshold(&self, volts: f32) -> u8 {
+        (volts / self.full_scale() * 256.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    }
+}
+
+/// Alarm thresholds of a channel, `None` disables the alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmLimits {
+    pub low: Option<u8>,
+    pub high: Option<u8>,
+}
+
+/// Control and status bytes of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStatus(pub [u8; 2]);
+
+impl ChannelStatus {
+    /// Resolution in bits.
+    pub fn resolution(&self) -> u8 {
+        match self.0[0] & 0x0F {
+            0 => 16,
+            bits => bits,
+        }
+    }
+
+    pub fn range(&self) -> InputRange {
+        match self.0[1] & IR != 0 {
+            true => InputRange::V5_12,
+            false => InputRange::V2_56,
+        }
+    }
+
+    pub fn alarm_low_enabled(&self) -> bool {
+        self.0[1] & AEL != 0
+    }
+
+    pub fn alarm_high_enabled(&self) -> bool {
+        self.0[1] & AEH != 0
+    }
+
+    /// Whether the last conversion was below the low threshold.
+    pub fn alarm_low(&self) -> bool {
+        self.0[1] & AFL != 0
+    }
+
+    /// Whether the last conversion was above the high threshold.
+    pub fn alarm_high(&self) -> bool {
+        self.0[1] & AFH != 0
+    }
+
+    /// Set after power-up until written. The device takes part in every
+    /// alarm search while it is set.
+    pub fn power_on_reset(&self) -> bool {
+        self.0[1] & POR != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds2450 {
+    id: SlaveId,
+}
+
+impl Ds2450 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    /// Reads `len` bytes from `address`, verifying the CRC16 sent at the end
+    /// of every page.
+    pub fn read_memory(
+        &self,
+        t: &mut impl Transport,
+        address: u8,
+        len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let start = usize::from(address);
+        if len == 0 || start + len > MEMORY_LEN {
+            return Err(Error::Device("address out of range"));
+        }
+        let header = [READ_MEMORY, address, 0x00];
+        // every page is read to its end to receive the CRC
+        let pages: Vec<_> = (start / PAGE_LEN..(start + len).div_ceil(PAGE_LEN))
+            .map(|page| (page * PAGE_LEN).max(start)..(page + 1) * PAGE_LEN)
+            .collect();
+        let mut cmds = vec![W1NetlinkCommand::Reset, write(header)];
+        cmds.extend(pages.iter().map(|page| read(page.len() + 2)));
+        let replies = read_data(t.slave_command(self.id, cmds)?);
+        if replies.len() != pages.len() {
+            return Err(Error::NoDevice);
+        }
+
+        let mut memory = Vec::with_capacity(pages.len() * PAGE_LEN);
+        for (i, (data, page)) in replies.iter().zip(&pages).enumerate() {
+            if data.len() != page.len() + 2 {
+                return Err(Error::Device("short memory read"));
+            }
+            if data.iter().all(|b| *b == 0xFF) {
+                return Err(Error::NoDevice);
+            }
+            let (data, crc) = data.split_at(page.len());
+            // the first CRC also covers command and address
+            let mut crc_data = if i == 0 { header.to_vec() } else { Vec::new() };
+            crc_data.extend(data);
+            check_crc16(&crc_data, [crc[0], crc[1]])?;
+            memory.extend(data);
+        }
+        Ok(memory[..len].to_vec())
+    }
+
+    /// Writes `data` from `address`. The device answers every byte with a
+    /// CRC16 and the byte read back, both are verified.
+    pub fn write_memory(
+        &self,
+        t: &mut impl Transport,
+        address: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let start = usize::from(address);
+        if data.is_empty() || start < usize::from(CONTROL_PAGE) || start + data.len() > MEMORY_LEN {
+            return Err(Error::Device("address out of range"));
+        }
+        let mut cmds = vec![W1NetlinkCommand::Reset];
+        for (i, byte) in data.iter().enumerate() {
+            match i {
+                0 => cmds.push(write([WRITE_MEMORY, address, 0x00, *byte])),
+                _ => cmds.push(write([*byte])),
+            }
+            cmds.push(read(3));
+        }
+        let replies = read_data(t.slave_command(self.id, cmds)?);
+        if replies.len() != data.len() {
+            return Err(Error::NoDevice);
+        }
+        for (i, (reply, byte)) in replies.iter().zip(data).enumerate() {
+            let (crc, verify) = match reply.as_slice() {
+                [lo, hi, verify] => ([*lo, *hi], *verify),
+                _ => return Err(Error::Device("short memory write reply")),
+            };
+            // later CRCs cover the incremented address instead of the command
+            let [lo, hi] = (u16::from(address) + i as u16).to_le_bytes();
+            match i {
+                0 => check_crc16(&[WRITE_MEMORY, lo, hi, *byte], crc)?,
+                _ => check_crc16(&[lo, hi, *byte], crc)?,
+            }
+            if verify != *byte {
+                return Err(Error::Device("memory write not verified"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts a conversion of `channels`. Wait for [`CONVERSION_TIME`] before
+    /// reading the results.
+    pub fn convert(&self, t: &mut impl Transport, channels: &[Channel]) -> Result<(), Error> {
+        let mask = channels.iter().fold(0, |mask, c| mask | c.mask());
+        // keep the previous results instead of presetting them
+        let header = [CONVERT, mask, 0x00];
+        let cmds = vec![W1NetlinkCommand::Reset, write(header), read(2)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        match data.first().map(Vec::as_slice) {
+            Some([lo, hi]) => check_crc16(&header, [*lo, *hi]),
+            _ => Err(Error::Device("short conversion reply")),
+        }
+    }
+
+    /// Reads the raw conversion results of all channels.
+    pub fn read_conversions(&self, t: &mut impl Transport) -> Result<[u16; 4], Error> {
+        let data = self.read_memory(t, CONVERSION_PAGE, PAGE_LEN)?;
+        Ok(
+            Channel::ALL
+                .map(|c| u16::from_le_bytes([data[2 * c.index()], data[2 * c.index() + 1]])),
+        )
+    }
+
+    pub fn read_status(&self, t: &mut impl Transport) -> Result<[ChannelStatus; 4], Error> {
+        let data = self.read_memory(t, CONTROL_PAGE, PAGE_LEN)?;
+        Ok(Channel::ALL.map(|c| ChannelStatus([data[2 * c.index()], data[2 * c.index() + 1]])))
+    }
+
+    /// Sets resolution in bits and input range of `channel`, keeping its
+    /// output and alarm settings.
+    pub fn set_input(
+        &self,
+        t: &mut impl Transport,
+        channel: Channel,
+        resolution: u8,
+        range: InputRange,
+    ) -> Result<(), Error> {
+        if !(1..=16).contains(&resolution) {
+            return Err(Error::Device("resolution out of range"));
+        }
+        let [control, status] = self.read_status(t)?[channel.index()].0;
+        let control = control & 0xF0 | resolution & 0x0F;
+        let status = match range {
+            InputRange::V2_56 => status & !IR,
+            InputRange::V5_12 => status | IR,
+        };
+        let address = CONTROL_PAGE + 2 * channel.index() as u8;
+        // writing the status byte also clears the power-on reset flag
+        self.write_memory(t, address, &[control, status & !(POR | AFL | AFH)])
+    }
+
+    /// Programs the alarm thresholds of `channel` and enables the alarms
+    /// given in `limits`.
+    pub fn set_alarm(
+        &self,
+        t: &mut impl Transport,
+        channel: Channel,
+        limits: AlarmLimits,
+    ) -> Result<(), Error> {
+        let address = ALARM_PAGE + 2 * channel.index() as u8;
+        let thresholds = [limits.low.unwrap_or(0x00), limits.high.unwrap_or(0xFF)];
+        self.write_memory(t, address, &thresholds)?;
+
+        let [_, status] = self.read_status(t)?[channel.index()].0;
+        let mut status = status & !(AEL | AEH | AFL | AFH | POR);
+        if limits.low.is_some() {
+            status |= AEL;
+        }
+        if limits.high.is_some() {
+            status |= AEH;
+        }
+        self.write_memory(t, CONTROL_PAGE + 2 * channel.index() as u8 + 1, &[status])
+    }
+}
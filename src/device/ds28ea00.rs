@@ -0,0 +1,214 @@
+//! DS28EA00 digital thermometer with sequence detect and two PIO pins.
+//!
+//! In chain mode PIOA acts as DONE output and PIOB as EN input. Wiring each
+//! DONE to the EN of the next device allows discovering the physical order of
+//! the devices along the cable.
+
+use super::{
+    rom::{MATCH_ROM, SKIP_ROM},
+    thermometer::{self, Resolution, Scratchpad},
+};
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const FAMILY: u8 = 0x42;
+
+pub const PIO_ACCESS_READ: u8 = 0xF5;
+pub const PIO_ACCESS_WRITE: u8 = 0xA5;
+pub const CHAIN: u8 = 0x99;
+/// ROM function command only answered by the enabled device in chain mode.
+pub const CONDITIONAL_READ_ROM: u8 = 0x0F;
+
+/// Confirmation byte sent after PIO writes and chain commands.
+const CONFIRMATION: u8 = 0xAA;
+
+/// Control byte of the chain command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChainControl {
+    Off,
+    On,
+    Done,
+}
+
+impl From<ChainControl> for u8 {
+    fn from(ctrl: ChainControl) -> Self {
+        match ctrl {
+            ChainControl::Off => 0x3C,
+            ChainControl::On => 0x5A,
+            ChainControl::Done => 0x96,
+        }
+    }
+}
+
+/// PIO status as returned by PIO Access Read, shared with the DS2413.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PioState(u8);
+
+impl PioState {
+    pub fn from_raw(raw: u8) -> Self {
+        Self(raw)
+    }
+
+    pub fn raw(&self) -> u8 {
+        self.0
+    }
+
+    /// Pin level of PIOA.
+    pub fn a(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    /// Output latch of PIOA, `false` if the output transistor is on.
+    pub fn a_latch(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Pin level of PIOB.
+    pub fn b(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// Output latch of PIOB, `false` if the output transistor is on.
+    pub fn b_latch(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    /// The upper nibble carries the inverted lower nibble.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.0 >> 4 == !self.0 & 0x0F
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ds28ea00 {
+    id: SlaveId,
+}
+
+impl Ds28ea00 {
+    pub fn new(id: SlaveId) -> Self {
+        Self { id }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    pub fn read_pio(&self, t: &mut impl Transport) -> Result<PioState, Error> {
+        let cmds = vec![W1NetlinkCommand::Reset, write([PIO_ACCESS_READ]), read(1)];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        let state = PioState(single_byte(&data)?);
+        if !state.is_valid() {
+            return Err(Error::Device("invalid PIO status"));
+        }
+        Ok(state)
+    }
+
+    /// Sets the output latches, `false` turns the output transistor on and
+    /// pulls the pin low. Returns the resulting PIO status.
+    pub fn write_pio(&self, t: &mut impl Transport, a: bool, b: bool) -> Result<PioState, Error> {
+        let value = 0xFC | (b as u8) << 1 | a as u8;
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write([PIO_ACCESS_WRITE, value, !value]),
+            read(2),
+        ];
+        let data = read_data(t.slave_command(self.id, cmds)?);
+        match data.first().map(Vec::as_slice) {
+            Some([CONFIRMATION, status]) => Ok(PioState(*status)),
+            Some([0xFF, 0xFF]) => Err(Error::NoDevice),
+            _ => Err(Error::Device("PIO write not confirmed")),
+        }
+    }
+
+    /// Starts a temperature conversion.
+    pub fn convert_temperature(&self, t: &mut impl Transport) -> Result<(), Error> {
+        thermometer::convert(t, self.id)
+    }
+
+    pub fn read_scratchpad(&self, t: &mut impl Transport) -> Result<Scratchpad, Error> {
+        thermometer::read_scratchpad(t, self.id)
+    }
+
+    /// Sets the resolution, keeping the alarm thresholds.
+    pub fn set_resolution(
+        &self,
+        t: &mut impl Transport,
+        resolution: Resolution,
+    ) -> Result<(), Error> {
+        let pad = self.read_scratchpad(t)?;
+        thermometer::write_scratchpad(t, self.id, pad.alarm_high(), pad.alarm_low(), resolution)
+    }
+}
+
+/// Discovers the DS28EA00 devices on the bus of `master` in their physical order,
+/// starting with the device whose EN input is tied to ground.
+///
+/// The bus is left with chain mode turned off, also if discovery fails.
+pub fn discover_chain(t: &mut impl Transport, master: u32) -> Result<Vec<SlaveId>, Error> {
+    let discovered =
+        chain(t, master, &[SKIP_ROM], ChainControl::On).and_then(|_| discover_enabled(t, master));
+    let off = chain(t, master, &[SKIP_ROM], ChainControl::Off);
+    let discovered = discovered?;
+    off?;
+    Ok(discovered)
+}
+
+fn discover_enabled(t: &mut impl Transport, master: u32) -> Result<Vec<SlaveId>, Error> {
+    let mut ids = Vec::new();
+    loop {
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write([CONDITIONAL_READ_ROM]),
+            read(SlaveId::LEN),
+        ];
+        let data = read_data(t.master_command(master, cmds)?);
+        let rom = data.first().ok_or(Error::NoDevice)?;
+        if rom.iter().all(|b| *b == 0xFF) {
+            // no enabled device left
+            return Ok(ids);
+        }
+        let rom: [u8; 8] = rom[..]
+            .try_into()
+            .map_err(|_| Error::Device("short ROM read"))?;
+        super::check_crc8(&rom)?;
+        let id = SlaveId::new(rom);
+        if ids.contains(&id) {
+            return Err(Error::Device("chain DONE not applied"));
+        }
+
+        let mut select = vec![MATCH_ROM];
+        select.extend(id.bytes());
+        chain(t, master, &select, ChainControl::Done)?;
+        ids.push(id);
+    }
+}
+
+/// Sends a chain command to the devices addressed by the ROM command in `select`.
+fn chain(
+    t: &mut impl Transport,
+    master: u32,
+    select: &[u8],
+    ctrl: ChainControl,
+) -> Result<(), Error> {
+    let ctrl = u8::from(ctrl);
+    let mut data = select.to_vec();
+    data.extend([CHAIN, ctrl, !ctrl]);
+    let cmds = vec![W1NetlinkCommand::Reset, write(data), read(1)];
+    let data = read_data(t.master_command(master, cmds)?);
+    match single_byte(&data)? {
+        CONFIRMATION => Ok(()),
+        0xFF => Err(Error::NoDevice),
+        _ => Err(Error::Device("chain command not confirmed")),
+    }
+}
+
+fn single_byte(data: &[Vec<u8>]) -> Result<u8, Error> {
+    match data.first().map(Vec::as_slice) {
+        Some([byte]) => Ok(*byte),
+        _ => Err(Error::Device("missing read data")),
+    }
+}
@@ -0,0 +1,292 @@
+//! DS2433 (4 kbit) and DS28EC20 (20 kbit) EEPROMs.
+//!
+//! Memory is read with a single Read Memory per netlink message, as long as it
+//! fits into a connector message. Writes go page by page through the
+//! scratchpad, which is read back and compared before copying it. The copied
+//! page is read back from memory as well.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    batch::MAX_DATA_LEN,
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const DS2433_FAMILY: u8 = 0x23;
+pub const DS28EC20_FAMILY: u8 = 0x43;
+
+pub const READ_MEMORY: u8 = 0xF0;
+pub const WRITE_SCRATCHPAD: u8 = 0x0F;
+pub const READ_SCRATCHPAD: u8 = 0xAA;
+pub const COPY_SCRATCHPAD: u8 = 0x55;
+
+pub const PAGE_LEN: usize = 32;
+
+/// Largest read fitting into a single request next to the Read Memory header.
+pub const MAX_READ_LEN: usize = MAX_DATA_LEN
+    // reset and write of command and address, next to the read
+    - 2 * W1NetlinkCommand::HEADER_LEN
+    - 3;
+
+/// Partial byte flag in the E/S byte.
+const ES_PF: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Model {
+    Ds2433,
+    Ds28ec20,
+}
+
+impl Model {
+    pub fn from_family(family: u8) -> Option<Self> {
+        match family {
+            DS2433_FAMILY => Some(Model::Ds2433),
+            DS28EC20_FAMILY => Some(Model::Ds28ec20),
+            _ => None,
+        }
+    }
+
+    pub fn family(&self) -> u8 {
+        match self {
+            Model::Ds2433 => DS2433_FAMILY,
+            Model::Ds28ec20 => DS28EC20_FAMILY,
+        }
+    }
+
+    /// Memory size in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Model::Ds2433 => 512,
+            Model::Ds28ec20 => 2560,
+        }
+    }
+
+    /// Maximum time needed to copy the scratchpad to memory.
+    pub fn programming_time(&self) -> Duration {
+        match self {
+            Model::Ds2433 => Duration::from_millis(5),
+            Model::Ds28ec20 => Duration::from_millis(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Eeprom {
+    id: SlaveId,
+    model: Model,
+    max_read: usize,
+}
+
+impl Eeprom {
+    pub fn new(id: SlaveId, model: Model) -> Self {
+        Self {
+            id,
+            model,
+            max_read: MAX_READ_LEN,
+        }
+    }
+
+    /// Picks the model from the family code of `id`.
+    pub fn from_id(id: SlaveId) -> Option<Self> {
+        Model::from_family(id.family()).map(|model| Self::new(id, model))
+    }
+
+    /// Limits the number of bytes read per request.
+    pub fn with_max_read(self, max_read: usize) -> Self {
+        Self {
+            max_read: max_read.clamp(1, MAX_READ_LEN),
+            ..self
+        }
+    }
+
+    pub fn id(&self) -> SlaveId {
+        self.id
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Fills `buf` with memory contents starting at `address`.
+    pub fn read_memory(
+        &self,
+        t: &mut impl Transport,
+        address: usize,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        self.check_range(address, buf.len())?;
+        for (i, chunk) in buf.chunks_mut(self.max_read).enumerate() {
+            let [lo, hi] = ((address + i * self.max_read) as u16).to_le_bytes();
+            let cmds = vec![
+                W1NetlinkCommand::Reset,
+                write([READ_MEMORY, lo, hi]),
+                read(chunk.len()),
+            ];
+            let data = read_data(t.slave_command(self.id, cmds)?);
+            match data.first() {
+                Some(data) if data.len() == chunk.len() => chunk.copy_from_slice(data),
+                _ => return Err(Error::Device("short memory read")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `data` starting at `address`, one page at a time.
+    pub fn write_memory(
+        &self,
+        t: &mut impl Transport,
+        address: usize,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.check_range(address, data.len())?;
+        let mut written = 0;
+        while written < data.len() {
+            let address = address + written;
+            let len = (PAGE_LEN - address % PAGE_LEN).min(data.len() - written);
+            self.write_page(t, address, &data[written..written + len])?;
+            written += len;
+        }
+        Ok(())
+    }
+
+    /// Writes data within a single page: write scratchpad, verify it and copy
+    /// it to memory.
+    pub fn write_page(
+        &self,
+        t: &mut impl Transport,
+        address: usize,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.check_range(address, data.len())?;
+        let offset = address % PAGE_LEN;
+        if data.is_empty() || offset + data.len() > PAGE_LEN {
+            return Err(Error::Device("write crosses page boundary"));
+        }
+        let [lo, hi] = (address as u16).to_le_bytes();
+
+        let mut header = vec![WRITE_SCRATCHPAD, lo, hi];
+        header.extend(data);
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write(header),
+            W1NetlinkCommand::Reset,
+            write([READ_SCRATCHPAD]),
+            read(3 + data.len()),
+        ];
+        let readback = read_data(t.slave_command(self.id, cmds)?);
+        let es = match readback.first().map(Vec::as_slice) {
+            Some([l, h, es, pad @ ..]) if [*l, *h] == [lo, hi] && pad == data => *es,
+            Some(pad) if pad.iter().all(|b| *b == 0xFF) => return Err(Error::NoDevice),
+            _ => return Err(Error::Device("scratchpad verification failed")),
+        };
+        if es & ES_PF != 0 || usize::from(es & 0x1F) != offset + data.len() - 1 {
+            return Err(Error::Device("scratchpad incomplete"));
+        }
+
+        let cmds = vec![
+            W1NetlinkCommand::Reset,
+            write([COPY_SCRATCHPAD, lo, hi, es]),
+        ];
+        t.slave_command(self.id, cmds)?;
+        // The w1 core cannot wait within a message, and releases the bus
+        // between messages. The copy runs inside the device and is not
+        // disturbed by other traffic, but the copy status read right after it
+        // would be. So the page is read back with a fresh reset instead.
+        thread::sleep(self.model.programming_time());
+        let mut copied = vec![0; data.len()];
+        self.read_memory(t, address, &mut copied)?;
+        match copied == data {
+            true => Ok(()),
+            false => Err(Error::Device("copy scratchpad failed")),
+        }
+    }
+
+    /// Wraps the device into a file-like type using `t` for all accesses.
+    pub fn file<T: Transport>(self, t: T) -> EepromFile<T> {
+        EepromFile {
+            eeprom: self,
+            transport: t,
+            pos: 0,
+        }
+    }
+
+    fn check_range(&self, address: usize, len: usize) -> Result<(), Error> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.model.size() => Ok(()),
+            _ => Err(Error::Device("address out of range")),
+        }
+    }
+}
+
+/// EEPROM contents accessible through [`Read`], [`Write`] and [`Seek`].
+pub struct EepromFile<T> {
+    eeprom: Eeprom,
+    transport: T,
+    pos: u64,
+}
+
+impl<T> EepromFile<T> {
+    pub fn eeprom(&self) -> &Eeprom {
+        &self.eeprom
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Number of bytes between the current position and the end of memory.
+    fn remaining(&self) -> usize {
+        (self.eeprom.model.size() as u64).saturating_sub(self.pos) as usize
+    }
+}
+
+impl<T: Transport> Read for EepromFile<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining());
+        if len == 0 {
+            return Ok(0);
+        }
+        self.eeprom
+            .read_memory(&mut self.transport, self.pos as usize, &mut buf[..len])?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<T: Transport> Write for EepromFile<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining());
+        if len == 0 {
+            return Ok(0);
+        }
+        self.eeprom
+            .write_memory(&mut self.transport, self.pos as usize, &buf[..len])?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    /// Writes are committed page by page, so there is nothing to flush.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T> Seek for EepromFile<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => (self.eeprom.model.size() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
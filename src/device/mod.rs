@@ -0,0 +1,68 @@
+//! Drivers for individual 1-Wire device families.
+//!
+//! Drivers are plain handles for a slave id. All bus traffic goes through the
+//! [`Transport`](crate::transport::Transport) passed to each operation.
+
+use crate::{
+    crc::{crc16, crc8},
+    transport::Error,
+};
+
+pub mod convert;
+pub mod ds2406;
+pub mod ds2408;
+pub mod ds2413;
+pub mod ds2423;
+pub mod ds2450;
+pub mod ds28ea00;
+pub mod eeprom;
+pub mod thermometer;
+
+/// ROM function commands, see e.g. the DS18B20 datasheet.
+pub mod rom {
+    pub const READ_ROM: u8 = 0x33;
+    pub const MATCH_ROM: u8 = 0x55;
+    pub const SKIP_ROM: u8 = 0xCC;
+    pub const SEARCH_ROM: u8 = 0xF0;
+    pub const ALARM_SEARCH: u8 = 0xEC;
+}
+
+/// Part number of the devices of `family` with a driver in this module.
+pub fn family_name(family: u8) -> Option<&'static str> {
+    match family {
+        thermometer::DS18B20_FAMILY => Some("DS18B20"),
+        thermometer::DS1822_FAMILY => Some("DS1822"),
+        ds28ea00::FAMILY => Some("DS28EA00"),
+        ds2406::FAMILY => Some("DS2406"),
+        ds2408::FAMILY => Some("DS2408"),
+        ds2413::FAMILY => Some("DS2413"),
+        ds2423::FAMILY => Some("DS2423"),
+        ds2450::FAMILY => Some("DS2450"),
+        eeprom::DS2433_FAMILY => Some("DS2433"),
+        eeprom::DS28EC20_FAMILY => Some("DS28EC20"),
+        _ => None,
+    }
+}
+
+/// Checks data followed by its CRC8 byte.
+pub(crate) fn check_crc8(data: &[u8]) -> Result<(), Error> {
+    let (data, crc) = data.split_at(data.len() - 1);
+    let expected = crc8(data);
+    if expected != crc[0] {
+        return Err(Error::Crc {
+            expected: expected.into(),
+            actual: crc[0].into(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks the inverted CRC16 a device sent after `data`.
+pub(crate) fn check_crc16(data: &[u8], crc: [u8; 2]) -> Result<(), Error> {
+    let expected = crc16(data);
+    let actual = !u16::from_le_bytes(crc);
+    if expected != actual {
+        return Err(Error::Crc { expected, actual });
+    }
+    Ok(())
+}
@@ -0,0 +1,190 @@
+//! Function commands shared by the DS18B20 style thermometers (DS18B20,
+//! DS1822, DS28EA00).
+
+use std::time::Duration;
+
+use super::{check_crc8, ds28ea00, rom::SKIP_ROM};
+use crate::{
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+pub const DS18B20_FAMILY: u8 = 0x28;
+pub const DS1822_FAMILY: u8 = 0x22;
+
+pub const CONVERT_T: u8 = 0x44;
+pub const WRITE_SCRATCHPAD: u8 = 0x4E;
+pub const READ_SCRATCHPAD: u8 = 0xBE;
+pub const COPY_SCRATCHPAD: u8 = 0x48;
+pub const RECALL_EEPROM: u8 = 0xB8;
+pub const READ_POWER_SUPPLY: u8 = 0xB4;
+
+/// Temperature in 1/16 °C, as stored in the scratchpad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Temperature(i16);
+
+impl Temperature {
+    pub fn from_raw(raw: i16) -> Self {
+        Self(raw)
+    }
+
+    /// Rounds to the nearest 1/16 °C.
+    pub fn from_celsius(celsius: f32) -> Self {
+        Self((celsius * 16.0).round() as i16)
+    }
+
+    pub fn raw(&self) -> i16 {
+        self.0
+    }
+
+    pub fn celsius(&self) -> f32 {
+        f32::from(self.0) / 16.0
+    }
+}
+
+/// Written as °C, which represents every raw value exactly.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Temperature {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.celsius())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Temperature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f32::deserialize(deserializer).map(Self::from_celsius)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolution {
+    Bits9,
+    Bits10,
+    Bits11,
+    Bits12,
+}
+
+impl Resolution {
+    /// Maximum conversion time according to the datasheets.
+    pub fn conversion_time(&self) -> Duration {
+        let ms = match self {
+            Resolution::Bits9 => 94,
+            Resolution::Bits10 => 188,
+            Resolution::Bits11 => 375,
+            Resolution::Bits12 => 750,
+        };
+        Duration::from_millis(ms)
+    }
+
+    pub fn from_config(config: u8) -> Self {
+        match config >> 5 & 0b11 {
+            0 => Resolution::Bits9,
+            1 => Resolution::Bits10,
+            2 => Resolution::Bits11,
+            _ => Resolution::Bits12,
+        }
+    }
+
+    /// Configuration register value selecting this resolution.
+    pub fn config(&self) -> u8 {
+        let bits = match self {
+            Resolution::Bits9 => 0,
+            Resolution::Bits10 => 1,
+            Resolution::Bits11 => 2,
+            Resolution::Bits12 => 3,
+        };
+        bits << 5 | 0x1F
+    }
+}
+
+/// Scratchpad contents including the CRC byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scratchpad(pub [u8; 9]);
+
+impl Scratchpad {
+    pub const LEN: usize = 9;
+
+    pub fn temperature(&self) -> Temperature {
+        Temperature(i16::from_le_bytes([self.0[0], self.0[1]]))
+    }
+
+    /// Upper alarm threshold (TH) in °C.
+    pub fn alarm_high(&self) -> i8 {
+        self.0[2] as i8
+    }
+
+    /// Lower alarm threshold (TL) in °C.
+    pub fn alarm_low(&self) -> i8 {
+        self.0[3] as i8
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        Resolution::from_config(self.0[4])
+    }
+}
+
+/// Starts a temperature conversion. Wait for [`Resolution::conversion_time`]
+/// before reading the scratchpad.
+pub fn convert(t: &mut impl Transport, id: SlaveId) -> Result<(), Error> {
+    t.slave_command(id, vec![W1NetlinkCommand::Reset, write([CONVERT_T])])?;
+    Ok(())
+}
+
+/// Starts a conversion on all thermometers of `master` at once.
+pub fn convert_all(t: &mut impl Transport, master: u32) -> Result<(), Error> {
+    t.master_command(
+        master,
+        vec![W1NetlinkCommand::Reset, write([SKIP_ROM, CONVERT_T])],
+    )?;
+    Ok(())
+}
+
+/// Whether `family` uses the thermometer function commands of this module.
+pub fn is_thermometer(family: u8) -> bool {
+    matches!(family, DS18B20_FAMILY | DS1822_FAMILY | ds28ea00::FAMILY)
+}
+
+pub fn read_scratchpad(t: &mut impl Transport, id: SlaveId) -> Result<Scratchpad, Error> {
+    let cmds = vec![
+        W1NetlinkCommand::Reset,
+        write([READ_SCRATCHPAD]),
+        read(Scratchpad::LEN),
+    ];
+    let data = read_data(t.slave_command(id, cmds)?);
+    let data = data.first().ok_or(Error::NoDevice)?;
+    if data.len() != Scratchpad::LEN {
+        return Err(Error::Device("short scratchpad read"));
+    }
+    if data.iter().all(|b| *b == 0xFF) {
+        return Err(Error::NoDevice);
+    }
+    check_crc8(data)?;
+    Ok(Scratchpad(data[..].try_into().unwrap()))
+}
+
+/// Writes alarm thresholds and resolution to the scratchpad.
+pub fn write_scratchpad(
+    t: &mut impl Transport,
+    id: SlaveId,
+    alarm_high: i8,
+    alarm_low: i8,
+    resolution: Resolution,
+) -> Result<(), Error> {
+    let data = [
+        WRITE_SCRATCHPAD,
+        alarm_high as u8,
+        alarm_low as u8,
+        resolution.config(),
+    ];
+    t.slave_command(id, vec![W1NetlinkCommand::Reset, write(data)])?;
+    Ok(())
+}
+
+/// Stores thresholds and configuration from the scratchpad in EEPROM.
+pub fn copy_scratchpad(t: &mut impl Transport, id: SlaveId) -> Result<(), Error> {
+    t.slave_command(id, vec![W1NetlinkCommand::Reset, write([COPY_SCRATCHPAD])])?;
+    Ok(())
+}
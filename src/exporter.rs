@@ -0,0 +1,284 @@
+//! Prometheus metrics for the devices on all buses.
+//!
+//! [`Collector::scrape`] searches every bus, starts a conversion on all
+//! thermometers and A/D converters at once and reads every device with a
+//! driver in [`device`](crate::device). [`Collector::render`] writes the
+//! readings of the last scrape and the error counters in the Prometheus text
+//! format, which [`serve`] answers `GET /metrics` with.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read as _, Write as _},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    device::{convert, family_name},
+    reading::{read_device, Quantity},
+    sink::Aliases,
+    transport::{Error, Transport},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Metric {
+    Temperature,
+    Voltage,
+    Switch,
+    Output,
+    Counter,
+    Devices,
+    SearchDuration,
+    CrcErrors,
+    ReadErrors,
+    BusErrors,
+    Scrapes,
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::Temperature => "w1_temperature_celsius",
+            Metric::Voltage => "w1_voltage_volts",
+            Metric::Switch => "w1_switch_state",
+            Metric::Output => "w1_switch_output",
+            Metric::Counter => "w1_counter_total",
+            Metric::Devices => "w1_devices",
+            Metric::SearchDuration => "w1_search_duration_seconds",
+            Metric::CrcErrors => "w1_crc_errors_total",
+            Metric::ReadErrors => "w1_read_errors_total",
+            Metric::BusErrors => "w1_bus_errors_total",
+            Metric::Scrapes => "w1_scrapes_total",
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        match self {
+            Metric::Temperature => "Temperature measured by a thermometer.",
+            Metric::Voltage => "Voltage measured by an A/D converter channel.",
+            Metric::Switch => "Sensed level of a switch channel, 1 if high.",
+            Metric::Output => "Output transistor of a switch channel, 1 if on.",
+            Metric::Counter => "Pulses counted on a counter input.",
+            Metric::Devices => "Devices found by the last search.",
+            Metric::SearchDuration => "Duration of the last search of a bus.",
+            Metric::CrcErrors => "Device reads failing the CRC check.",
+            Metric::ReadErrors => "Device reads failing for other reasons.",
+            Metric::BusErrors => "Failed searches and conversions on a bus.",
+            Metric::Scrapes => "Scrapes of all buses.",
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Metric::Counter
+            | Metric::CrcErrors
+            | Metric::ReadErrors
+            | Metric::BusErrors
+            | Metric::Scrapes => "counter",
+            _ => "gauge",
+        }
+    }
+}
+
+/// Escapes a label value of the text format.
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Samples by metric and label set.
+type Samples = BTreeMap<(Metric, String), f64>;
+
+#[derive(Debug, Clone)]
+pub struct Collector {
+    conversion_time: Duration,
+    aliases: Aliases,
+    /// Readings of the last scrape.
+    gauges: Samples,
+    counters: Samples,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self {
+            conversion_time: convert::conversion_time(),
+            aliases: Aliases::new(),
+            gauges: Samples::new(),
+            counters: Samples::new(),
+        }
+    }
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time to wait between starting the conversions and reading the devices.
+    pub fn with_conversion_time(self, conversion_time: Duration) -> Self {
+        Self {
+            conversion_time,
+            ..self
+        }
+    }
+
+    /// Names added to the samples of a device as `name` label.
+    pub fn with_aliases(self, aliases: Aliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Reads all devices on all buses. Errors of single buses and devices are
+    /// counted, only failing to list the masters is returned.
+    pub fn scrape(&mut self, t: &mut impl Transport) -> Result<(), Error> {
+        self.gauges.clear();
+        *self
+            .counters
+            .entry((Metric::Scrapes, String::new()))
+            .or_default() += 1.0;
+        for master in t.list_masters()? {
+            let labels = format!("master=\"{master}\"");
+            if self.scrape_bus(t, master).is_err() {
+                *self
+                    .counters
+                    .entry((Metric::BusErrors, labels))
+                    .or_default() += 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    fn scrape_bus(&mut self, t: &mut impl Transport, master: u32) -> Result<(), Error> {
+        let labels = format!("master=\"{master}\"");
+        let start = Instant::now();
+        let ids = t.search(master, false)?;
+        let duration = start.elapsed().as_secs_f64();
+        self.gauges
+            .insert((Metric::SearchDuration, labels.clone()), duration);
+        self.gauges
+            .insert((Metric::Devices, labels), ids.len() as f64);
+
+        convert::start(t, master)?;
+        thread::sleep(self.conversion_time);
+        for id in ids {
+            let family = match family_name(id.family()) {
+                Some(name) => name.to_string(),
+                None => format!("{:02x}", id.family()),
+            };
+            let mut labels = format!("master=\"{master}\",rom=\"{id}\",family=\"{family}\"");
+            if let Some(name) = self.aliases.get(&id) {
+                write!(labels, ",name=\"{}\"", label_value(name)).unwrap();
+            }
+            let readings = match read_device(t, id) {
+                Ok(readings) => readings,
+                Err(err) => {
+                    let metric = match err {
+                        Error::Crc { .. } => Metric::CrcErrors,
+                        _ => Metric::ReadErrors,
+                    };
+                    *self.counters.entry((metric, labels)).or_default() += 1.0;
+                    continue;
+                }
+            };
+            for reading in readings {
+                let metric = match reading.quantity {
+                    Quantity::Temperature => Metric::Temperature,
+                    Quantity::Voltage => Metric::Voltage,
+                    Quantity::Level => Metric::Switch,
+                    Quantity::Output => Metric::Output,
+                    Quantity::Count => Metric::Counter,
+                };
+                let labels = match reading.channel {
+                    Some(channel) => format!("{labels},channel=\"{channel}\""),
+                    None => labels.clone(),
+                };
+                self.gauges.insert((metric, labels), reading.value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut samples: Vec<_> = self.gauges.iter().chain(&self.counters).collect();
+        samples.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut out = String::new();
+        let mut current = None;
+        for ((metric, labels), value) in samples {
+            if current != Some(metric) {
+                let name = metric.name();
+                writeln!(out, "# HELP {name} {}", metric.help()).unwrap();
+                writeln!(out, "# TYPE {name} {}", metric.kind()).unwrap();
+                current = Some(metric);
+            }
+            match labels.is_empty() {
+                true => writeln!(out, "{} {value}", metric.name()).unwrap(),
+                false => writeln!(out, "{}{{{labels}}} {value}", metric.name()).unwrap(),
+            }
+        }
+        out
+    }
+}
+
+/// Time a client has to send its request and take the response, so a stalled
+/// client cannot block the others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Limit of request line and headers, which are read and dropped.
+const MAX_REQUEST_LEN: u64 = 8192;
+/// Pause after a failed accept, which fails again right away e.g. while out
+/// of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Answers HTTP requests on `listener` one at a time, serving the text
+/// returned by `metrics` on `GET /metrics`. Failing to accept a connection
+/// is passed to `on_error` and does not stop serving, nor do clients going
+/// away.
+pub fn serve(
+    listener: &TcpListener,
+    metrics: impl Fn() -> String,
+    mut on_error: impl FnMut(io::Error),
+) -> ! {
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = respond(stream, &metrics);
+            }
+            Err(err) => {
+                on_error(err);
+                thread::sleep(ACCEPT_BACKOFF);
+            }
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &impl Fn() -> String) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream).take(MAX_REQUEST_LEN);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // skip the headers, there is no request body to expect
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics()),
+        _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
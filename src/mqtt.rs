@@ -0,0 +1,499 @@
+//! Bridge between the devices on all buses and an MQTT broker, announcing
+//! them to Home Assistant through MQTT discovery.
+//!
+//! Every device with a driver in [`device`](crate::device) becomes a Home
+//! Assistant device identified by its ROM, with one entity per reading or
+//! output. The retained discovery configs are published to
+//! `<prefix>/<component>/w1_<rom>/<object>/config`, states to
+//! `<base>/<rom>/<object>`. Outputs of DS2413 and DS2408 switches accept `ON`
+//! and `OFF` on `<base>/<rom>/<object>/set`, `ON` turns the output transistor
+//! on.
+//!
+//! [`Bridge`] only tells what to publish, the `w1-mqtt` binary connects it to
+//! a broker.
+
+use std::{collections::BTreeMap, str, thread, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    device::{
+        convert, ds2406,
+        ds2408::{self, Ds2408},
+        ds2413::{self, Ds2413},
+        ds2450::{self, Channel},
+        ds28ea00, family_name, thermometer,
+    },
+    proto::{
+        command::SlaveId,
+        message::{EventKind, W1NetlinkMessage},
+    },
+    reading::{read_device, Quantity, Reading},
+    sink::Aliases,
+    transport::{self, Transport},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Transport(#[from] transport::Error),
+
+    #[error("Not the command topic of a known output: {0}")]
+    Topic(String),
+
+    #[error("Expected ON or OFF, got {0:?}")]
+    Payload(String),
+}
+
+/// A message for the broker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Publish {
+    pub topic: String,
+    /// Empty to delete a retained message.
+    pub payload: String,
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Temperature,
+    Voltage,
+    /// Sensed level of a switch channel.
+    Input,
+    /// Output transistor of a switch channel.
+    Output,
+}
+
+impl Kind {
+    fn quantity(&self) -> Quantity {
+        match self {
+            Kind::Temperature => Quantity::Temperature,
+            Kind::Voltage => Quantity::Voltage,
+            Kind::Input => Quantity::Level,
+            Kind::Output => Quantity::Output,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entity {
+    kind: Kind,
+    channel: u8,
+    /// Channel of the readings, empty for single channel devices.
+    label: String,
+    /// Topic level and suffix of the unique id.
+    object: String,
+    name: String,
+}
+
+impl Entity {
+    fn new(kind: Kind, channel: u8, label: &str) -> Self {
+        let (object, name) = match kind {
+            Kind::Temperature => ("temperature", "Temperature"),
+            Kind::Voltage => ("voltage", "Voltage"),
+            Kind::Input => ("input", "Input"),
+            Kind::Output => ("output", "Output"),
+        };
+        match label.is_empty() {
+            true => Self {
+                kind,
+                channel,
+                label: String::new(),
+                object: object.to_string(),
+                name: name.to_string(),
+            },
+            false => Self {
+                kind,
+                channel,
+                label: label.to_string(),
+                object: format!("{object}_{}", label.to_lowercase()),
+                name: format!("{name} {label}"),
+            },
+        }
+    }
+
+    /// Whether `reading` holds the state of this entity.
+    fn reads(&self, reading: &Reading) -> bool {
+        reading.quantity == self.kind.quantity()
+            && reading.channel.unwrap_or_default() == self.label
+    }
+
+    fn component(&self) -> &'static str {
+        match self.kind {
+            Kind::Temperature | Kind::Voltage => "sensor",
+            Kind::Input => "binary_sensor",
+            Kind::Output => "switch",
+        }
+    }
+}
+
+/// Entities of the devices of `family`, empty without a driver.
+fn entities(family: u8) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    if thermometer::is_thermometer(family) {
+        entities.push(Entity::new(Kind::Temperature, 0, ""));
+    }
+    match family {
+        ds2450::FAMILY => {
+            for c in Channel::ALL {
+                entities.push(Entity::new(Kind::Voltage, c as u8, &format!("{c:?}")));
+            }
+        }
+        // whether a DS2406 has channel B depends on the package, see
+        // `Bridge::announce_channel_b`
+        ds2406::FAMILY => {
+            entities.push(Entity::new(Kind::Input, 0, "A"));
+        }
+        ds28ea00::FAMILY => {
+            entities.push(Entity::new(Kind::Input, 0, "A"));
+            entities.push(Entity::new(Kind::Input, 1, "B"));
+        }
+        ds2413::FAMILY => {
+            entities.push(Entity::new(Kind::Output, 0, "A"));
+            entities.push(Entity::new(Kind::Output, 1, "B"));
+        }
+        ds2408::FAMILY => {
+            for channel in 0..8 {
+                entities.push(Entity::new(Kind::Output, channel, &channel.to_string()));
+            }
+        }
+        _ => {}
+    }
+    entities
+}
+
+fn on_off(on: bool) -> String {
+    match on {
+        true => "ON".to_string(),
+        false => "OFF".to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bridge {
+    base_topic: String,
+    discovery_prefix: String,
+    conversion_time: Duration,
+    aliases: Aliases,
+    /// Announced devices.
+    devices: BTreeMap<SlaveId, Vec<Entity>>,
+}
+
+impl Default for Bridge {
+    fn default() -> Self {
+        Self {
+            base_topic: "w1".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+            conversion_time: convert::conversion_time(),
+            aliases: Aliases::new(),
+            devices: BTreeMap::new(),
+        }
+    }
+}
+
+impl Bridge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Topic prefix of states and commands, `w1` by default.
+    pub fn with_base_topic(self, base_topic: impl Into<String>) -> Self {
+        Self {
+            base_topic: base_topic.into(),
+            ..self
+        }
+    }
+
+    /// Discovery prefix configured in Home Assistant, `homeassistant` by default.
+    pub fn with_discovery_prefix(self, discovery_prefix: impl Into<String>) -> Self {
+        Self {
+            discovery_prefix: discovery_prefix.into(),
+            ..self
+        }
+    }
+
+    /// Time to wait between starting the conversions and reading the devices.
+    pub fn with_conversion_time(self, conversion_time: Duration) -> Self {
+        Self {
+            conversion_time,
+            ..self
+        }
+    }
+
+    /// Device names shown in Home Assistant instead of model and ROM.
+    pub fn with_aliases(self, aliases: Aliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Topic for `online` and `offline` of the bridge itself, meant to be
+    /// published retained and as last will.
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.base_topic)
+    }
+
+    /// Topic filter matching the command topics of all outputs.
+    pub fn command_filter(&self) -> String {
+        format!("{}/+/+/set", self.base_topic)
+    }
+
+    /// Announced devices.
+    pub fn devices(&self) -> impl Iterator<Item = SlaveId> + '_ {
+        self.devices.keys().copied()
+    }
+
+    fn state_topic(&self, id: SlaveId, entity: &Entity) -> String {
+        format!("{}/{id}/{}", self.base_topic, entity.object)
+    }
+
+    fn device_availability_topic(&self, id: SlaveId) -> String {
+        format!("{}/{id}/status", self.base_topic)
+    }
+
+    fn config_topic(&self, id: SlaveId, entity: &Entity) -> String {
+        format!(
+            "{}/{}/w1_{id}/{}/config",
+            self.discovery_prefix,
+            entity.component(),
+            entity.object
+        )
+    }
+
+    fn config(&self, id: SlaveId, entity: &Entity) -> Value {
+        let model = family_name(id.family()).unwrap_or_default();
+        let name = match self.aliases.get(&id) {
+            Some(alias) => alias.clone(),
+            None => format!("{model} {id}"),
+        };
+        let mut config = json!({
+            "name": entity.name,
+            "unique_id": format!("w1_{id}_{}", entity.object),
+            "state_topic": self.state_topic(id, entity),
+            "availability": [
+                {"topic": self.availability_topic()},
+                {"topic": self.device_availability_topic(id)},
+            ],
+            "availability_mode": "all",
+            "device": {
+                "identifiers": [format!("w1_{id}")],
+                "name": name,
+                "model": model,
+                "serial_number": id.to_string(),
+            },
+        });
+        let extra = match entity.kind {
+            Kind::Temperature => json!({
+                "device_class": "temperature",
+                "unit_of_measurement": "°C",
+                "state_class": "measurement",
+            }),
+            Kind::Voltage => json!({
+                "device_class": "voltage",
+                "unit_of_measurement": "V",
+                "state_class": "measurement",
+            }),
+            Kind::Input => json!({}),
+            Kind::Output => json!({
+                "command_topic": format!("{}/set", self.state_topic(id, entity)),
+            }),
+        };
+        if let (Value::Object(config), Value::Object(extra)) = (&mut config, extra) {
+            config.extend(extra);
+        }
+        config
+    }
+
+    fn configs(&self, id: SlaveId, entities: &[Entity]) -> Vec<Publish> {
+        entities
+            .iter()
+            .map(|entity| Publish {
+                topic: self.config_topic(id, entity),
+                payload: self.config(id, entity).to_string(),
+                retain: true,
+            })
+            .collect()
+    }
+
+    /// Announces a device unless it is known already or has no driver.
+    pub fn announce(&mut self, id: SlaveId) -> Vec<Publish> {
+        let entities = entities(id.family());
+        if entities.is_empty() || self.devices.contains_key(&id) {
+            return Vec::new();
+        }
+        let configs = self.configs(id, &entities);
+        self.devices.insert(id, entities);
+        configs
+    }
+
+    /// Announces channel B of a DS2406 once its readings tell that the
+    /// package provides it.
+    fn announce_channel_b(&mut self, id: SlaveId, readings: &[Reading]) -> Vec<Publish> {
+        let Some(entities) = self.devices.get_mut(&id) else {
+            return Vec::new();
+        };
+        let entity = Entity::new(Kind::Input, 1, "B");
+        if id.family() != ds2406::FAMILY
+            || entities.contains(&entity)
+            || !readings.iter().any(|reading| entity.reads(reading))
+        {
+            return Vec::new();
+        }
+        entities.push(entity.clone());
+        self.configs(id, &[entity])
+    }
+
+    /// Removes the entities of a device from Home Assistant and clears its
+    /// retained states.
+    pub fn remove(&mut self, id: SlaveId) -> Vec<Publish> {
+        let Some(entities) = self.devices.remove(&id) else {
+            return Vec::new();
+        };
+        let clear = |topic| Publish {
+            topic,
+            payload: String::new(),
+            retain: true,
+        };
+        let mut out = Vec::new();
+        for entity in &entities {
+            out.push(clear(self.config_topic(id, entity)));
+            out.push(clear(self.state_topic(id, entity)));
+        }
+        out.push(clear(self.device_availability_topic(id)));
+        out
+    }
+
+    /// Discovery configs of all announced devices, e.g. to publish again
+    /// after reconnecting to a broker without persistence.
+    pub fn announcements(&self) -> Vec<Publish> {
+        self.devices
+            .iter()
+            .flat_map(|(id, entities)| self.configs(*id, entities))
+            .collect()
+    }
+
+    /// Creates and removes entities for hotplug events.
+    pub fn handle_event(&mut self, msg: &W1NetlinkMessage) -> Vec<Publish> {
+        match msg {
+            W1NetlinkMessage::SlaveEvent {
+                kind: EventKind::Add,
+                target,
+            } => self.announce(SlaveId::from(*target)),
+            W1NetlinkMessage::SlaveEvent {
+                kind: EventKind::Remove,
+                target,
+            } => self.remove(SlaveId::from(*target)),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Searches all buses, announces devices not known yet and publishes the
+    /// states of all devices found. Devices failing to read are marked
+    /// unavailable, only failing to list the masters is returned.
+    pub fn poll(&mut self, t: &mut impl Transport) -> Result<Vec<Publish>, transport::Error> {
+        let mut out = Vec::new();
+        for master in t.list_masters()? {
+            // a failing bus must not keep the others from being published
+            let Ok(ids) = t.search(master, false) else {
+                continue;
+            };
+            for id in &ids {
+                out.extend(self.announce(*id));
+            }
+            let converted = convert::start(t, master).is_ok();
+            if converted {
+                thread::sleep(self.conversion_time);
+            }
+            for id in ids {
+                if !self.devices.contains_key(&id) {
+                    continue;
+                }
+                let readings = match converted {
+                    true => read_device(t, id),
+                    false => Err(transport::Error::Device("conversion failed")),
+                };
+                if let Ok(readings) = &readings {
+                    out.extend(self.announce_channel_b(id, readings));
+                }
+                let entities = &self.devices[&id];
+                let available = readings.is_ok();
+                for (entity, state) in states(entities, &readings.unwrap_or_default()) {
+                    out.push(Publish {
+                        topic: self.state_topic(id, entity),
+                        payload: state,
+                        retain: true,
+                    });
+                }
+                out.push(Publish {
+                    topic: self.device_availability_topic(id),
+                    payload: if available { "online" } else { "offline" }.to_string(),
+                    retain: true,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Switches an output for a message received on its command topic and
+    /// returns its new state.
+    pub fn command(
+        &mut self,
+        t: &mut impl Transport,
+        topic: &str,
+        payload: &[u8],
+    ) -> Result<Vec<Publish>, Error> {
+        let unknown = || Error::Topic(topic.to_string());
+        let (id, object) = topic
+            .strip_prefix(&self.base_topic)
+            .and_then(|topic| topic.strip_prefix('/'))
+            .and_then(|topic| topic.strip_suffix("/set"))
+            .and_then(|topic| topic.split_once('/'))
+            .ok_or_else(unknown)?;
+        let id: SlaveId = id.parse().map_err(|_| unknown())?;
+        let entity = self
+            .devices
+            .get(&id)
+            .and_then(|entities| entities.iter().find(|e| e.object == object))
+            .filter(|entity| entity.kind == Kind::Output)
+            .ok_or_else(unknown)?;
+        let on = match str::from_utf8(payload) {
+            Ok("ON") => true,
+            Ok("OFF") => false,
+            _ => return Err(Error::Payload(String::from_utf8_lossy(payload).into())),
+        };
+
+        match id.family() {
+            ds2413::FAMILY => {
+                let dev = Ds2413::new(id);
+                let state = dev.read_pio(t)?;
+                let (a, b) = match entity.channel {
+                    0 => (!on, state.b_latch()),
+                    _ => (state.a_latch(), !on),
+                };
+                dev.write_pio(t, a, b)?;
+            }
+            ds2408::FAMILY => {
+                Ds2408::new(id).set_latch(t, entity.channel, !on)?;
+            }
+            _ => return Err(unknown()),
+        }
+        Ok(vec![Publish {
+            topic: self.state_topic(id, entity),
+            payload: on_off(on),
+            retain: true,
+        }])
+    }
+}
+
+/// States of the entities of a device with a reading.
+fn states<'a>(entities: &'a [Entity], readings: &[Reading]) -> Vec<(&'a Entity, String)> {
+    entities
+        .iter()
+        .filter_map(|entity| {
+            let reading = readings.iter().find(|reading| entity.reads(reading))?;
+            let state = match entity.kind {
+                Kind::Temperature | Kind::Voltage => reading.value.to_string(),
+                Kind::Input | Kind::Output => on_off(reading.value != 0.0),
+            };
+            Some((entity, state))
+        })
+        .collect()
+}
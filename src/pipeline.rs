@@ -0,0 +1,307 @@
+//! Post-processing of [`Reading`]s: plausibility checks, calibration and
+//! smoothing.
+//!
+//! Every temperature and voltage passes three steps, levels and outputs of
+//! switches and counts pass unchanged:
+//!
+//! 1. Values the devices report instead of a measurement are rejected, like
+//!    the 85 °C a thermometer holds after power-on until its first conversion
+//!    and the −127 °C some drivers report for a disconnected thermometer.
+//! 2. The calibration of the device is applied, `value * gain + offset`.
+//! 3. Values outside the plausible range of their quantity are rejected, the
+//!    others pass the smoothing stages one after the other.
+//!
+//! Rejected readings do not affect the smoothing and are returned with the
+//! reason, so they can be logged or counted.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    ops::RangeInclusive,
+};
+
+#[cfg(feature = "registry")]
+use crate::registry::Registry;
+use crate::{
+    proto::command::SlaveId,
+    reading::{Quantity, Reading},
+};
+
+/// Temperature of a thermometer after power-on, before its first conversion.
+pub const POWER_ON_CELSIUS: f64 = 85.0;
+/// Temperature reported for a disconnected thermometer.
+pub const DISCONNECTED_CELSIUS: f64 = -127.0;
+
+/// Linear calibration, `value * gain + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub offset: f64,
+    pub gain: f64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn offset(offset: f64) -> Self {
+        Self {
+            offset,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_gain(self, gain: f64) -> Self {
+        Self { gain, ..self }
+    }
+
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.gain + self.offset
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Median of the last `n` values, of fewer until `n` were seen.
+    Median(usize),
+    /// Exponential moving average, weighting the newest value by `alpha`
+    /// between 0 and 1.
+    Ema(f64),
+}
+
+/// Why a reading was rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// The power-on value of a thermometer that has not converted yet.
+    PowerOn,
+    /// The value reported for a disconnected thermometer.
+    Disconnected,
+    /// The calibrated value is outside the plausible range.
+    OutOfRange(f64),
+    /// Not a number.
+    NotANumber,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::PowerOn => write!(f, "power-on value"),
+            Reason::Disconnected => write!(f, "disconnected"),
+            Reason::OutOfRange(value) => write!(f, "{value} out of range"),
+            Reason::NotANumber => write!(f, "not a number"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    /// The reading as the device reported it.
+    pub reading: Reading,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Default)]
+pub struct Processed {
+    pub readings: Vec<Reading>,
+    pub rejected: Vec<Rejection>,
+}
+
+/// Smoothing state of one quantity of one device.
+#[derive(Debug, Clone, Default)]
+struct State {
+    windows: Vec<VecDeque<f64>>,
+    averages: Vec<Option<f64>>,
+}
+
+impl State {
+    fn smooth(&mut self, stages: &[Smoothing], mut value: f64) -> f64 {
+        self.windows.resize_with(stages.len(), VecDeque::new);
+        self.averages.resize(stages.len(), None);
+        for (i, stage) in stages.iter().enumerate() {
+            value = match *stage {
+                Smoothing::Median(n) => {
+                    let window = &mut self.windows[i];
+                    window.push_back(value);
+                    while window.len() > n.max(1) {
+                        window.pop_front();
+                    }
+                    median(window)
+                }
+                Smoothing::Ema(alpha) => {
+                    let alpha = alpha.clamp(0.0, 1.0);
+                    let average = match self.averages[i] {
+                        Some(average) => alpha * value + (1.0 - alpha) * average,
+                        None => value,
+                    };
+                    self.averages[i] = Some(average);
+                    average
+                }
+            };
+        }
+        value
+    }
+}
+
+fn median(window: &VecDeque<f64>) -> f64 {
+    let mut sorted: Vec<f64> = window.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        _ => sorted[mid],
+    }
+}
+
+type Key = (SlaveId, Quantity, Option<&'static str>);
+
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    reject_markers: bool,
+    ranges: BTreeMap<Quantity, RangeInclusive<f64>>,
+    smoothing: Vec<Smoothing>,
+    calibrations: BTreeMap<SlaveId, Calibration>,
+    device_smoothing: BTreeMap<SlaveId, Vec<Smoothing>>,
+    states: BTreeMap<Key, State>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            reject_markers: true,
+            // the measurement ranges of the thermometers and the DS2450
+            ranges: BTreeMap::from([
+                (Quantity::Temperature, -55.0..=125.0),
+                (Quantity::Voltage, 0.0..=5.1),
+            ]),
+            smoothing: Vec::new(),
+            calibrations: BTreeMap::new(),
+            device_smoothing: BTreeMap::new(),
+            states: BTreeMap::new(),
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to reject the power-on and disconnected values of
+    /// thermometers, on by default. 85 °C is a valid temperature too, turn
+    /// this off when it can occur.
+    pub fn with_reject_markers(self, reject_markers: bool) -> Self {
+        Self {
+            reject_markers,
+            ..self
+        }
+    }
+
+    /// Plausible calibrated values of a quantity, −55 to 125 °C and 0 to
+    /// 5.1 V by default.
+    pub fn with_range(mut self, quantity: Quantity, range: RangeInclusive<f64>) -> Self {
+        self.ranges.insert(quantity, range);
+        self
+    }
+
+    /// Smoothing of devices without one of their own, none by default.
+    pub fn with_smoothing(self, smoothing: Vec<Smoothing>) -> Self {
+        Self { smoothing, ..self }
+    }
+
+    /// Takes the offsets and gains of the registered devices.
+    #[cfg(feature = "registry")]
+    pub fn with_registry(mut self, registry: &Registry) -> Self {
+        for (id, info) in registry.devices() {
+            if info.offset.is_some() || info.gain.is_some() {
+                let calibration = Calibration {
+                    offset: info.offset.unwrap_or(0.0),
+                    gain: info.gain.unwrap_or(1.0),
+                };
+                self.calibrations.insert(id, calibration);
+            }
+        }
+        self
+    }
+
+    /// Sets the calibration of all temperatures and voltages of a device.
+    pub fn set_calibration(&mut self, id: SlaveId, calibration: Calibration) {
+        self.calibrations.insert(id, calibration);
+    }
+
+    /// Sets the smoothing of a device and restarts it.
+    pub fn set_smoothing(&mut self, id: SlaveId, smoothing: Vec<Smoothing>) {
+        self.device_smoothing.insert(id, smoothing);
+        self.forget(id);
+    }
+
+    /// Restarts the smoothing of a device, e.g. after it was replaced.
+    pub fn forget(&mut self, id: SlaveId) {
+        self.states.retain(|(rom, _, _), _| *rom != id);
+    }
+
+    fn check_raw(&self, reading: &Reading) -> Result<(), Reason> {
+        if reading.value.is_nan() {
+            return Err(Reason::NotANumber);
+        }
+        if self.reject_markers && reading.quantity == Quantity::Temperature {
+            if reading.value == POWER_ON_CELSIUS {
+                return Err(Reason::PowerOn);
+            }
+            if reading.value == DISCONNECTED_CELSIUS {
+                return Err(Reason::Disconnected);
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes one reading, returning it calibrated and smoothed.
+    pub fn process_one(&mut self, reading: Reading) -> Result<Reading, Rejection> {
+        if matches!(
+            reading.quantity,
+            Quantity::Level | Quantity::Output | Quantity::Count
+        ) {
+            return Ok(reading);
+        }
+        let reject = |reading, reason| Rejection { reading, reason };
+        if let Err(reason) = self.check_raw(&reading) {
+            return Err(reject(reading, reason));
+        }
+        let calibration = self
+            .calibrations
+            .get(&reading.rom)
+            .copied()
+            .unwrap_or_default();
+        let value = calibration.apply(reading.value);
+        if let Some(range) = self.ranges.get(&reading.quantity) {
+            if !range.contains(&value) {
+                return Err(reject(reading, Reason::OutOfRange(value)));
+            }
+        }
+        let stages = self
+            .device_smoothing
+            .get(&reading.rom)
+            .unwrap_or(&self.smoothing);
+        let state = self
+            .states
+            .entry((reading.rom, reading.quantity, reading.channel))
+            .or_default();
+        let value = state.smooth(stages, value);
+        Ok(Reading { value, ..reading })
+    }
+
+    pub fn process(&mut self, readings: impl IntoIterator<Item = Reading>) -> Processed {
+        let mut processed = Processed::default();
+        for reading in readings {
+            match self.process_one(reading) {
+                Ok(reading) => processed.readings.push(reading),
+                Err(rejection) => processed.rejected.push(rejection),
+            }
+        }
+        processed
+    }
+}
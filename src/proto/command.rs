@@ -1,12 +1,17 @@
-use std::mem;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{fmt, str::FromStr};
 
 use self::raw::W1NetlinkCmd;
-use super::{Deserializable, InvalidLength, InvalidValue, Serializable};
+use super::{
+    check_len, len_field, read_header, take, Deserializable, Hex, InvalidLength, InvalidValue,
+    RawHeader, Serializable, SerializeError, Truncated,
+};
+use crate::crc::crc8;
 
 mod raw {
     //! Taken from https://www.kernel.org/doc/Documentation/w1/w1.netlink
 
-    use safe_transmute::TriviallyTransmutable;
+    use crate::proto::{ne_u16, RawHeader};
 
     pub mod constants {
         pub const W1_CMD_READ: u8 = 0;
@@ -23,8 +28,7 @@ mod raw {
     }
 
     /// Command for given master or slave device
-    #[repr(C)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Default)]
     pub struct W1NetlinkCmd {
         /// Command opcode. See also [constants].
         pub cmd: u8,
@@ -34,10 +38,27 @@ mod raw {
         pub len: u16,
     }
 
-    unsafe impl TriviallyTransmutable for W1NetlinkCmd {}
+    impl RawHeader for W1NetlinkCmd {
+        const LEN: usize = 4;
+
+        fn decode(bytes: &[u8]) -> Self {
+            Self {
+                cmd: bytes[0],
+                _res: bytes[1],
+                len: ne_u16(bytes, 2),
+            }
+        }
+
+        fn encode(&self, buffer: &mut [u8]) {
+            buffer[0] = self.cmd;
+            buffer[1] = self._res;
+            buffer[2..4].copy_from_slice(&self.len.to_ne_bytes());
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum W1CommandType {
     Read,
     Write,
@@ -88,129 +109,491 @@ impl From<W1CommandType> for u8 {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Names as in the kernel's `W1_CMD_*` constants.
+impl fmt::Display for W1CommandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            W1CommandType::Read => "READ",
+            W1CommandType::Write => "WRITE",
+            W1CommandType::Search => "SEARCH",
+            W1CommandType::AlarmSearch => "ALARM_SEARCH",
+            W1CommandType::Touch => "TOUCH",
+            W1CommandType::Reset => "RESET",
+            W1CommandType::SlaveAdd => "SLAVE_ADD",
+            W1CommandType::SlaveRemove => "SLAVE_REMOVE",
+            W1CommandType::ListSlaves => "LIST_SLAVES",
+        })
+    }
+}
+
+/// 64 bit ROM id of a slave device in bus order: family code, 48 bit serial
+/// number (least significant byte first) and CRC8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SlaveId([u8; 8]);
 
-#[derive(Debug, Clone)]
+impl SlaveId {
+    pub const LEN: usize = 8;
+
+    pub fn new(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+
+    /// Builds an id from family code and serial number, calculating the CRC.
+    pub fn from_parts(family: u8, serial: u64) -> Self {
+        let mut bytes = [0; 8];
+        bytes[0] = family;
+        bytes[1..7].copy_from_slice(&serial.to_le_bytes()[..6]);
+        bytes[7] = crc8(&bytes[..7]);
+        Self(bytes)
+    }
+
+    pub fn bytes(&self) -> [u8; 8] {
+        self.0
+    }
+
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn serial(&self) -> u64 {
+        let mut serial = [0; 8];
+        serial[..6].copy_from_slice(&self.0[1..7]);
+        u64::from_le_bytes(serial)
+    }
+
+    pub fn crc(&self) -> u8 {
+        self.0[7]
+    }
+
+    /// Whether the CRC byte matches family code and serial number.
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0[..7]) == self.crc()
+    }
+}
+
+/// The kernel passes ids as `struct w1_reg_num`, the ROM read least
+/// significant bit first into a `u64` in host byte order.
+impl From<u64> for SlaveId {
+    fn from(id: u64) -> Self {
+        Self(id.to_le_bytes())
+    }
+}
+
+impl From<SlaveId> for u64 {
+    fn from(id: SlaveId) -> Self {
+        u64::from_le_bytes(id.0)
+    }
+}
+
+/// Formats the id like the kernel's sysfs entries, e.g. `28-0000056c3a1f`.
+impl fmt::Display for SlaveId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}-{:012x}", self.family(), self.serial())
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseSlaveIdError(String);
+
+impl fmt::Display for ParseSlaveIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid slave id: {}", self.0)
+    }
+}
+
+impl core::error::Error for ParseSlaveIdError {}
+
+impl FromStr for SlaveId {
+    type Err = ParseSlaveIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseSlaveIdError(s.to_owned());
+        let (family, serial) = s.split_once('-').ok_or_else(err)?;
+        if family.len() != 2 || serial.len() != 12 {
+            return Err(err());
+        }
+        let family = u8::from_str_radix(family, 16).map_err(|_| err())?;
+        let serial = u64::from_str_radix(serial, 16).map_err(|_| err())?;
+        Ok(Self::from_parts(family, serial))
+    }
+}
+
+/// Written as the sysfs style id, which leaves out the CRC. A wrong CRC is
+/// appended after a dot, e.g. `28-0000056c3a1f.ab`, so every id survives a
+/// round trip.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SlaveId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.is_valid() {
+            true => serializer.collect_str(self),
+            false => serializer.collect_str(&format_args!("{self}.{:02x}", self.crc())),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SlaveId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = SlaveId;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a slave id like 28-0000056c3a1f")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<SlaveId, E> {
+                let Some((id, crc)) = s.split_once('.') else {
+                    return s.parse().map_err(E::custom);
+                };
+                let mut bytes = id.parse::<SlaveId>().map_err(E::custom)?.bytes();
+                bytes[7] = match crc.len() {
+                    2 => u8::from_str_radix(crc, 16).map_err(|_| E::custom("invalid CRC"))?,
+                    _ => return Err(E::custom("invalid CRC")),
+                };
+                Ok(SlaveId::new(bytes))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum W1NetlinkCommand {
     Write(Vec<u8>),
     Read(Option<Vec<u8>>),
     Search(Option<Vec<SlaveId>>),
     AlarmSearch(Option<Vec<SlaveId>>),
-    Touch,
+    Touch(Vec<u8>),
     Reset,
-    //SlaveAdd(), todo
-    //SlaveRemove(), todo
-    ListSlaves,
+    /// Registers a slave with the master, in master commands only.
+    SlaveAdd(SlaveId),
+    /// Unregisters a slave from the master, in master commands only.
+    SlaveRemove(SlaveId),
+    ListSlaves(Option<Vec<SlaveId>>),
 }
 
 impl W1NetlinkCommand {
-    pub const HEADER_LEN: usize = mem::size_of::<W1NetlinkCmd>();
+    pub const HEADER_LEN: usize = <W1NetlinkCmd as RawHeader>::LEN;
 
-    fn cmd_type(&self) -> W1CommandType {
+    pub fn cmd_type(&self) -> W1CommandType {
         match self {
             W1NetlinkCommand::Write(_) => W1CommandType::Write,
             W1NetlinkCommand::Read(_) => W1CommandType::Read,
             W1NetlinkCommand::Search(_) => W1CommandType::Search,
             W1NetlinkCommand::AlarmSearch(_) => W1CommandType::AlarmSearch,
-            W1NetlinkCommand::Touch => W1CommandType::Touch,
+            W1NetlinkCommand::Touch(_) => W1CommandType::Touch,
             W1NetlinkCommand::Reset => W1CommandType::Reset,
-            W1NetlinkCommand::ListSlaves => W1CommandType::ListSlaves,
+            W1NetlinkCommand::SlaveAdd(_) => W1CommandType::SlaveAdd,
+            W1NetlinkCommand::SlaveRemove(_) => W1CommandType::SlaveRemove,
+            W1NetlinkCommand::ListSlaves(_) => W1CommandType::ListSlaves,
         }
     }
+}
 
-    fn read_slaves(payload: &[u8]) -> Result<Vec<SlaveId>, InvalidLength> {
-        let mut slaves = Vec::new();
-        for id in payload.chunks(8) {
-            if id.len() != 8 {
-                return Err(InvalidLength(id.len()));
+impl W1NetlinkCommand {
+    /// Whether the w1 core replies to the command with data.
+    pub fn expects_reply(&self) -> bool {
+        match self {
+            W1NetlinkCommand::Read(data) => data.as_ref().is_some_and(|data| !data.is_empty()),
+            W1NetlinkCommand::Touch(data) => !data.is_empty(),
+            W1NetlinkCommand::Search(_)
+            | W1NetlinkCommand::AlarmSearch(_)
+            | W1NetlinkCommand::ListSlaves(_) => true,
+            W1NetlinkCommand::Write(_)
+            | W1NetlinkCommand::Reset
+            | W1NetlinkCommand::SlaveAdd(_)
+            | W1NetlinkCommand::SlaveRemove(_) => false,
+        }
+    }
+
+    /// Writes the command as sent, with the data written to the bus or the
+    /// number of bytes to read. A touch that was `sent` shows its data,
+    /// otherwise its length like a read.
+    pub(crate) fn fmt_request(&self, f: &mut fmt::Formatter<'_>, sent: bool) -> fmt::Result {
+        write!(f, "{}", self.cmd_type())?;
+        match self {
+            W1NetlinkCommand::Write(data) if !data.is_empty() => write!(f, " {}", Hex(data)),
+            W1NetlinkCommand::Touch(data) if sent && !data.is_empty() => {
+                write!(f, " {}", Hex(data))
+            }
+            W1NetlinkCommand::Read(Some(data)) | W1NetlinkCommand::Touch(data) => {
+                write!(f, " {}", data.len())
+            }
+            W1NetlinkCommand::SlaveAdd(id) | W1NetlinkCommand::SlaveRemove(id) => {
+                write!(f, " {id}")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the data returned by the command, if any. Reads ending in a
+    /// matching CRC8 are marked `[crc ok]`.
+    pub(crate) fn fmt_reply(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            W1NetlinkCommand::Read(Some(data)) if !data.is_empty() => {
+                write!(f, " -> {}", Hex(data))?;
+                if data.len() > 1 && crc8(data) == 0 {
+                    f.write_str(" [crc ok]")?;
+                }
+                Ok(())
+            }
+            W1NetlinkCommand::Touch(data) if !data.is_empty() => write!(f, " -> {}", Hex(data)),
+            W1NetlinkCommand::Search(Some(ids))
+            | W1NetlinkCommand::AlarmSearch(Some(ids))
+            | W1NetlinkCommand::ListSlaves(Some(ids)) => {
+                f.write_str(" ->")?;
+                if ids.is_empty() {
+                    return f.write_str(" none");
+                }
+                for (i, id) in ids.iter().enumerate() {
+                    let sep = if i > 0 { "," } else { "" };
+                    write!(f, "{sep} {id}")?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Renders the command for logs, e.g. `WRITE cc 44` or
+/// `READ 9 -> 50 05 4b 46 7f ff 0c 10 1c [crc ok]`. The alternate form `{:#}`
+/// shows a request as sent, without the placeholder read buffer.
+impl fmt::Display for W1NetlinkCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.alternate() {
+            true => self.fmt_request(f, true),
+            false => {
+                self.fmt_request(f, false)?;
+                self.fmt_reply(f)
             }
-            slaves.push(SlaveId(id.try_into().unwrap()));
         }
-        Ok(slaves)
     }
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug)]
 pub enum DeserializeError {
-    #[error("Invalid command type")]
-    InvalidType(#[from] InvalidValue),
+    InvalidType(InvalidValue),
+    InvalidLength(InvalidLength),
+    Truncated(Truncated),
+    Unsupported(W1CommandType),
+}
 
-    #[error("Unable to read header: {0}")]
-    InvalidHeader(safe_transmute::Error<'static, u8, W1NetlinkCmd>),
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::InvalidType(_) => write!(f, "Invalid command type"),
+            DeserializeError::InvalidLength(err) => write!(f, "Cannot read slave id: {err}"),
+            DeserializeError::Truncated(err) => err.fmt(f),
+            DeserializeError::Unsupported(cmd) => write!(f, "Unsupported command type {cmd:?}"),
+        }
+    }
+}
 
-    #[error("Cannot read slave id: {0}")]
-    InvalidLength(#[from] InvalidLength),
+impl core::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            DeserializeError::InvalidType(err) => Some(err),
+            DeserializeError::InvalidLength(err) => Some(err),
+            DeserializeError::Truncated(_) | DeserializeError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<InvalidValue> for DeserializeError {
+    fn from(err: InvalidValue) -> Self {
+        DeserializeError::InvalidType(err)
+    }
+}
+
+impl From<InvalidLength> for DeserializeError {
+    fn from(err: InvalidLength) -> Self {
+        DeserializeError::InvalidLength(err)
+    }
+}
+
+impl From<Truncated> for DeserializeError {
+    fn from(err: Truncated) -> Self {
+        DeserializeError::Truncated(err)
+    }
 }
 
 impl Deserializable for W1NetlinkCommand {
     type Error = DeserializeError;
 
     fn deserialize(payload: &[u8]) -> Result<(Self, usize), Self::Error> {
-        let (header, payload) = payload.split_at(mem::size_of::<W1NetlinkCmd>());
-        let W1NetlinkCmd { cmd, len, .. } = safe_transmute::transmute_one_pedantic(header)
-            .map_err(|e| Self::Error::InvalidHeader(e.without_src()))?;
+        W1NetlinkCommandRef::parse(payload).map(|(cmd, len)| (cmd.into_owned(), len))
+    }
+}
+
+/// Slave ids borrowed from a received buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlaveIds<'a>(&'a [u8]);
+
+impl<'a> SlaveIds<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, InvalidLength> {
+        match data.len() % SlaveId::LEN {
+            0 => Ok(Self(data)),
+            _ => Err(InvalidLength(data.len())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len() / SlaveId::LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = SlaveId> + 'a {
+        self.0
+            .chunks_exact(SlaveId::LEN)
+            .map(|id| SlaveId::from(u64::from_ne_bytes(id.try_into().unwrap())))
+    }
+}
+
+/// The single `struct w1_reg_num` of a slave add or remove command.
+fn slave_id(data: &[u8]) -> Result<SlaveId, InvalidLength> {
+    let id = data.try_into().map_err(|_| InvalidLength(data.len()))?;
+    Ok(SlaveId::from(u64::from_ne_bytes(id)))
+}
+
+/// A command borrowing its data from a received buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum W1NetlinkCommandRef<'a> {
+    Write(&'a [u8]),
+    /// Empty for a read without data.
+    Read(&'a [u8]),
+    Search(SlaveIds<'a>),
+    AlarmSearch(SlaveIds<'a>),
+    Touch(&'a [u8]),
+    Reset,
+    SlaveAdd(SlaveId),
+    SlaveRemove(SlaveId),
+    ListSlaves(SlaveIds<'a>),
+}
+
+impl<'a> W1NetlinkCommandRef<'a> {
+    /// Decodes the command at the start of `payload`, returning it along with
+    /// the number of bytes it occupies.
+    pub fn parse(payload: &'a [u8]) -> Result<(Self, usize), DeserializeError> {
+        let W1NetlinkCmd { cmd, len, .. } = read_header(payload)?;
+        let len = len as usize;
+        let data = take(payload, W1NetlinkCommand::HEADER_LEN, len)?;
 
         let cmd = match W1CommandType::try_from(cmd)? {
-            W1CommandType::Read => {
-                let payload = Some(len).filter(|l| *l > 0).map(|_| payload.to_vec());
-                Self::Read(payload)
-            }
-            W1CommandType::Write => {
-                let payload = payload.to_vec();
-                Self::Write(payload)
-            }
-            W1CommandType::Search => Self::Search(Some(Self::read_slaves(payload)?)),
-            W1CommandType::AlarmSearch => Self::AlarmSearch(Some(Self::read_slaves(payload)?)),
-            W1CommandType::Touch => Self::Touch,
+            W1CommandType::Read => Self::Read(data),
+            W1CommandType::Write => Self::Write(data),
+            W1CommandType::Search => Self::Search(SlaveIds::new(data)?),
+            W1CommandType::AlarmSearch => Self::AlarmSearch(SlaveIds::new(data)?),
+            W1CommandType::Touch => Self::Touch(data),
             W1CommandType::Reset => Self::Reset,
-            W1CommandType::SlaveAdd => unimplemented!(),
-            W1CommandType::SlaveRemove => unimplemented!(),
-            W1CommandType::ListSlaves => Self::ListSlaves,
+            W1CommandType::SlaveAdd => Self::SlaveAdd(slave_id(data)?),
+            W1CommandType::SlaveRemove => Self::SlaveRemove(slave_id(data)?),
+            W1CommandType::ListSlaves => Self::ListSlaves(SlaveIds::new(data)?),
         };
-        Ok((cmd, len as usize))
+        Ok((cmd, W1NetlinkCommand::HEADER_LEN + len))
+    }
+
+    pub fn cmd_type(&self) -> W1CommandType {
+        match self {
+            Self::Write(_) => W1CommandType::Write,
+            Self::Read(_) => W1CommandType::Read,
+            Self::Search(_) => W1CommandType::Search,
+            Self::AlarmSearch(_) => W1CommandType::AlarmSearch,
+            Self::Touch(_) => W1CommandType::Touch,
+            Self::Reset => W1CommandType::Reset,
+            Self::SlaveAdd(_) => W1CommandType::SlaveAdd,
+            Self::SlaveRemove(_) => W1CommandType::SlaveRemove,
+            Self::ListSlaves(_) => W1CommandType::ListSlaves,
+        }
+    }
+
+    pub fn into_owned(self) -> W1NetlinkCommand {
+        match self {
+            Self::Write(data) => W1NetlinkCommand::Write(data.to_vec()),
+            Self::Read(data) => {
+                W1NetlinkCommand::Read(Some(data).filter(|d| !d.is_empty()).map(<[u8]>::to_vec))
+            }
+            Self::Search(ids) => W1NetlinkCommand::Search(Some(ids.iter().collect())),
+            Self::AlarmSearch(ids) => W1NetlinkCommand::AlarmSearch(Some(ids.iter().collect())),
+            Self::Touch(data) => W1NetlinkCommand::Touch(data.to_vec()),
+            Self::Reset => W1NetlinkCommand::Reset,
+            Self::SlaveAdd(id) => W1NetlinkCommand::SlaveAdd(id),
+            Self::SlaveRemove(id) => W1NetlinkCommand::SlaveRemove(id),
+            Self::ListSlaves(ids) => W1NetlinkCommand::ListSlaves(Some(ids.iter().collect())),
+        }
+    }
+}
+
+impl From<W1NetlinkCommandRef<'_>> for W1NetlinkCommand {
+    fn from(cmd: W1NetlinkCommandRef<'_>) -> Self {
+        cmd.into_owned()
     }
 }
 
 impl Serializable for W1NetlinkCommand {
     fn buffer_len(&self) -> usize {
         let inner = match self {
-            W1NetlinkCommand::Write(pl) => pl.len(),
+            W1NetlinkCommand::Write(pl) | W1NetlinkCommand::Touch(pl) => pl.len(),
             W1NetlinkCommand::Read(pl) => pl.as_ref().map(Vec::len).unwrap_or_default(),
-            W1NetlinkCommand::Search(_) => 0,
-            W1NetlinkCommand::AlarmSearch(_) => 0,
-            W1NetlinkCommand::Touch => 0,
+            W1NetlinkCommand::Search(ids)
+            | W1NetlinkCommand::AlarmSearch(ids)
+            | W1NetlinkCommand::ListSlaves(ids) => ids
+                .as_ref()
+                .map(|ids| ids.len() * SlaveId::LEN)
+                .unwrap_or_default(),
             W1NetlinkCommand::Reset => 0,
-            W1NetlinkCommand::ListSlaves => todo!(),
+            W1NetlinkCommand::SlaveAdd(_) | W1NetlinkCommand::SlaveRemove(_) => SlaveId::LEN,
         };
         inner + Self::HEADER_LEN
     }
 
+    fn validate(&self) -> Result<(), SerializeError> {
+        let len = self.buffer_len() - Self::HEADER_LEN;
+        check_len("Command data", len, u16::MAX.into())
+    }
+
     fn serialize(&self, buffer: &mut [u8]) {
         let cmd_type = self.cmd_type();
-        let len = (self.buffer_len() - Self::HEADER_LEN) as u16;
+        let len = len_field(self.buffer_len() - Self::HEADER_LEN);
         let raw = W1NetlinkCmd {
             cmd: cmd_type.into(),
             _res: Default::default(),
             len,
         };
 
-        let msg = safe_transmute::transmute_one_to_bytes(&raw);
-        debug_assert_eq!(mem::size_of::<W1NetlinkCmd>(), Self::HEADER_LEN);
-        buffer[0..Self::HEADER_LEN].copy_from_slice(msg);
+        raw.encode(&mut buffer[..Self::HEADER_LEN]);
 
         match self {
-            W1NetlinkCommand::Write(pl) => buffer[Self::HEADER_LEN..].copy_from_slice(pl),
+            W1NetlinkCommand::Write(pl) | W1NetlinkCommand::Touch(pl) => {
+                buffer[Self::HEADER_LEN..].copy_from_slice(pl)
+            }
             W1NetlinkCommand::Read(pl) => {
                 if let Some(pl) = pl {
                     buffer[Self::HEADER_LEN..].copy_from_slice(pl);
                 }
             }
-            W1NetlinkCommand::Search(_) => {}
-            W1NetlinkCommand::AlarmSearch(_) => {}
-            W1NetlinkCommand::Touch => {}
+            W1NetlinkCommand::Search(ids)
+            | W1NetlinkCommand::AlarmSearch(ids)
+            | W1NetlinkCommand::ListSlaves(ids) => {
+                for (id, chunk) in ids
+                    .iter()
+                    .flatten()
+                    .zip(buffer[Self::HEADER_LEN..].chunks_mut(SlaveId::LEN))
+                {
+                    chunk.copy_from_slice(&u64::from(*id).to_ne_bytes());
+                }
+            }
             W1NetlinkCommand::Reset => {}
-            W1NetlinkCommand::ListSlaves => todo!(),
+            W1NetlinkCommand::SlaveAdd(id) | W1NetlinkCommand::SlaveRemove(id) => {
+                buffer[Self::HEADER_LEN..].copy_from_slice(&u64::from(*id).to_ne_bytes())
+            }
         }
     }
 }
@@ -1,14 +1,20 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "std")]
 use netlink_packet_core::{NetlinkDeserializable, NetlinkPayload, NetlinkSerializable, NLMSG_DONE};
-use std::mem;
 
 use self::raw::CnMsg;
-use super::{Deserializable, Serializable};
+use super::{
+    check_len, len_field, read_header, take, Deserializable, RawHeader, Serializable,
+    SerializeError, Truncated,
+};
 
 mod raw {
-    use safe_transmute::TriviallyTransmutable;
+    use crate::proto::{ne_u16, ne_u32, RawHeader};
 
-    #[repr(C)]
-    #[derive(Clone, Copy)]
+    /// `struct cn_msg` from `include/uapi/linux/connector.h`.
+    #[derive(Clone, Copy, Default)]
     pub struct CnMsg {
         pub idx: u32,
         pub val: u32,
@@ -20,30 +26,75 @@ mod raw {
         pub flags: u16,
     }
 
-    /// Safety: Struct uses repr(C) and should be well aligned for byte slices
-    unsafe impl TriviallyTransmutable for CnMsg {}
+    impl RawHeader for CnMsg {
+        const LEN: usize = 20;
+
+        fn decode(bytes: &[u8]) -> Self {
+            Self {
+                idx: ne_u32(bytes, 0),
+                val: ne_u32(bytes, 4),
+                seq: ne_u32(bytes, 8),
+                ack: ne_u32(bytes, 12),
+                len: ne_u16(bytes, 16),
+                flags: ne_u16(bytes, 18),
+            }
+        }
+
+        fn encode(&self, buffer: &mut [u8]) {
+            buffer[0..4].copy_from_slice(&self.idx.to_ne_bytes());
+            buffer[4..8].copy_from_slice(&self.val.to_ne_bytes());
+            buffer[8..12].copy_from_slice(&self.seq.to_ne_bytes());
+            buffer[12..16].copy_from_slice(&self.ack.to_ne_bytes());
+            buffer[16..18].copy_from_slice(&self.len.to_ne_bytes());
+            buffer[18..20].copy_from_slice(&self.flags.to_ne_bytes());
+        }
+    }
 }
 
+/// Kernel limit for a connector message including its `cn_msg` header but not
+/// the netlink header, see `CONNECTOR_MAX_MSG_SIZE` in
+/// `include/linux/connector.h`.
+pub const CONNECTOR_MAX_MSG_SIZE: usize = 16384;
+
 pub trait NlConnectorType {
     fn idx() -> u32;
     fn val() -> u32;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NlConnectorHeader {
-    seq: u32,
-    ack: u32,
-    flags: u16,
+    pub seq: u32,
+    /// Kernel docs:
+    /// > acknowledge number is set to seq+1
+    ///
+    /// A non-zero value in a request asks the w1 core for status replies.
+    pub ack: u32,
+    pub flags: u16,
+}
+
+/// Renders `seq=3`, followed by the acknowledge number if it is set.
+impl fmt::Display for NlConnectorHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "seq={}", self.seq)?;
+        if self.ack != 0 {
+            write!(f, " ack={}", self.ack)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NlConnectorMessage<T> {
-    header: NlConnectorHeader,
-    payload: Vec<T>,
+    pub header: NlConnectorHeader,
+    pub payload: Vec<T>,
 }
 
+const CONNECTOR_HEADER_LEN: usize = <CnMsg as RawHeader>::LEN;
+
 impl<T> NlConnectorMessage<T> {
-    pub const HEADER_LEN: usize = mem::size_of::<CnMsg>();
+    pub const HEADER_LEN: usize = CONNECTOR_HEADER_LEN;
 
     pub fn new(seq: u32, payload: impl IntoIterator<Item = T>) -> Self {
         let payload = payload.into_iter().collect();
@@ -58,43 +109,82 @@ impl<T> NlConnectorMessage<T> {
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum DeserializeError<E: std::error::Error> {
-    #[error("Invalid Netlink message type")]
-    InvalidMessageType,
-
-    #[error("Invalid payload: {0}")]
-    InvalidHeader(safe_transmute::Error<'static, u8, CnMsg>),
+/// Renders the header followed by the payload, messages separated by ` | `.
+impl<T: fmt::Display> fmt::Display for NlConnectorMessage<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.header.fmt(f)?;
+        for (i, item) in self.payload.iter().enumerate() {
+            f.write_str(if i > 0 { " | " } else { " " })?;
+            item.fmt(f)?;
+        }
+        Ok(())
+    }
+}
 
-    #[error("Payload length does not match header field")]
+#[derive(Debug)]
+pub enum DeserializeError<E: core::error::Error> {
+    InvalidMessageType,
     InvalidPayloadLength,
-
-    #[error("Invalid connector index, expected {0}, got {1}")]
     UnexpectedIdx(u32, u32),
-
-    #[error("Invalid connector value, expected {0}, got {1}")]
     UnexpectedVal(u32, u32),
-
-    #[error(transparent)]
-    Inner(#[from] E),
+    Truncated(Truncated),
+    Inner { offset: usize, source: E },
 }
 
-impl<T> NetlinkDeserializable for NlConnectorMessage<T>
-where
-    T: Deserializable + NlConnectorType,
-    T::Error: std::error::Error,
-{
-    type Error = DeserializeError<T::Error>;
+impl<E: core::error::Error> fmt::Display for DeserializeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::InvalidMessageType => write!(f, "Invalid Netlink message type"),
+            DeserializeError::InvalidPayloadLength => {
+                write!(f, "Data after the connector message")
+            }
+            DeserializeError::UnexpectedIdx(expected, actual) => write!(
+                f,
+                "Invalid connector index, expected {expected}, got {actual}"
+            ),
+            DeserializeError::UnexpectedVal(expected, actual) => write!(
+                f,
+                "Invalid connector value, expected {expected}, got {actual}"
+            ),
+            DeserializeError::Truncated(err) => err.fmt(f),
+            DeserializeError::Inner { offset, source } => {
+                write!(f, "Invalid message at offset {offset}: {source}")
+            }
+        }
+    }
+}
 
-    fn deserialize(
-        header: &netlink_packet_core::NetlinkHeader,
-        payload: &[u8],
-    ) -> Result<Self, Self::Error> {
-        if header.message_type as isize != netlink_sys::constants::NETLINK_CONNECTOR {
-            return Err(Self::Error::InvalidMessageType);
+impl<E: core::error::Error + 'static> core::error::Error for DeserializeError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            DeserializeError::Inner { source, .. } => Some(source),
+            _ => None,
         }
+    }
+}
+
+impl<E: core::error::Error> From<Truncated> for DeserializeError<E> {
+    fn from(err: Truncated) -> Self {
+        DeserializeError::Truncated(err)
+    }
+}
 
-        let (header, payload_bytes) = payload.split_at(mem::size_of::<CnMsg>());
+/// A connector message borrowing its payload from a received buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NlConnectorMessageRef<'a> {
+    pub header: NlConnectorHeader,
+    pub payload: &'a [u8],
+}
+
+impl<'a> NlConnectorMessageRef<'a> {
+    /// Decodes a single `cn_msg` header for a payload of type `T`. The kernel
+    /// may bundle several of them into one netlink message, the returned
+    /// length tells where the next one starts.
+    pub fn parse<T, E>(payload: &'a [u8]) -> Result<(Self, usize), DeserializeError<E>>
+    where
+        T: NlConnectorType,
+        E: core::error::Error,
+    {
         let CnMsg {
             idx,
             val,
@@ -102,47 +192,102 @@ where
             ack,
             len,
             flags,
-        } = safe_transmute::transmute_one_pedantic(header)
-            .map_err(|e| Self::Error::InvalidHeader(e.without_src()))?;
+        } = read_header(payload)?;
 
-        if len as usize != payload.len() {
-            return Err(Self::Error::InvalidPayloadLength);
-        }
         if idx != T::idx() {
-            return Err(Self::Error::UnexpectedIdx(T::idx(), idx));
+            return Err(DeserializeError::UnexpectedIdx(T::idx(), idx));
         }
         if val != T::val() {
-            return Err(Self::Error::UnexpectedVal(T::val(), val));
+            return Err(DeserializeError::UnexpectedVal(T::val(), val));
         }
+        let data = take(payload, CONNECTOR_HEADER_LEN, len as usize)?;
 
         let header = NlConnectorHeader { seq, ack, flags };
+        let msg = Self {
+            header,
+            payload: data,
+        };
+        Ok((msg, CONNECTOR_HEADER_LEN + data.len()))
+    }
+}
+
+impl<T> Deserializable for NlConnectorMessage<T>
+where
+    T: Deserializable + NlConnectorType,
+{
+    type Error = DeserializeError<T::Error>;
+
+    /// Decodes a single `cn_msg` with all messages attached to it.
+    fn deserialize(payload: &[u8]) -> Result<(Self, usize), Self::Error> {
+        let (msg, len) = NlConnectorMessageRef::parse::<T, _>(payload)?;
+        let data = msg.payload;
         let mut payload = Vec::new();
         let mut cursor = 0;
-        while cursor < payload.len() {
-            let (item, n) = T::deserialize(&payload_bytes[cursor..])?;
+        while cursor < data.len() {
+            let (item, n) =
+                T::deserialize(&data[cursor..]).map_err(|source| Self::Error::Inner {
+                    offset: Self::HEADER_LEN + cursor,
+                    source,
+                })?;
             payload.push(item);
+            if n == 0 {
+                break;
+            }
             cursor += n;
         }
 
-        Ok(Self { header, payload })
+        let header = msg.header;
+        Ok((Self { header, payload }, len))
     }
 }
 
-impl<T> NetlinkSerializable for NlConnectorMessage<T>
+/// Decodes netlink messages holding exactly one `cn_msg`. Bundled replies
+/// are rejected, decode them with [`Vec`]'s [`Deserializable`] instead.
+#[cfg(feature = "std")]
+impl<T> NetlinkDeserializable for NlConnectorMessage<T>
 where
-    T: Serializable + NlConnectorType,
+    T: Deserializable + NlConnectorType,
 {
-    fn message_type(&self) -> u16 {
-        NLMSG_DONE
+    type Error = DeserializeError<T::Error>;
+
+    fn deserialize(
+        header: &netlink_packet_core::NetlinkHeader,
+        payload: &[u8],
+    ) -> Result<Self, Self::Error> {
+        // the connector sends everything as NLMSG_DONE
+        if header.message_type != NLMSG_DONE {
+            return Err(Self::Error::InvalidMessageType);
+        }
+        let (msg, len) = <Self as Deserializable>::deserialize(payload)?;
+        if len != payload.len() {
+            return Err(Self::Error::InvalidPayloadLength);
+        }
+        Ok(msg)
     }
+}
 
+impl<T> Serializable for NlConnectorMessage<T>
+where
+    T: Serializable + NlConnectorType,
+{
     fn buffer_len(&self) -> usize {
         let inner_len: usize = self.payload.iter().map(Serializable::buffer_len).sum();
         inner_len + Self::HEADER_LEN
     }
 
+    /// Checks all messages and the total size against the limit of the
+    /// kernel connector.
+    fn validate(&self) -> Result<(), SerializeError> {
+        self.payload.validate()?;
+        check_len(
+            "Connector message",
+            Serializable::buffer_len(self),
+            CONNECTOR_MAX_MSG_SIZE,
+        )
+    }
+
     fn serialize(&self, buffer: &mut [u8]) {
-        let len = (buffer.len() - Self::HEADER_LEN) as u16;
+        let len = len_field(buffer.len() - Self::HEADER_LEN);
         let NlConnectorHeader { seq, ack, flags } = self.header;
         let raw = CnMsg {
             idx: T::idx(),
@@ -152,20 +297,30 @@ where
             len,
             flags,
         };
-        let msg = safe_transmute::transmute_one_to_bytes(&raw);
+        raw.encode(&mut buffer[..Self::HEADER_LEN]);
+        self.payload.serialize(&mut buffer[Self::HEADER_LEN..]);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> NetlinkSerializable for NlConnectorMessage<T>
+where
+    T: Serializable + NlConnectorType,
+{
+    fn message_type(&self) -> u16 {
+        NLMSG_DONE
+    }
 
-        debug_assert_eq!(Self::HEADER_LEN, mem::size_of::<CnMsg>());
-        buffer[0..Self::HEADER_LEN].copy_from_slice(msg);
+    fn buffer_len(&self) -> usize {
+        Serializable::buffer_len(self)
+    }
 
-        let mut cursor = Self::HEADER_LEN;
-        for item in &self.payload {
-            let len = item.buffer_len();
-            item.serialize(&mut buffer[cursor..cursor + len]);
-            cursor += len;
-        }
+    fn serialize(&self, buffer: &mut [u8]) {
+        Serializable::serialize(self, buffer)
     }
 }
 
+#[cfg(feature = "std")]
 impl<T> From<NlConnectorMessage<T>> for NetlinkPayload<NlConnectorMessage<T>> {
     fn from(msg: NlConnectorMessage<T>) -> Self {
         Self::InnerMessage(msg)
@@ -1,15 +1,19 @@
-use std::mem;
+use alloc::vec::Vec;
+use core::fmt;
 
 use self::raw::W1NetlinkMsg;
 use super::{
-    command::W1NetlinkCommand, connector::NlConnectorType, Deserializable, InvalidValue,
-    Serializable,
+    check_len,
+    command::{self, SlaveId, W1NetlinkCommand, W1NetlinkCommandRef},
+    connector::{self, NlConnectorMessage, NlConnectorMessageRef, NlConnectorType},
+    len_field, ne_u32, read_header, take, Deserializable, InvalidValue, RawHeader, Serializable,
+    SerializeError, Truncated,
 };
 
 mod raw {
     //! Taken from https://www.kernel.org/doc/Documentation/w1/w1.netlink
 
-    use safe_transmute::TriviallyTransmutable;
+    use crate::proto::{ne_u16, RawHeader};
 
     pub mod constants {
         pub const CONNECTOR_W1_IDX: u32 = 0x3;
@@ -24,8 +28,7 @@ mod raw {
         pub const W1_LIST_MASTERS: u8 = 6;
     }
 
-    #[repr(C)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Default)]
     pub struct W1NetlinkMsg {
         /// Message type. See also [constants].
         pub r#type: u8,
@@ -33,13 +36,37 @@ mod raw {
         pub status: u8,
         /// Size of data attached to this header data
         pub len: u16,
-        /// Master or slave ID
+        /// Master or slave ID, a union of the slave's `struct w1_reg_num` and
+        /// the master's id followed by a reserved word. Both are in host byte
+        /// order.
         pub id: [u8; 8],
     }
 
-    unsafe impl TriviallyTransmutable for W1NetlinkMsg {}
+    impl RawHeader for W1NetlinkMsg {
+        const LEN: usize = 12;
+
+        fn decode(bytes: &[u8]) -> Self {
+            Self {
+                r#type: bytes[0],
+                status: bytes[1],
+                len: ne_u16(bytes, 2),
+                id: bytes[4..12].try_into().unwrap(),
+            }
+        }
+
+        fn encode(&self, buffer: &mut [u8]) {
+            buffer[0] = self.r#type;
+            buffer[1] = self.status;
+            buffer[2..4].copy_from_slice(&self.len.to_ne_bytes());
+            buffer[4..12].copy_from_slice(&self.id);
+        }
+    }
 }
 
+/// Connector flag asking the w1 core to bundle all replies to a request into a
+/// single netlink message.
+pub const W1_CN_BUNDLE: u16 = 1;
+
 /// See also [raw::constants].
 #[derive(Debug, Clone, Copy)]
 enum W1MessageType {
@@ -86,13 +113,15 @@ impl From<W1MessageType> for u8 {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventKind {
     Add,
     Remove,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum W1NetlinkMessage {
     ListMasters(Option<Vec<u32>>),
     MasterCommand {
@@ -100,6 +129,7 @@ pub enum W1NetlinkMessage {
         cmds: Vec<W1NetlinkCommand>,
     },
     SlaveCommand {
+        #[cfg_attr(feature = "serde", serde(with = "slave_target"))]
         target: u64,
         cmds: Vec<W1NetlinkCommand>,
     },
@@ -109,12 +139,13 @@ pub enum W1NetlinkMessage {
     },
     SlaveEvent {
         kind: EventKind,
+        #[cfg_attr(feature = "serde", serde(with = "slave_target"))]
         target: u64,
     },
 }
 
 impl W1NetlinkMessage {
-    pub const HEADER_LEN: usize = mem::size_of::<W1NetlinkMsg>();
+    pub const HEADER_LEN: usize = <W1NetlinkMsg as RawHeader>::LEN;
 }
 
 impl NlConnectorType for W1NetlinkMessage {
@@ -127,81 +158,365 @@ impl NlConnectorType for W1NetlinkMessage {
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum DeserializeError {
-    #[error("Invalid header payload: {0}")]
-    InvalidHeader(safe_transmute::Error<'static, u8, W1NetlinkMsg>),
+impl W1NetlinkMessage {
+    /// Writes message type and target, e.g. `SLAVE_CMD 28-0000056c3a1f`.
+    fn fmt_head(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = |kind: &EventKind| match kind {
+            EventKind::Add => "ADD",
+            EventKind::Remove => "REMOVE",
+        };
+        match self {
+            W1NetlinkMessage::ListMasters(_) => f.write_str("LIST_MASTERS"),
+            W1NetlinkMessage::MasterCommand { target, .. } => write!(f, "MASTER_CMD {target}"),
+            W1NetlinkMessage::SlaveCommand { target, .. } => {
+                write!(f, "SLAVE_CMD {}", SlaveId::from(*target))
+            }
+            W1NetlinkMessage::MasterEvent { kind, target } => {
+                write!(f, "MASTER_{} {target}", event(kind))
+            }
+            W1NetlinkMessage::SlaveEvent { kind, target } => {
+                write!(f, "SLAVE_{} {}", event(kind), SlaveId::from(*target))
+            }
+        }
+    }
 
-    #[error("Invalid message type: {0}")]
-    InvalidMessageType(InvalidValue),
+    fn commands(&self) -> &[W1NetlinkCommand] {
+        match self {
+            W1NetlinkMessage::MasterCommand { cmds, .. }
+            | W1NetlinkMessage::SlaveCommand { cmds, .. } => cmds,
+            _ => &[],
+        }
+    }
+}
+
+fn fmt_masters(f: &mut fmt::Formatter<'_>, ids: &[u32]) -> fmt::Result {
+    f.write_str(" ->")?;
+    if ids.is_empty() {
+        return f.write_str(" none");
+    }
+    for (i, id) in ids.iter().enumerate() {
+        let sep = if i > 0 { "," } else { "" };
+        write!(f, "{sep} {id}")?;
+    }
+    Ok(())
+}
+
+/// Renders the message for logs, e.g.
+/// `SLAVE_CMD 28-0000056c3a1f: RESET; WRITE cc 44; READ 9`. The alternate form
+/// `{:#}` is passed on to the commands.
+impl fmt::Display for W1NetlinkMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_head(f)?;
+        if let W1NetlinkMessage::ListMasters(Some(ids)) = self {
+            if !f.alternate() {
+                fmt_masters(f, ids)?;
+            }
+        }
+        for (i, cmd) in self.commands().iter().enumerate() {
+            f.write_str(if i > 0 { "; " } else { ": " })?;
+            fmt::Display::fmt(cmd, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A request rendered along with the data replied to it, e.g.
+/// `seq=3 SLAVE_CMD 28-0000056c3a1f: RESET; WRITE cc 44; READ 9 -> 50 05 4b 46 7f ff 0c 10 1c [crc ok]`.
+///
+/// Replies are assigned in order to the commands expecting data.
+#[derive(Debug, Clone, Copy)]
+pub struct Exchange<'a> {
+    pub request: &'a NlConnectorMessage<W1NetlinkMessage>,
+    pub replies: &'a [W1NetlinkMessage],
+}
 
-    #[error("Payload length does not match header")]
+impl<'a> Exchange<'a> {
+    pub fn new(
+        request: &'a NlConnectorMessage<W1NetlinkMessage>,
+        replies: &'a [W1NetlinkMessage],
+    ) -> Self {
+        Self { request, replies }
+    }
+}
+
+impl fmt::Display for Exchange<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut replies = self.replies.iter().flat_map(W1NetlinkMessage::commands);
+        let mut masters = self.replies.iter().filter_map(|reply| match reply {
+            W1NetlinkMessage::ListMasters(Some(ids)) => Some(ids),
+            _ => None,
+        });
+        self.request.header.fmt(f)?;
+        for (i, msg) in self.request.payload.iter().enumerate() {
+            f.write_str(if i > 0 { " | " } else { " " })?;
+            msg.fmt_head(f)?;
+            if let W1NetlinkMessage::ListMasters(_) = msg {
+                if let Some(ids) = masters.next() {
+                    fmt_masters(f, ids)?;
+                }
+            }
+            for (i, cmd) in msg.commands().iter().enumerate() {
+                f.write_str(if i > 0 { "; " } else { ": " })?;
+                cmd.fmt_request(f, true)?;
+                if !cmd.expects_reply() {
+                    continue;
+                }
+                if let Some(reply) = replies
+                    .by_ref()
+                    .find(|reply| reply.cmd_type() == cmd.cmd_type())
+                {
+                    reply.fmt_reply(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum DeserializeError {
+    InvalidMessageType(InvalidValue),
     InvalidPayloadLength,
+    Status(u8),
+    Truncated(Truncated),
+    Command {
+        offset: usize,
+        source: command::DeserializeError,
+    },
+}
 
-    #[error(transparent)]
-    Command(#[from] super::command::DeserializeError),
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::InvalidMessageType(err) => write!(f, "Invalid message type: {err}"),
+            DeserializeError::InvalidPayloadLength => {
+                write!(f, "Payload length does not match header")
+            }
+            DeserializeError::Status(status) => write!(f, "Kernel reported error status {status}"),
+            DeserializeError::Truncated(err) => err.fmt(f),
+            DeserializeError::Command { offset, source } => {
+                write!(f, "Invalid command at offset {offset}: {source}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            DeserializeError::Command { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<Truncated> for DeserializeError {
+    fn from(err: Truncated) -> Self {
+        DeserializeError::Truncated(err)
+    }
 }
 
 impl Deserializable for W1NetlinkMessage {
     type Error = DeserializeError;
 
     fn deserialize(payload: &[u8]) -> Result<(Self, usize), Self::Error> {
-        let (header, payload) = payload.split_at(Self::HEADER_LEN);
+        let (msg, len) = W1NetlinkMessageRef::parse(payload)?;
+        Ok((msg.try_into_owned()?, len))
+    }
+}
+
+/// Master ids borrowed from a received buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasterIds<'a>(&'a [u8]);
+
+impl<'a> MasterIds<'a> {
+    pub fn len(&self) -> usize {
+        self.0.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = u32> + 'a {
+        self.0.chunks_exact(4).map(|id| ne_u32(id, 0))
+    }
+}
+
+/// Commands attached to a message, decoded while iterating. Iteration ends
+/// after the first error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Commands<'a> {
+    data: &'a [u8],
+    cursor: usize,
+}
+
+impl Commands<'_> {
+    /// Decodes the remaining commands.
+    pub fn into_owned(self) -> Result<Vec<W1NetlinkCommand>, DeserializeError> {
+        self.map(|cmd| cmd.map(W1NetlinkCommandRef::into_owned))
+            .collect()
+    }
+}
+
+impl<'a> Iterator for Commands<'a> {
+    type Item = Result<W1NetlinkCommandRef<'a>, DeserializeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .data
+            .get(self.cursor..)
+            .filter(|rest| !rest.is_empty())?;
+        match W1NetlinkCommandRef::parse(rest) {
+            Ok((cmd, len)) => {
+                self.cursor += len;
+                Some(Ok(cmd))
+            }
+            Err(source) => {
+                let offset = W1NetlinkMessage::HEADER_LEN + self.cursor;
+                self.cursor = self.data.len();
+                Some(Err(DeserializeError::Command { offset, source }))
+            }
+        }
+    }
+}
+
+/// A message borrowing its payload from a received buffer. Commands are only
+/// decoded when iterated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum W1NetlinkMessageRef<'a> {
+    ListMasters(MasterIds<'a>),
+    MasterCommand { target: u32, cmds: Commands<'a> },
+    SlaveCommand { target: u64, cmds: Commands<'a> },
+    MasterEvent { kind: EventKind, target: u32 },
+    SlaveEvent { kind: EventKind, target: u64 },
+}
+
+impl<'a> W1NetlinkMessageRef<'a> {
+    /// Decodes the header of the message at the start of `payload`, returning
+    /// the message along with the number of bytes it occupies.
+    pub fn parse(payload: &'a [u8]) -> Result<(Self, usize), DeserializeError> {
         let W1NetlinkMsg {
             r#type,
             status,
             len,
             id,
-        } = safe_transmute::transmute_one_pedantic(header)
-            .map_err(|e| Self::Error::InvalidHeader(e.without_src()))?;
+        } = read_header(payload)?;
 
         if status > 0 {
-            todo!(); // error handling
+            return Err(DeserializeError::Status(status));
         }
 
         let len = len as usize;
-        let msg_type = r#type.try_into().map_err(Self::Error::InvalidMessageType)?;
-        let ret = match msg_type {
+        let data = take(payload, W1NetlinkMessage::HEADER_LEN, len)?;
+        let master = ne_u32(&id, 0);
+        let slave = u64::from_ne_bytes(id);
+        let cmds = Commands { data, cursor: 0 };
+        let msg_type = r#type
+            .try_into()
+            .map_err(DeserializeError::InvalidMessageType)?;
+        let msg = match msg_type {
             W1MessageType::SlaveAdd => Self::SlaveEvent {
                 kind: EventKind::Add,
-                target: u64::from_le_bytes(id),
+                target: slave,
             },
             W1MessageType::SlaveRemove => Self::SlaveEvent {
                 kind: EventKind::Remove,
-                target: u64::from_le_bytes(id),
+                target: slave,
             },
             W1MessageType::MasterAdd => Self::MasterEvent {
                 kind: EventKind::Add,
-                target: u32::from_le_bytes(id[..4].try_into().unwrap()),
+                target: master,
             },
             W1MessageType::MasterRemove => Self::MasterEvent {
                 kind: EventKind::Remove,
-                target: u32::from_le_bytes(id[..4].try_into().unwrap()),
+                target: master,
+            },
+            W1MessageType::MasterCmd => Self::MasterCommand {
+                target: master,
+                cmds,
             },
-            W1MessageType::MasterCmd => {
-                let target = u32::from_le_bytes(id[..4].try_into().unwrap());
-                let (cmds, _) = Deserializable::deserialize(payload)?;
-                Self::MasterCommand { target, cmds }
+            W1MessageType::SlaveCmd => Self::SlaveCommand {
+                target: slave,
+                cmds,
+            },
+            W1MessageType::ListMasters if !len.is_multiple_of(4) => {
+                return Err(DeserializeError::InvalidPayloadLength)
             }
-            W1MessageType::SlaveCmd => {
-                let target = u64::from_le_bytes(id);
-                let (cmds, _) = Deserializable::deserialize(payload)?;
-                Self::SlaveCommand { target, cmds }
+            W1MessageType::ListMasters => Self::ListMasters(MasterIds(data)),
+        };
+        Ok((msg, W1NetlinkMessage::HEADER_LEN + len))
+    }
+
+    /// Iterates over the messages in the payload of a connector message.
+    pub fn iter(payload: &'a [u8]) -> Messages<'a> {
+        Messages {
+            data: payload,
+            cursor: 0,
+        }
+    }
+
+    /// Decodes all commands into an owned message.
+    pub fn try_into_owned(self) -> Result<W1NetlinkMessage, DeserializeError> {
+        let msg = match self {
+            Self::ListMasters(ids) => W1NetlinkMessage::ListMasters(Some(ids.iter().collect())),
+            Self::MasterCommand { target, cmds } => W1NetlinkMessage::MasterCommand {
+                target,
+                cmds: cmds.into_owned()?,
+            },
+            Self::SlaveCommand { target, cmds } => W1NetlinkMessage::SlaveCommand {
+                target,
+                cmds: cmds.into_owned()?,
+            },
+            Self::MasterEvent { kind, target } => W1NetlinkMessage::MasterEvent { kind, target },
+            Self::SlaveEvent { kind, target } => W1NetlinkMessage::SlaveEvent { kind, target },
+        };
+        Ok(msg)
+    }
+}
+
+/// Messages in the payload of a connector message, decoded while iterating.
+/// Errors carry their offset from the start of the connector message and end
+/// the iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Messages<'a> {
+    data: &'a [u8],
+    cursor: usize,
+}
+
+impl Messages<'_> {
+    /// Offset of the next message from the start of the connector message.
+    pub fn offset(&self) -> usize {
+        NlConnectorMessage::<W1NetlinkMessage>::HEADER_LEN + self.cursor
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<W1NetlinkMessageRef<'a>, connector::DeserializeError<DeserializeError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .data
+            .get(self.cursor..)
+            .filter(|rest| !rest.is_empty())?;
+        match W1NetlinkMessageRef::parse(rest) {
+            Ok((msg, len)) => {
+                self.cursor += len;
+                Some(Ok(msg))
             }
-            W1MessageType::ListMasters => {
-                // read from payload
-                let mut bus_ids = Vec::new();
-                for chunk in payload.chunks(4) {
-                    if chunk.len() < 4 {
-                        return Err(DeserializeError::InvalidPayloadLength);
-                    }
-                    let id = u32::from_le_bytes(chunk.try_into().unwrap());
-                    bus_ids.push(id);
-                }
-                Self::ListMasters(Some(bus_ids))
+            Err(source) => {
+                let offset = self.offset();
+                self.cursor = self.data.len();
+                Some(Err(connector::DeserializeError::Inner { offset, source }))
             }
-        };
-        Ok((ret, len + Self::HEADER_LEN))
+        }
+    }
+}
+
+impl<'a> NlConnectorMessageRef<'a> {
+    /// Iterates over the w1 messages attached to this connector message.
+    pub fn messages(&self) -> Messages<'a> {
+        W1NetlinkMessageRef::iter(self.payload)
     }
 }
 
@@ -218,41 +533,83 @@ impl Serializable for W1NetlinkMessage {
         inner + Self::HEADER_LEN
     }
 
+    fn validate(&self) -> Result<(), SerializeError> {
+        if let W1NetlinkMessage::MasterCommand { cmds, .. }
+        | W1NetlinkMessage::SlaveCommand { cmds, .. } = self
+        {
+            cmds.validate()?;
+        }
+        let len = self.buffer_len() - Self::HEADER_LEN;
+        check_len("Message payload", len, u16::MAX.into())
+    }
+
     fn serialize(&self, buffer: &mut [u8]) {
-        let len = (self.buffer_len() - Self::HEADER_LEN) as u16;
+        let len = len_field(self.buffer_len() - Self::HEADER_LEN);
 
         use W1NetlinkMessage::*;
-        let (msg_type, id, payload) = match self {
-            ListMasters(ids) => {
-                let pl: Vec<u8> = ids
-                    .as_ref()
-                    .map(|ids| {
-                        ids.iter()
-                            .cloned()
-                            .map(u32::to_le_bytes)
-                            .map(IntoIterator::into_iter)
-                            .flatten()
-                            .collect()
-                    })
-                    .unwrap_or_default();
-                (W1MessageType::ListMasters, 0u64, pl)
+        let (msg_type, id) = match self {
+            ListMasters(_) => (W1MessageType::ListMasters, [0; 8]),
+            MasterCommand { target, .. } => (W1MessageType::MasterCmd, master_id(*target)),
+            SlaveCommand { target, .. } => (W1MessageType::SlaveCmd, target.to_ne_bytes()),
+            MasterEvent { kind, target } => {
+                let msg_type = match kind {
+                    EventKind::Add => W1MessageType::MasterAdd,
+                    EventKind::Remove => W1MessageType::MasterRemove,
+                };
+                (msg_type, master_id(*target))
+            }
+            SlaveEvent { kind, target } => {
+                let msg_type = match kind {
+                    EventKind::Add => W1MessageType::SlaveAdd,
+                    EventKind::Remove => W1MessageType::SlaveRemove,
+                };
+                (msg_type, target.to_ne_bytes())
             }
-            MasterCommand { target, cmds } => todo!(),
-            SlaveCommand { target, cmds } => todo!(),
-            MasterEvent { kind, target } => todo!(),
-            SlaveEvent { kind, target } => todo!(),
         };
 
         let raw = W1NetlinkMsg {
             r#type: msg_type.into(),
             status: 0,
             len,
-            id: id.to_le_bytes(),
+            id,
         };
-        let msg = safe_transmute::transmute_one_to_bytes(&raw);
+        raw.encode(&mut buffer[..Self::HEADER_LEN]);
+
+        let payload = &mut buffer[Self::HEADER_LEN..];
+        match self {
+            ListMasters(ids) => {
+                for (id, chunk) in ids.iter().flatten().zip(payload.chunks_mut(4)) {
+                    chunk.copy_from_slice(&id.to_ne_bytes());
+                }
+            }
+            MasterCommand { cmds, .. } | SlaveCommand { cmds, .. } => cmds.serialize(payload),
+            MasterEvent { .. } | SlaveEvent { .. } => {}
+        }
+    }
+}
+
+/// Master ids occupy the first four bytes of the id field, followed by a
+/// reserved word.
+fn master_id(target: u32) -> [u8; 8] {
+    let mut id = [0; 8];
+    id[..4].copy_from_slice(&target.to_ne_bytes());
+    id
+}
+
+/// Slave targets are written as sysfs style ids like [`SlaveId`].
+///
+/// [`SlaveId`]: command::SlaveId
+#[cfg(feature = "serde")]
+mod slave_target {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::proto::command::SlaveId;
+
+    pub fn serialize<S: Serializer>(target: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        SlaveId::from(*target).serialize(serializer)
+    }
 
-        debug_assert_eq!(Self::HEADER_LEN, mem::size_of::<W1NetlinkMsg>());
-        buffer[0..Self::HEADER_LEN].copy_from_slice(msg);
-        buffer[Self::HEADER_LEN..].copy_from_slice(&payload);
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        SlaveId::deserialize(deserializer).map(u64::from)
     }
 }
@@ -11,24 +11,190 @@
 //! command request. One reply is generated exactly for one w1_netlink_cmd
 //! read request.
 
-use std::{convert::Infallible, marker::PhantomData};
+use alloc::{vec, vec::Vec};
+use core::{convert::Infallible, fmt, marker::PhantomData};
 
 pub mod command;
 pub mod connector;
 pub mod message;
 
-#[derive(Debug, thiserror::Error)]
-#[error("Invalid value received: {0}")]
+// Error types implement `Display` and `Error` by hand so that they are
+// available without `std`.
+
+#[derive(Debug)]
 pub struct InvalidValue(u8);
 
-#[derive(Debug, thiserror::Error)]
-#[error("Invalid length: {0}")]
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid value received: {}", self.0)
+    }
+}
+
+impl core::error::Error for InvalidValue {}
+
+#[derive(Debug)]
 pub struct InvalidLength(usize);
 
+impl fmt::Display for InvalidLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid length: {}", self.0)
+    }
+}
+
+impl core::error::Error for InvalidLength {}
+
+/// Input ended before a structure or the data announced by a `len` field was
+/// complete. Offsets are relative to the start of the decoded structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncated {
+    pub offset: usize,
+    pub needed: usize,
+    pub available: usize,
+}
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Truncated input at offset {}: {} bytes needed, {} available",
+            self.offset, self.needed, self.available
+        )
+    }
+}
+
+impl core::error::Error for Truncated {}
+
+/// A message cannot be encoded because a length does not fit its field or
+/// exceeds a kernel limit, or the buffer does not match the encoded size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializeError {
+    TooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+    BufferSize {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::TooLong { field, len, max } => {
+                write!(f, "{field} of {len} bytes exceeds the limit of {max} bytes")
+            }
+            SerializeError::BufferSize { expected, actual } => {
+                write!(f, "Buffer of {actual} bytes given for {expected} bytes")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SerializeError {}
+
+/// Bytes written as lower case hex pairs separated by spaces, e.g. `cc 44`.
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `len` stays within `max`.
+pub(crate) fn check_len(field: &'static str, len: usize, max: usize) -> Result<(), SerializeError> {
+    match len <= max {
+        true => Ok(()),
+        false => Err(SerializeError::TooLong { field, len, max }),
+    }
+}
+
+/// Converts the value of a `len` field, panicking instead of truncating.
+pub(crate) fn len_field(len: usize) -> u16 {
+    u16::try_from(len).expect("length field overflow, validate before serializing")
+}
+
+/// Fixed size structure of the kernel ABI. Fields are in host byte order as
+/// the kernel defines them and encoded one by one, so there are no alignment
+/// requirements on the buffer.
+pub(crate) trait RawHeader: Sized {
+    const LEN: usize;
+
+    /// Decodes from exactly [`LEN`](Self::LEN) bytes.
+    fn decode(bytes: &[u8]) -> Self;
+
+    /// Encodes into exactly [`LEN`](Self::LEN) bytes.
+    fn encode(&self, buffer: &mut [u8]);
+}
+
+/// Decodes the raw header at the start of `buffer`.
+pub(crate) fn read_header<T: RawHeader>(buffer: &[u8]) -> Result<T, Truncated> {
+    take(buffer, 0, T::LEN).map(T::decode)
+}
+
+pub(crate) fn ne_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub(crate) fn ne_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns `len` bytes at `offset` of `buffer`.
+pub(crate) fn take(buffer: &[u8], offset: usize, len: usize) -> Result<&[u8], Truncated> {
+    offset
+        .checked_add(len)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or(Truncated {
+            offset,
+            needed: len,
+            available: buffer.len().saturating_sub(offset),
+        })
+}
+
 pub trait Serializable {
     fn buffer_len(&self) -> usize;
 
+    /// Encodes into a buffer of exactly [`buffer_len`](Self::buffer_len)
+    /// bytes.
+    ///
+    /// # Panics
+    ///
+    /// On a buffer of another size or a length overflowing its field, use
+    /// [`try_serialize`](Self::try_serialize) for untrusted input.
     fn serialize(&self, buffer: &mut [u8]);
+
+    /// Checks every length field against its size and the kernel limits.
+    fn validate(&self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+
+    fn try_serialize(&self, buffer: &mut [u8]) -> Result<(), SerializeError> {
+        self.validate()?;
+        let expected = self.buffer_len();
+        if buffer.len() != expected {
+            return Err(SerializeError::BufferSize {
+                expected,
+                actual: buffer.len(),
+            });
+        }
+        self.serialize(buffer);
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut buffer = vec![0; self.buffer_len()];
+        self.try_serialize(&mut buffer)?;
+        Ok(buffer)
+    }
 }
 
 impl Serializable for () {
@@ -47,6 +213,10 @@ where
         self.iter().map(Serializable::buffer_len).sum()
     }
 
+    fn validate(&self) -> Result<(), SerializeError> {
+        self.iter().try_for_each(Serializable::validate)
+    }
+
     fn serialize(&self, buffer: &mut [u8]) {
         let mut cursor = 0;
         for item in self {
@@ -61,7 +231,7 @@ pub trait Deserializable
 where
     Self: Sized,
 {
-    type Error: std::error::Error + Send + Sync + 'static;
+    type Error: core::error::Error + Send + Sync + 'static;
 
     fn deserialize(payload: &[u8]) -> Result<(Self, usize), Self::Error>;
 }
@@ -73,7 +243,7 @@ impl<H> Deserializable for Empty<H> {
     type Error = Infallible;
 
     fn deserialize(_payload: &[u8]) -> Result<(Self, usize), Self::Error> {
-        Ok((Self(PhantomData::default()), 0))
+        Ok((Self(PhantomData), 0))
     }
 }
 
@@ -90,6 +260,9 @@ where
         while cursor < len {
             let (item, read) = T::deserialize(&payload[cursor..len])?;
             cmds.push(item);
+            if read == 0 {
+                break;
+            }
             cursor += read;
         }
         Ok((cmds, cursor))
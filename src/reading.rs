@@ -0,0 +1,164 @@
+//! Readings of the devices with a driver in [`device`](crate::device), for
+//! consumers that do not care which driver produced them.
+
+use std::time::SystemTime;
+
+use crate::{
+    device::{
+        ds2406::{self, Ds2406},
+        ds2408::{self, Ds2408},
+        ds2413::{self, Ds2413},
+        ds2423::{self, Ds2423},
+        ds2450::{self, Channel, Ds2450},
+        ds28ea00::{self, Ds28ea00},
+        thermometer,
+    },
+    proto::command::SlaveId,
+    transport::{Error, Transport},
+};
+
+const LETTERS: [&str; 4] = ["A", "B", "C", "D"];
+const DIGITS: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Quantity {
+    /// In °C.
+    Temperature,
+    /// In V.
+    Voltage,
+    /// Sensed level of a switch channel, 1 if high.
+    Level,
+    /// State of the output transistor of a switch channel, 1 if on.
+    Output,
+    /// Pulses counted on a counter input.
+    Count,
+}
+
+impl Quantity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantity::Temperature => "temperature",
+            Quantity::Voltage => "voltage",
+            Quantity::Level => "level",
+            Quantity::Output => "output",
+            Quantity::Count => "count",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Quantity::Temperature => "°C",
+            Quantity::Voltage => "V",
+            Quantity::Level | Quantity::Output | Quantity::Count => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub timestamp: SystemTime,
+    pub rom: SlaveId,
+    pub quantity: Quantity,
+    /// Channel of multi channel devices, e.g. `A` or `7`.
+    pub channel: Option<&'static str>,
+    pub value: f64,
+}
+
+/// Whether [`read_device`] knows the devices of `family`.
+pub fn has_readings(family: u8) -> bool {
+    thermometer::is_thermometer(family)
+        || matches!(
+            family,
+            ds2450::FAMILY
+                | ds2406::FAMILY
+                | ds28ea00::FAMILY
+                | ds2413::FAMILY
+                | ds2408::FAMILY
+                | ds2423::FAMILY
+        )
+}
+
+/// Reads all values of a device. Thermometers and A/D converters return the
+/// result of the last conversion, start one first.
+pub fn read_device(t: &mut impl Transport, id: SlaveId) -> Result<Vec<Reading>, Error> {
+    let mut values = Vec::new();
+    if thermometer::is_thermometer(id.family()) {
+        let pad = thermometer::read_scratchpad(t, id)?;
+        values.push((
+            Quantity::Temperature,
+            None,
+            widen(pad.temperature().celsius()),
+        ));
+    }
+    let mut switch = |quantity, channel, high: bool| {
+        values.push((quantity, Some(channel), f64::from(u8::from(high))));
+    };
+    let level = Quantity::Level;
+    let output = Quantity::Output;
+    match id.family() {
+        ds2406::FAMILY => {
+            let info = Ds2406::new(id).channel_info(t)?;
+            switch(level, LETTERS[0], info.level_a());
+            if info.has_b() {
+                switch(level, LETTERS[1], info.level_b());
+            }
+        }
+        ds28ea00::FAMILY => {
+            let pio = Ds28ea00::new(id).read_pio(t)?;
+            switch(level, LETTERS[0], pio.a());
+            switch(level, LETTERS[1], pio.b());
+        }
+        ds2413::FAMILY => {
+            let pio = Ds2413::new(id).read_pio(t)?;
+            switch(level, LETTERS[0], pio.a());
+            switch(level, LETTERS[1], pio.b());
+            // a latch cleared turns the output transistor on
+            switch(output, LETTERS[0], !pio.a_latch());
+            switch(output, LETTERS[1], !pio.b_latch());
+        }
+        ds2408::FAMILY => {
+            let registers = Ds2408::new(id).read_registers(t)?;
+            let (levels, latches) = (registers.levels(), registers.latches());
+            for (n, channel) in DIGITS.into_iter().enumerate() {
+                switch(level, channel, levels & 1 << n != 0);
+            }
+            for (n, channel) in DIGITS.into_iter().enumerate() {
+                switch(output, channel, latches & 1 << n == 0);
+            }
+        }
+        ds2423::FAMILY => {
+            let counters = Ds2423::new(id).read_counters(t)?;
+            for (count, channel) in counters.into_iter().zip(LETTERS) {
+                values.push((Quantity::Count, Some(channel), f64::from(count)));
+            }
+        }
+        ds2450::FAMILY => {
+            let adc = Ds2450::new(id);
+            let status = adc.read_status(t)?;
+            let raw = adc.read_conversions(t)?;
+            for c in Channel::ALL {
+                let volts = status[c.index()].range().voltage(raw[c.index()]);
+                values.push((Quantity::Voltage, Some(LETTERS[c.index()]), widen(volts)));
+            }
+        }
+        _ => {}
+    }
+    let timestamp = SystemTime::now();
+    Ok(values
+        .into_iter()
+        .map(|(quantity, channel, value)| Reading {
+            timestamp,
+            rom: id,
+            quantity,
+            channel,
+            value,
+        })
+        .collect())
+}
+
+/// Widens a reading without exposing digits beyond its `f32` precision.
+pub(crate) fn widen(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(f64::NAN)
+}
@@ -0,0 +1,245 @@
+//! Names, locations and other metadata of devices, kept in a TOML or YAML
+//! file.
+//!
+//! ```toml
+//! [devices.28-0000056c3a1f]
+//! name = "Kitchen"
+//! location = "Ground floor"
+//! offset = -0.25
+//! master = 1
+//! ```
+//!
+//! The registry also tells which devices to expect: [`Registry::check`]
+//! flags devices missing from the registry, registered devices missing on
+//! the buses and devices found on another master than expected.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    proto::command::SlaveId,
+    sink::Aliases,
+    topology::{Change, Snapshot},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Cannot write TOML: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+
+    #[error("Invalid YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("Unknown registry format of {0}, expected .toml, .yaml or .yml")]
+    Format(PathBuf),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+
+    /// Calibration offset added to the readings, in their unit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+
+    /// Calibration gain the readings are multiplied with before adding the
+    /// offset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+
+    /// Master the device is expected on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub master: Option<u32>,
+}
+
+/// A difference between the registry and the devices present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "finding", rename_all = "snake_case")]
+pub enum Finding {
+    /// A device present but not registered.
+    Unknown { id: SlaveId },
+    /// A registered device not present.
+    Missing { id: SlaveId },
+    /// A device present on another master than registered.
+    Misplaced {
+        id: SlaveId,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl Finding {
+    pub fn id(&self) -> SlaveId {
+        match self {
+            Finding::Unknown { id } | Finding::Missing { id } | Finding::Misplaced { id, .. } => {
+                *id
+            }
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Unknown { id } => write!(f, "unknown device {id}"),
+            Finding::Missing { id } => write!(f, "missing device {id}"),
+            Finding::Misplaced {
+                id,
+                expected,
+                actual,
+            } => write!(f, "device {id} on master {actual}, expected {expected}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registry {
+    #[serde(default)]
+    devices: BTreeMap<SlaveId, DeviceInfo>,
+}
+
+enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            _ => Err(Error::Format(path.to_path_buf())),
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a registry, the extension of `path` tells the format.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let format = Format::of(path)?;
+        let text = fs::read_to_string(path)?;
+        match format {
+            Format::Toml => Self::from_toml(&text),
+            Format::Yaml => Self::from_yaml(&text),
+        }
+    }
+
+    /// Writes the registry, the extension of `path` tells the format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let text = match Format::of(path)? {
+            Format::Toml => self.to_toml()?,
+            Format::Yaml => self.to_yaml()?,
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, Error> {
+        Ok(serde_yaml::from_str(text)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, Error> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    pub fn get(&self, id: SlaveId) -> Option<&DeviceInfo> {
+        self.devices.get(&id)
+    }
+
+    pub fn insert(&mut self, id: SlaveId, info: DeviceInfo) -> Option<DeviceInfo> {
+        self.devices.insert(id, info)
+    }
+
+    pub fn remove(&mut self, id: SlaveId) -> Option<DeviceInfo> {
+        self.devices.remove(&id)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = (SlaveId, &DeviceInfo)> {
+        self.devices.iter().map(|(id, info)| (*id, info))
+    }
+
+    pub fn name(&self, id: SlaveId) -> Option<&str> {
+        self.get(id)?.name.as_deref()
+    }
+
+    /// Names of all named devices, e.g. for sinks and exporters.
+    pub fn aliases(&self) -> Aliases {
+        self.devices
+            .iter()
+            .filter_map(|(id, info)| Some((*id, info.name.clone()?)))
+            .collect()
+    }
+
+    fn check_present(&self, master: u32, id: SlaveId) -> Option<Finding> {
+        match self.get(id) {
+            None => Some(Finding::Unknown { id }),
+            Some(DeviceInfo {
+                master: Some(expected),
+                ..
+            }) if *expected != master => Some(Finding::Misplaced {
+                id,
+                expected: *expected,
+                actual: master,
+            }),
+            Some(_) => None,
+        }
+    }
+
+    /// Compares the registry with all devices present.
+    pub fn check(&self, snapshot: &Snapshot) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for master in snapshot.masters() {
+            for id in snapshot.slaves(master) {
+                findings.extend(self.check_present(master, id));
+            }
+        }
+        for id in self.devices.keys() {
+            if snapshot.master_of(*id).is_none() {
+                findings.push(Finding::Missing { id: *id });
+            }
+        }
+        findings
+    }
+
+    /// Flags a device appearing or a registered device going away.
+    pub fn check_change(&self, change: &Change) -> Option<Finding> {
+        match *change {
+            Change::SlaveAdded { master, id } => self.check_present(master, id),
+            Change::SlaveRemoved { id, .. } if self.devices.contains_key(&id) => {
+                Some(Finding::Missing { id })
+            }
+            _ => None,
+        }
+    }
+}
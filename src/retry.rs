@@ -0,0 +1,244 @@
+//! Retries of failed bus transactions.
+//!
+//! A 1-Wire bus picks up noise, so a single CRC mismatch or a missing
+//! presence pulse says little about the device. [`classify`] tells such
+//! transient errors from permanent ones, like an unknown master, and a
+//! [`RetryPolicy`] repeats an operation on transient errors with a growing
+//! delay between the attempts:
+//!
+//! ```no_run
+//! # use w1_netlink::{client::W1Client, device::thermometer, retry::RetryPolicy};
+//! # let id = "28-0000056c3a1f".parse().unwrap();
+//! let mut client = W1Client::new().unwrap();
+//! let policy = RetryPolicy::new().with_attempts(5);
+//! let pad = policy.run(|| thermometer::read_scratchpad(&mut client, id)).unwrap();
+//! ```
+//!
+//! Retry whole driver operations, which check the CRC of what they read.
+//! [`Retry`] only repeats single messages to the w1 core.
+//!
+//! The w1 core selects the slave at the start of every slave command and
+//! reports `ENODEV` both when the slave is unknown and when it misses the
+//! presence pulse. [`classify_slave`] tells the two apart by asking whether
+//! the slave is still registered, [`RetryPolicy::run_slave`] retries with it.
+
+use std::{io, thread, time::Duration};
+
+use crate::{
+    proto::{command::SlaveId, message::W1NetlinkMessage},
+    transport::{Error, Transport},
+};
+
+// Error statuses of the w1 core, the negated errno of the failed command.
+const EINTR: u8 = 4;
+const EIO: u8 = 5;
+const EAGAIN: u8 = 11;
+const EBUSY: u8 = 16;
+const ETIMEDOUT: u8 = 110;
+/// An unknown master or slave, or a slave that missed the presence pulse
+/// when selected at the start of a slave command.
+const ENODEV: u8 = 19;
+/// A slave command whose reset saw no presence pulse. Selecting the slave
+/// fails with `-1`, which the w1 core negates like an errno.
+const NO_PRESENCE: u8 = 1;
+/// A master reset without presence pulse, reported as `-1`.
+const NO_PRESENCE_MASTER: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Likely to succeed when tried again, e.g. after a CRC mismatch.
+    Transient,
+    /// Fails the same way every time, e.g. for an unknown master.
+    Permanent,
+}
+
+/// Whether trying again may help.
+///
+/// CRC mismatches, missing presence pulses, busy or timed out buses and
+/// interrupted or timed out socket calls are transient. Unknown masters and
+/// slaves (`ENODEV`, see [`classify_slave`]), malformed requests, devices of
+/// an unexpected family and protocol errors are permanent.
+pub fn classify(err: &Error) -> ErrorClass {
+    let transient = match err {
+        Error::Crc { .. } | Error::NoDevice => true,
+        Error::Status(status) => matches!(
+            *status,
+            NO_PRESENCE | NO_PRESENCE_MASTER | EINTR | EIO | EAGAIN | EBUSY | ETIMEDOUT
+        ),
+        Error::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ),
+        Error::Netlink(_)
+        | Error::Connector(_)
+        | Error::Serialize(_)
+        | Error::UnexpectedReply(_)
+        | Error::Device(_) => false,
+    };
+    match transient {
+        true => ErrorClass::Transient,
+        false => ErrorClass::Permanent,
+    }
+}
+
+pub fn is_transient(err: &Error) -> bool {
+    classify(err) == ErrorClass::Transient
+}
+
+/// Like [`classify`] for an error of a command to `slave`. `ENODEV` is
+/// transient while the w1 core still lists the slave on one of its masters,
+/// as the slave then only missed the presence pulse, and permanent otherwise.
+pub fn classify_slave(
+    t: &mut (impl Transport + ?Sized),
+    slave: SlaveId,
+    err: &Error,
+) -> ErrorClass {
+    if !matches!(err, Error::Status(ENODEV)) {
+        return classify(err);
+    }
+    let registered = t.list_masters().is_ok_and(|masters| {
+        masters
+            .into_iter()
+            .any(|master| t.list_slaves(master).is_ok_and(|ids| ids.contains(&slave)))
+    });
+    match registered {
+        true => ErrorClass::Transient,
+        false => ErrorClass::Permanent,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tries an operation at most `attempts` times, including the first, 3 by
+    /// default.
+    pub fn with_attempts(self, attempts: u32) -> Self {
+        Self {
+            attempts: attempts.max(1),
+            ..self
+        }
+    }
+
+    /// Delay before the first retry, doubling for every further one, 10 ms by
+    /// default.
+    pub fn with_backoff(self, backoff: Duration) -> Self {
+        Self { backoff, ..self }
+    }
+
+    /// Upper limit of the delay, 1 s by default.
+    pub fn with_max_backoff(self, max_backoff: Duration) -> Self {
+        Self {
+            max_backoff,
+            ..self
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Delay before retry number `retry`, counting from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    /// Runs `op` until it succeeds, fails permanently or all attempts are
+    /// used up, and returns the last result.
+    pub fn run<R>(&self, mut op: impl FnMut() -> Result<R, Error>) -> Result<R, Error> {
+        self.run_classified(&mut (), |_| op(), |_, err| classify(err))
+    }
+
+    /// Runs an operation on `slave` like [`run`](Self::run), classifying
+    /// errors with [`classify_slave`].
+    pub fn run_slave<T: Transport + ?Sized, R>(
+        &self,
+        t: &mut T,
+        slave: SlaveId,
+        op: impl FnMut(&mut T) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        self.run_classified(t, op, |t, err| classify_slave(t, slave, err))
+    }
+
+    fn run_classified<C: ?Sized, R>(
+        &self,
+        ctx: &mut C,
+        mut op: impl FnMut(&mut C) -> Result<R, Error>,
+        classify: impl Fn(&mut C, &Error) -> ErrorClass,
+    ) -> Result<R, Error> {
+        let mut retry = 0;
+        loop {
+            match op(ctx) {
+                Err(err)
+                    if retry + 1 < self.attempts
+                        && classify(ctx, &err) == ErrorClass::Transient =>
+                {
+                    thread::sleep(self.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A transport retrying every message to the w1 core that failed
+/// transiently.
+///
+/// Messages with writes are repeated as a whole, which is only safe for
+/// writes that can be repeated without harm.
+#[derive(Debug)]
+pub struct Retry<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T: Transport> Retry<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport> Transport for Retry<T> {
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        match &msg {
+            W1NetlinkMessage::SlaveCommand { target, .. } => {
+                let slave = SlaveId::from(*target);
+                self.policy
+                    .run_slave(&mut self.inner, slave, |t| t.transact(msg.clone()))
+            }
+            _ => {
+                let inner = &mut self.inner;
+                self.policy.run(|| inner.transact(msg.clone()))
+            }
+        }
+    }
+}
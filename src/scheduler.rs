@@ -0,0 +1,437 @@
+//! Polling of many devices, each at its own interval.
+//!
+//! A bus only does one thing at a time, so every master gets a queue of its
+//! own and the devices of a queue are read one after the other, the one with
+//! the earliest deadline first. Thermometers and A/D converters due within
+//! the batch window share a single bus-wide conversion, and the conversions
+//! of all masters run at the same time.
+//!
+//! The scheduler does not sleep between polls, the caller decides how to
+//! wait:
+//!
+//! ```no_run
+//! # use std::{thread, time::Instant};
+//! # use w1_netlink::{client::W1Client, scheduler::Scheduler, sink::LineProtocol};
+//! let mut client = W1Client::new().unwrap();
+//! let mut scheduler = Scheduler::new().with_sink(LineProtocol::stdout());
+//! scheduler.discover(&mut client, Instant::now()).unwrap();
+//! loop {
+//!     scheduler.poll(&mut client, Instant::now());
+//!     if let Some(due) = scheduler.next_due() {
+//!         thread::sleep(due.saturating_duration_since(Instant::now()));
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    device::{convert, ds2450, thermometer},
+    pipeline::{Pipeline, Rejection},
+    proto::command::SlaveId,
+    reading::{has_readings, read_device, Reading},
+    retry::RetryPolicy,
+    sink::{self, Sink},
+    topology::Change,
+    transport::{Error, Transport},
+};
+
+/// When to poll a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    pub interval: Duration,
+    /// How late after being due a poll may complete.
+    pub deadline: Duration,
+}
+
+impl Schedule {
+    /// Polls every `interval`, with the interval as deadline.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            deadline: interval,
+        }
+    }
+
+    pub fn with_deadline(self, deadline: Duration) -> Self {
+        Self { deadline, ..self }
+    }
+}
+
+/// Activity of a bus since the stats were reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BusStats {
+    /// Time spent on searches, conversions and reads.
+    pub busy: Duration,
+    pub elapsed: Duration,
+    pub searches: u64,
+    pub conversions: u64,
+    pub reads: u64,
+    /// Failed searches, conversions and reads.
+    pub errors: u64,
+    /// Polls completed after their deadline.
+    pub missed_deadlines: u64,
+}
+
+impl BusStats {
+    /// Fraction of the elapsed time the bus was busy.
+    pub fn utilization(&self) -> f64 {
+        match self.elapsed.is_zero() {
+            true => 0.0,
+            false => (self.busy.as_secs_f64() / self.elapsed.as_secs_f64()).min(1.0),
+        }
+    }
+}
+
+/// Outcome of a [`Scheduler::poll`].
+#[derive(Debug, Default)]
+pub struct Report {
+    pub readings: Vec<Reading>,
+    /// Devices that failed to read.
+    pub failures: Vec<(SlaveId, Error)>,
+    /// Masters whose conversion failed. Their thermometers and A/D converters
+    /// were not read.
+    pub bus_failures: Vec<(u32, Error)>,
+    /// Readings the pipeline rejected, not passed to the sinks.
+    pub rejected: Vec<Rejection>,
+    pub sink_errors: Vec<sink::Error>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    schedule: Schedule,
+    due: Instant,
+}
+
+impl Slot {
+    fn deadline(&self) -> Instant {
+        self.due + self.schedule.deadline
+    }
+
+    /// Keeps the phase unless the device fell behind by a whole interval.
+    fn reschedule(&mut self, now: Instant) {
+        self.due += self.schedule.interval;
+        if self.due <= now {
+            self.due = now + self.schedule.interval;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Queue {
+    devices: BTreeMap<SlaveId, Slot>,
+    stats: BusStats,
+    since: Instant,
+}
+
+impl Queue {
+    fn new(now: Instant) -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            stats: BusStats::default(),
+            since: now,
+        }
+    }
+}
+
+fn needs_conversion(family: u8) -> bool {
+    thermometer::is_thermometer(family) || family == ds2450::FAMILY
+}
+
+pub struct Scheduler {
+    default: Schedule,
+    schedules: BTreeMap<SlaveId, Schedule>,
+    conversion_time: Duration,
+    batch_window: Duration,
+    queues: BTreeMap<u32, Queue>,
+    retry: Option<RetryPolicy>,
+    pipeline: Option<Pipeline>,
+    sinks: Vec<Box<dyn Sink + Send>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            default: Schedule::every(Duration::from_secs(60)),
+            schedules: BTreeMap::new(),
+            conversion_time: convert::conversion_time(),
+            batch_window: Duration::from_secs(1),
+            queues: BTreeMap::new(),
+            retry: None,
+            pipeline: None,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule of devices without one of their own, every minute by default.
+    pub fn with_default_schedule(self, default: Schedule) -> Self {
+        Self { default, ..self }
+    }
+
+    /// Time to wait between starting the conversions and reading the devices.
+    pub fn with_conversion_time(self, conversion_time: Duration) -> Self {
+        Self {
+            conversion_time,
+            ..self
+        }
+    }
+
+    /// Thermometers and A/D converters due within this time are polled early
+    /// to share the conversion of those already due, 1 s by default.
+    pub fn with_batch_window(self, batch_window: Duration) -> Self {
+        Self {
+            batch_window,
+            ..self
+        }
+    }
+
+    /// Retries reading a device after transient errors, no retries by
+    /// default. Conversions are not retried.
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self {
+            retry: Some(retry),
+            ..self
+        }
+    }
+
+    /// Processes the readings of every poll before they reach the sinks.
+    pub fn with_pipeline(self, pipeline: Pipeline) -> Self {
+        Self {
+            pipeline: Some(pipeline),
+            ..self
+        }
+    }
+
+    /// Adds a sink receiving the readings of every poll.
+    pub fn with_sink(mut self, sink: impl Sink + Send + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Sets the schedule of a device, also before it is added.
+    pub fn set_schedule(&mut self, id: SlaveId, schedule: Schedule) {
+        self.schedules.insert(id, schedule);
+        if let Some(slot) = self.slot_mut(id) {
+            slot.schedule = schedule;
+        }
+    }
+
+    fn slot_mut(&mut self, id: SlaveId) -> Option<&mut Slot> {
+        self.queues
+            .values_mut()
+            .find_map(|queue| queue.devices.get_mut(&id))
+    }
+
+    /// Adds a device to the queue of `master`, due right away. Returns
+    /// `false` for devices without readings and devices already queued.
+    pub fn add_device(&mut self, master: u32, id: SlaveId, now: Instant) -> bool {
+        if !has_readings(id.family()) {
+            return false;
+        }
+        let queue = self.queues.entry(master).or_insert_with(|| Queue::new(now));
+        if queue.devices.contains_key(&id) {
+            return false;
+        }
+        let schedule = self.schedules.get(&id).copied().unwrap_or(self.default);
+        queue.devices.insert(id, Slot { schedule, due: now });
+        // a device moved to another bus
+        for (other, queue) in &mut self.queues {
+            if *other != master {
+                queue.devices.remove(&id);
+            }
+        }
+        true
+    }
+
+    pub fn remove_device(&mut self, id: SlaveId) -> bool {
+        if let Some(pipeline) = &mut self.pipeline {
+            pipeline.forget(id);
+        }
+        self.queues
+            .values_mut()
+            .any(|queue| queue.devices.remove(&id).is_some())
+    }
+
+    /// Follows a change of the [`Topology`](crate::topology::Topology), so
+    /// the queues need no searches of their own.
+    pub fn apply(&mut self, change: &Change, now: Instant) {
+        match *change {
+            Change::SlaveAdded { master, id } => {
+                self.add_device(master, id, now);
+            }
+            Change::SlaveRemoved { id, .. } => {
+                self.remove_device(id);
+            }
+            Change::MasterRemoved(master) => {
+                self.queues.remove(&master);
+            }
+            Change::MasterAdded(_) => {}
+        }
+    }
+
+    /// Devices queued for `master`.
+    pub fn devices(&self, master: u32) -> impl Iterator<Item = SlaveId> + '_ {
+        self.queues
+            .get(&master)
+            .into_iter()
+            .flat_map(|queue| queue.devices.keys().copied())
+    }
+
+    /// Searches all buses, queues the devices found and drops the queued
+    /// devices that were not found. Failed searches keep their queue as is.
+    pub fn discover(&mut self, t: &mut impl Transport, now: Instant) -> Result<(), Error> {
+        let masters = t.list_masters()?;
+        self.queues.retain(|master, _| masters.contains(master));
+        for master in masters {
+            let start = Instant::now();
+            let found = t.search(master, false);
+            let queue = self.queues.entry(master).or_insert_with(|| Queue::new(now));
+            queue.stats.busy += start.elapsed();
+            queue.stats.searches += 1;
+            let Ok(found) = found else {
+                queue.stats.errors += 1;
+                continue;
+            };
+            queue.devices.retain(|id, _| found.contains(id));
+            for id in found {
+                self.add_device(master, id, now);
+            }
+        }
+        Ok(())
+    }
+
+    /// When the next device is due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queues
+            .values()
+            .flat_map(|queue| queue.devices.values())
+            .map(|slot| slot.due)
+            .min()
+    }
+
+    /// Reads all devices due at `now` and passes their readings through the
+    /// pipeline to the sinks.
+    pub fn poll(&mut self, t: &mut impl Transport, now: Instant) -> Report {
+        let started = Instant::now();
+        let horizon = now + self.batch_window;
+        let mut report = Report::default();
+
+        let mut batches: Vec<(u32, Vec<SlaveId>)> = Vec::new();
+        for (master, queue) in &self.queues {
+            let converting = queue
+                .devices
+                .iter()
+                .any(|(id, slot)| slot.due <= now && needs_conversion(id.family()));
+            let mut due: Vec<_> = queue
+                .devices
+                .iter()
+                .filter(|(id, slot)| {
+                    slot.due <= now
+                        || converting && needs_conversion(id.family()) && slot.due <= horizon
+                })
+                .collect();
+            if due.is_empty() {
+                continue;
+            }
+            due.sort_by_key(|(_, slot)| slot.deadline());
+            batches.push((*master, due.into_iter().map(|(id, _)| *id).collect()));
+        }
+
+        // start all conversions before waiting for any of them
+        let mut converting = Vec::new();
+        for (master, ids) in &batches {
+            if !ids.iter().any(|id| needs_conversion(id.family())) {
+                continue;
+            }
+            let start = Instant::now();
+            let result = convert::start(t, *master);
+            let stats = &mut self.queues.get_mut(master).unwrap().stats;
+            stats.busy += start.elapsed();
+            stats.conversions += 1;
+            match result {
+                Ok(()) => converting.push(*master),
+                Err(err) => {
+                    stats.errors += 1;
+                    report.bus_failures.push((*master, err));
+                }
+            }
+        }
+        if !converting.is_empty() {
+            thread::sleep(self.conversion_time);
+            for master in &converting {
+                self.queues.get_mut(master).unwrap().stats.busy += self.conversion_time;
+            }
+        }
+
+        let retry = self
+            .retry
+            .unwrap_or_else(|| RetryPolicy::new().with_attempts(1));
+        for (master, ids) in batches {
+            let queue = self.queues.get_mut(&master).unwrap();
+            for id in ids {
+                let slot = queue.devices.get_mut(&id).unwrap();
+                if !needs_conversion(id.family()) || converting.contains(&master) {
+                    let start = Instant::now();
+                    let result = retry.run_slave(t, id, |t| read_device(t, id));
+                    queue.stats.busy += start.elapsed();
+                    queue.stats.reads += 1;
+                    match result {
+                        Ok(readings) => report.readings.extend(readings),
+                        Err(err) => {
+                            queue.stats.errors += 1;
+                            report.failures.push((id, err));
+                        }
+                    }
+                }
+                let completed = now + started.elapsed();
+                if completed > slot.deadline() {
+                    queue.stats.missed_deadlines += 1;
+                }
+                slot.reschedule(now);
+            }
+        }
+
+        if let Some(pipeline) = &mut self.pipeline {
+            let processed = pipeline.process(report.readings.drain(..));
+            report.readings = processed.readings;
+            report.rejected = processed.rejected;
+        }
+        for sink in &mut self.sinks {
+            if let Err(err) = sink.write(&report.readings) {
+                report.sink_errors.push(err);
+            }
+        }
+        report
+    }
+
+    /// Activity of every bus since the stats were last reset.
+    pub fn stats(&self, now: Instant) -> BTreeMap<u32, BusStats> {
+        self.queues
+            .iter()
+            .map(|(master, queue)| {
+                let stats = BusStats {
+                    elapsed: now.saturating_duration_since(queue.since),
+                    ..queue.stats
+                };
+                (*master, stats)
+            })
+            .collect()
+    }
+
+    pub fn reset_stats(&mut self, now: Instant) {
+        for queue in self.queues.values_mut() {
+            queue.stats = BusStats::default();
+            queue.since = now;
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! ROM search in userspace, following Maxim application note 187.
+//!
+//! The kernel only offers complete bus searches. [`Search`] instead drives the
+//! search algorithm itself with [`Reset`](W1NetlinkCommand::Reset) and
+//! bit-level [`Touch`](W1NetlinkCommand::Touch) commands, which allows
+//! searching for a single family, skipping families, verifying a single ROM
+//! and resuming a search from a known state.
+//!
+//! A touch block cannot react to the bits read in it, so every round trip
+//! sends read-read-write triplets with guessed directions. Decoding stops at
+//! the first wrong guess and the next round trip continues with the corrected
+//! prefix, resolving at least one more bit each time.
+
+use crate::{
+    crc::crc8,
+    device::rom::{ALARM_SEARCH, SEARCH_ROM},
+    proto::command::{SlaveId, W1NetlinkCommand},
+    transport::{read_data, touch, write, Error, Transport},
+};
+
+const ROM_BITS: usize = 64;
+/// Three time slots per ROM bit.
+const TOUCH_LEN: usize = ROM_BITS * 3 / 8;
+
+/// State of a search on a single master.
+///
+/// Discrepancies are bit positions counted from 1 as in the application note,
+/// 0 meaning there is none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    master: u32,
+    command: u8,
+    rom: [u8; 8],
+    last_discrepancy: u8,
+    last_family_discrepancy: u8,
+    last_device: bool,
+    family: Option<u8>,
+}
+
+impl Search {
+    /// Searches all devices on `master`.
+    pub fn new(master: u32) -> Self {
+        Self {
+            master,
+            command: SEARCH_ROM,
+            rom: [0; 8],
+            last_discrepancy: 0,
+            last_family_discrepancy: 0,
+            last_device: false,
+            family: None,
+        }
+    }
+
+    /// Searches devices on `master` with an active alarm condition.
+    pub fn alarm(master: u32) -> Self {
+        Self {
+            command: ALARM_SEARCH,
+            ..Self::new(master)
+        }
+    }
+
+    /// Searches only devices of `family`.
+    pub fn family(master: u32, family: u8) -> Self {
+        Self {
+            rom: [family, 0, 0, 0, 0, 0, 0, 0],
+            last_discrepancy: ROM_BITS as u8,
+            family: Some(family),
+            ..Self::new(master)
+        }
+    }
+
+    /// Continues a search after `last`, which was found with
+    /// `last_discrepancy`.
+    pub fn resume(master: u32, last: SlaveId, last_discrepancy: u8) -> Self {
+        Self {
+            rom: last.bytes(),
+            last_discrepancy,
+            last_device: last_discrepancy == 0,
+            ..Self::new(master)
+        }
+    }
+
+    pub fn master(&self) -> u32 {
+        self.master
+    }
+
+    /// ROM found last, all zero before the first device was found.
+    pub fn last_rom(&self) -> SlaveId {
+        SlaveId::new(self.rom)
+    }
+
+    pub fn last_discrepancy(&self) -> u8 {
+        self.last_discrepancy
+    }
+
+    pub fn last_family_discrepancy(&self) -> u8 {
+        self.last_family_discrepancy
+    }
+
+    /// Whether all devices have been found.
+    pub fn is_done(&self) -> bool {
+        self.last_device
+    }
+
+    /// Skips the remaining devices of the family found last.
+    pub fn skip_family(&mut self) {
+        self.last_discrepancy = self.last_family_discrepancy;
+        self.last_family_discrepancy = 0;
+        if self.last_discrepancy == 0 {
+            self.last_device = true;
+        }
+    }
+
+    /// Finds the next device, `None` once the search is complete.
+    pub fn next(&mut self, t: &mut impl Transport) -> Result<Option<SlaveId>, Error> {
+        if self.last_device {
+            return Ok(None);
+        }
+
+        let mut rom = self.rom;
+        let mut known = 0;
+        let (last_zero, last_family_zero) = 'search: loop {
+            let guesses: Vec<bool> = (0..ROM_BITS)
+                .map(|bit| match bit < known {
+                    true => rom_bit(&rom, bit),
+                    false => self.direction(bit),
+                })
+                .collect();
+            let slots = round_trip(t, self.master, self.command, &guesses)?;
+
+            let mut last_zero = 0;
+            let mut last_family_zero = 0;
+            for (bit, guess) in guesses.iter().enumerate() {
+                let direction = match (slots[3 * bit], slots[3 * bit + 1]) {
+                    (true, true) => {
+                        self.finish();
+                        return Ok(None);
+                    }
+                    (false, false) => {
+                        let direction = match bit < known {
+                            true => rom_bit(&rom, bit),
+                            false => self.direction(bit),
+                        };
+                        if !direction {
+                            last_zero = bit + 1;
+                            if bit < 8 {
+                                last_family_zero = bit + 1;
+                            }
+                        }
+                        direction
+                    }
+                    (id_bit, _) => id_bit,
+                };
+                set_rom_bit(&mut rom, bit, direction);
+                if direction != *guess {
+                    known = bit + 1;
+                    continue 'search;
+                }
+            }
+            break (last_zero, last_family_zero);
+        };
+
+        let crc = crc8(&rom[..7]);
+        if crc != rom[7] {
+            self.finish();
+            return Err(Error::Crc {
+                expected: crc.into(),
+                actual: rom[7].into(),
+            });
+        }
+
+        let id = SlaveId::new(rom);
+        if self.family.is_some_and(|family| family != id.family()) {
+            self.finish();
+            return Ok(None);
+        }
+        self.rom = rom;
+        self.last_discrepancy = last_zero as u8;
+        self.last_family_discrepancy = last_family_zero as u8;
+        self.last_device = last_zero == 0;
+        Ok(Some(id))
+    }
+
+    /// Runs the search to completion.
+    pub fn collect(mut self, t: &mut impl Transport) -> Result<Vec<SlaveId>, Error> {
+        let mut found = Vec::new();
+        while let Some(id) = self.next(t)? {
+            found.push(id);
+        }
+        Ok(found)
+    }
+
+    /// Direction taken at a discrepancy in `bit` according to the previous
+    /// search pass.
+    fn direction(&self, bit: usize) -> bool {
+        let position = bit + 1;
+        let last = usize::from(self.last_discrepancy);
+        match position.cmp(&last) {
+            std::cmp::Ordering::Less => rom_bit(&self.rom, bit),
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Greater => false,
+        }
+    }
+
+    fn finish(&mut self) {
+        self.last_discrepancy = 0;
+        self.last_family_discrepancy = 0;
+        self.last_device = true;
+    }
+}
+
+/// Checks whether the device `id` is present on `master` with a single
+/// search pass following its ROM.
+pub fn verify(t: &mut impl Transport, master: u32, id: SlaveId) -> Result<bool, Error> {
+    let rom = id.bytes();
+    let bits: Vec<bool> = (0..ROM_BITS).map(|bit| rom_bit(&rom, bit)).collect();
+    let slots = round_trip(t, master, SEARCH_ROM, &bits)?;
+    // every bit must have been sent by a device, either alone or along with
+    // devices sending its complement
+    Ok(bits.iter().enumerate().all(|(bit, value)| {
+        let (id_bit, cmp_bit) = (slots[3 * bit], slots[3 * bit + 1]);
+        (!id_bit && !cmp_bit) || (id_bit == *value && cmp_bit != *value)
+    }))
+}
+
+/// Resets the bus, sends the search `command` and one triplet per ROM bit
+/// writing `directions`. Returns the sampled time slots.
+fn round_trip(
+    t: &mut impl Transport,
+    master: u32,
+    command: u8,
+    directions: &[bool],
+) -> Result<Vec<bool>, Error> {
+    let mut data = [0u8; TOUCH_LEN];
+    for (bit, direction) in directions.iter().enumerate() {
+        // read slots are written as 1 so devices can pull the bus low
+        for (slot, value) in [
+            (3 * bit, true),
+            (3 * bit + 1, true),
+            (3 * bit + 2, *direction),
+        ] {
+            data[slot / 8] |= (value as u8) << (slot % 8);
+        }
+    }
+    let cmds = vec![W1NetlinkCommand::Reset, write([command]), touch(data)];
+    let replies = read_data(t.master_command(master, cmds)?);
+    match replies.first() {
+        Some(data) if data.len() == TOUCH_LEN => Ok((0..TOUCH_LEN * 8)
+            .map(|slot| data[slot / 8] >> (slot % 8) & 1 == 1)
+            .collect()),
+        _ => Err(Error::Device("short touch reply")),
+    }
+}
+
+fn rom_bit(rom: &[u8; 8], bit: usize) -> bool {
+    rom[bit / 8] >> (bit % 8) & 1 == 1
+}
+
+fn set_rom_bit(rom: &mut [u8; 8], bit: usize, value: bool) {
+    rom[bit / 8] = rom[bit / 8] & !(1 << (bit % 8)) | (value as u8) << (bit % 8);
+}
@@ -0,0 +1,266 @@
+//! Simulated DS2406 dual addressable switch.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::{
+    crc::{crc16, crc16_update},
+    device::ds2406::{
+        ChannelControl, Channels, ConditionalSearch, CrcInterval, SearchSource, CHANNEL_ACCESS,
+        MEMORY_LEN, READ_MEMORY, READ_STATUS, STATUS_CONTROL, STATUS_LEN, WRITE_STATUS,
+    },
+};
+
+#[derive(Debug, Clone)]
+enum State {
+    Idle,
+    /// Collecting command and parameter bytes.
+    Header(Vec<u8>),
+    ChannelAccess {
+        control: ChannelControl,
+        /// Index of the next data byte
+        n: usize,
+        crc: u16,
+    },
+    /// Transmitting until reset
+    Done,
+}
+
+pub struct Ds2406 {
+    pub memory: [u8; MEMORY_LEN],
+    pub status: [u8; STATUS_LEN],
+    /// Levels applied externally to PIO-A and PIO-B, `true` if pulled up.
+    pub inputs: [bool; 2],
+    /// Whether the package provides PIO-B, the TO-92 one does not.
+    pub has_b: bool,
+    activity: [bool; 2],
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Default for Ds2406 {
+    fn default() -> Self {
+        let mut status = [0xFF; STATUS_LEN];
+        // flip-flops off, search on activity of both channels
+        status[STATUS_CONTROL as usize] = 0x7F;
+        Self {
+            memory: [0xFF; MEMORY_LEN],
+            status,
+            inputs: [true; 2],
+            has_b: true,
+            activity: [false; 2],
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl Ds2406 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flip-flops of PIO-A and PIO-B, `false` if the transistor is on.
+    pub fn flip_flops(&self) -> [bool; 2] {
+        let control = self.status[STATUS_CONTROL as usize];
+        [control & 0x20 != 0, control & 0x40 != 0]
+    }
+
+    /// Changes an external input level, latching activity.
+    pub fn set_input(&mut self, channel: usize, level: bool) {
+        if self.levels()[channel] != (level && self.flip_flops()[channel]) {
+            self.activity[channel] = true;
+        }
+        self.inputs[channel] = level;
+    }
+
+    fn levels(&self) -> [bool; 2] {
+        let flip_flops = self.flip_flops();
+        [
+            flip_flops[0] && self.inputs[0],
+            flip_flops[1] && self.inputs[1],
+        ]
+    }
+
+    fn set_flip_flop(&mut self, channel: usize, value: bool) {
+        let level = self.levels()[channel];
+        let mask = 0x20 << channel;
+        let control = &mut self.status[STATUS_CONTROL as usize];
+        *control = *control & !mask | if value { mask } else { 0 };
+        if self.levels()[channel] != level {
+            self.activity[channel] = true;
+        }
+    }
+
+    fn info(&self) -> u8 {
+        let [ff_a, ff_b] = self.flip_flops();
+        let [level_a, level_b] = self.levels();
+        (self.has_b as u8) << 6
+            | (self.activity[1] as u8) << 5
+            | (self.activity[0] as u8) << 4
+            | (level_b as u8) << 3
+            | (level_a as u8) << 2
+            | (ff_b as u8) << 1
+            | ff_a as u8
+    }
+
+    fn channels(control: &ChannelControl) -> &'static [usize] {
+        match control.channels {
+            Channels::A => &[0],
+            Channels::B => &[1],
+            Channels::Both => &[0, 1],
+        }
+    }
+
+    /// Starts a command once all header bytes are received.
+    fn header(&mut self, header: Vec<u8>) -> State {
+        match header[..] {
+            [READ_MEMORY, lo, _] | [READ_STATUS, lo, _] => {
+                let mem: &[u8] = if header[0] == READ_MEMORY {
+                    &self.memory
+                } else {
+                    &self.status
+                };
+                let start = usize::from(lo).min(mem.len());
+                let mut crc = crc16(&header);
+                for byte in &mem[start..] {
+                    crc = crc16_update(crc, &[*byte]);
+                    self.out.push_back(*byte);
+                }
+                self.out.extend((!crc).to_le_bytes());
+                State::Done
+            }
+            [WRITE_STATUS, address, _, value] => {
+                self.out.extend((!crc16(&header)).to_le_bytes());
+                if address == STATUS_CONTROL {
+                    let control = &mut self.status[STATUS_CONTROL as usize];
+                    *control = *control & 0x80 | value & 0x7F;
+                }
+                let address = usize::from(address).min(STATUS_LEN - 1);
+                self.out.push_back(self.status[address]);
+                State::Done
+            }
+            [CHANNEL_ACCESS, control, _] => {
+                let control = ChannelControl {
+                    reset_activity: control & 0x80 != 0,
+                    interleave: control & 0x40 != 0,
+                    toggle: control & 0x20 != 0,
+                    read: control & 0x10 != 0,
+                    channels: match control >> 2 & 0b11 {
+                        0b01 => Channels::A,
+                        0b10 => Channels::B,
+                        _ => Channels::Both,
+                    },
+                    crc: match control & 0b11 {
+                        0b00 => CrcInterval::Disabled,
+                        0b01 => CrcInterval::Byte,
+                        0b10 => CrcInterval::Bytes8,
+                        _ => CrcInterval::Bytes32,
+                    },
+                };
+                if control.reset_activity {
+                    self.activity = [false; 2];
+                }
+                let info = self.info();
+                self.out.push_back(info);
+                State::ChannelAccess {
+                    control,
+                    n: 0,
+                    crc: crc16_update(crc16(&header), &[info]),
+                }
+            }
+            _ => State::Header(header),
+        }
+    }
+
+    /// Accounts for a transferred data byte, queueing the CRC when due.
+    fn channel_byte(&mut self, control: ChannelControl, n: usize, crc: u16, byte: u8) -> State {
+        let mut crc = crc16_update(crc, &[byte]);
+        if control
+            .crc
+            .bytes()
+            .filter(|i| (n + 1).is_multiple_of(*i))
+            .is_some()
+        {
+            self.out.extend((!crc).to_le_bytes());
+            crc = 0;
+        }
+        State::ChannelAccess {
+            control,
+            n: n + 1,
+            crc,
+        }
+    }
+}
+
+impl SimFunction for Ds2406 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => match byte {
+                READ_MEMORY | READ_STATUS | WRITE_STATUS | CHANNEL_ACCESS => {
+                    State::Header(vec![byte])
+                }
+                _ => State::Done,
+            },
+            State::Header(mut header) => {
+                header.push(byte);
+                self.header(header)
+            }
+            State::ChannelAccess { control, n, crc } => {
+                let channels = Self::channels(&control);
+                for bit in 0..8 {
+                    let channel = channels[bit % channels.len()];
+                    self.set_flip_flop(channel, byte >> bit & 1 != 0);
+                }
+                self.channel_byte(control, n, crc, byte)
+            }
+            State::Done => State::Done,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::ChannelAccess { control, n, crc } if control.reads_byte(n) => {
+                let channels = Self::channels(&control);
+                let levels = self.levels();
+                let byte = (0..8).fold(0, |acc, bit| {
+                    acc | (levels[channels[bit % channels.len()]] as u8) << bit
+                });
+                self.state = self.channel_byte(control, n, crc, byte);
+                Some(byte)
+            }
+            State::Done => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn alarm(&self) -> bool {
+        let Some(search) = ConditionalSearch::from_status(self.status[STATUS_CONTROL as usize])
+        else {
+            return false;
+        };
+        let signal = |channel: usize| match search.source {
+            SearchSource::ActivityLatch => self.activity[channel],
+            SearchSource::FlipFlop => self.flip_flops()[channel],
+            SearchSource::Level => self.levels()[channel],
+        };
+        match search.channels {
+            None => false,
+            Some(Channels::A) => signal(0) == search.high,
+            Some(Channels::B) => signal(1) == search.high,
+            Some(Channels::Both) => (signal(0) || signal(1)) == search.high,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,128 @@
+//! Simulated DS2408 8 channel addressable switch.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::{
+    crc::crc16,
+    device::ds2408::{CHANNEL_ACCESS_WRITE, READ_PIO_REGISTERS, REGISTERS, REGISTERS_LEN},
+};
+
+#[derive(Debug, Clone)]
+enum State {
+    Idle,
+    /// Collecting command and address bytes.
+    Header(Vec<u8>),
+    ChannelWrite(Option<u8>),
+    /// Transmitting until reset
+    Done,
+}
+
+pub struct Ds2408 {
+    /// Levels applied externally to the pins, bit n set if PIOn is pulled up.
+    pub inputs: u8,
+    latches: u8,
+    activity: u8,
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Default for Ds2408 {
+    fn default() -> Self {
+        Self {
+            inputs: 0xFF,
+            latches: 0xFF,
+            activity: 0,
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl Ds2408 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output latches, bit n cleared if the transistor of PIOn is on.
+    pub fn latches(&self) -> u8 {
+        self.latches
+    }
+
+    fn levels(&self) -> u8 {
+        self.latches & self.inputs
+    }
+
+    fn registers(&self) -> [u8; REGISTERS_LEN] {
+        // no conditional search, RSTZ as reset input, VCC supplied
+        [
+            self.levels(),
+            self.latches,
+            self.activity,
+            0,
+            0,
+            0x88,
+            0xFF,
+            0xFF,
+        ]
+    }
+
+    /// Starts the register read once the target address is received.
+    fn header(&mut self, header: Vec<u8>) -> State {
+        let [_, lo, hi] = header[..] else {
+            return State::Header(header);
+        };
+        let address = usize::from(u16::from_le_bytes([lo, hi]));
+        let start = address.saturating_sub(REGISTERS.into()).min(REGISTERS_LEN);
+        let mut data = header;
+        data.extend(&self.registers()[start..]);
+        self.out.extend(&data[3..]);
+        self.out.extend((!crc16(&data)).to_le_bytes());
+        State::Done
+    }
+}
+
+impl SimFunction for Ds2408 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => match byte {
+                READ_PIO_REGISTERS => State::Header(vec![byte]),
+                CHANNEL_ACCESS_WRITE => State::ChannelWrite(None),
+                _ => State::Done,
+            },
+            State::Header(mut header) => {
+                header.push(byte);
+                self.header(header)
+            }
+            State::ChannelWrite(None) => State::ChannelWrite(Some(byte)),
+            State::ChannelWrite(Some(value)) if value == !byte => {
+                let before = self.levels();
+                self.latches = value;
+                self.activity |= before ^ self.levels();
+                self.out.push_back(0xAA);
+                self.out.push_back(self.levels());
+                State::ChannelWrite(None)
+            }
+            State::ChannelWrite(Some(_)) | State::Done => State::Done,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::Done => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,99 @@
+//! Simulated DS2413 dual channel addressable switch.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::device::ds2413::{PIO_ACCESS_READ, PIO_ACCESS_WRITE};
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    PioRead,
+    PioWrite(Option<u8>),
+    /// Transmitting until reset
+    Done,
+}
+
+pub struct Ds2413 {
+    /// Levels applied externally to PIOA and PIOB, `true` if pulled up.
+    pub inputs: [bool; 2],
+    latches: [bool; 2],
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Default for Ds2413 {
+    fn default() -> Self {
+        Self {
+            inputs: [true; 2],
+            latches: [true; 2],
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl Ds2413 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output latches of PIOA and PIOB, `false` if the transistor is on.
+    pub fn latches(&self) -> [bool; 2] {
+        self.latches
+    }
+
+    fn pio_status(&self) -> u8 {
+        let pin = |i: usize| self.latches[i] && self.inputs[i];
+        let status = pin(0) as u8
+            | (self.latches[0] as u8) << 1
+            | (pin(1) as u8) << 2
+            | (self.latches[1] as u8) << 3;
+        status | (!status) << 4
+    }
+}
+
+impl SimFunction for Ds2413 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match self.state {
+            State::Idle => match byte {
+                PIO_ACCESS_READ => State::PioRead,
+                PIO_ACCESS_WRITE => State::PioWrite(None),
+                _ => State::Done,
+            },
+            State::PioWrite(None) => State::PioWrite(Some(byte)),
+            State::PioWrite(Some(value)) => {
+                if value != !byte {
+                    // the device stops answering until the next reset
+                    State::Done
+                } else {
+                    self.latches = [value & 0x01 != 0, value & 0x02 != 0];
+                    self.out.push_back(0xAA);
+                    self.out.push_back(self.pio_status());
+                    State::PioWrite(None)
+                }
+            }
+            state => state,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::PioRead => Some(self.pio_status()),
+            State::Done => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
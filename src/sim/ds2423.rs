@@ -0,0 +1,116 @@
+//! Simulated DS2423 RAM with counters.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::{
+    crc::crc16,
+    device::ds2423::{COUNTER_PAGES, PAGE_LEN, READ_MEMORY_COUNTER},
+};
+
+const PAGES: usize = 16;
+
+#[derive(Debug, Clone)]
+enum State {
+    Idle,
+    /// Collecting command and address bytes
+    Header(Vec<u8>),
+    /// Sending pages, continuing at the given address
+    Reading(usize),
+    /// Ignoring the master until reset
+    Done,
+}
+
+pub struct Ds2423 {
+    pub memory: Vec<u8>,
+    /// Counters of the pages 12 to 15, the last two count the inputs A and B.
+    pub counters: [u32; 4],
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Default for Ds2423 {
+    fn default() -> Self {
+        Self {
+            memory: vec![0xFF; PAGES * PAGE_LEN],
+            counters: [0; 4],
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl Ds2423 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the rest of the page at `address` with its counter and CRC,
+    /// which for the first page also covers the command `header`. Returns the
+    /// address of the next page.
+    fn queue_page(&mut self, address: usize, header: &[u8]) -> usize {
+        let page = address / PAGE_LEN;
+        let end = (page + 1) * PAGE_LEN;
+        let counter = match u8::try_from(page) {
+            Ok(page) if COUNTER_PAGES.contains(&page) => {
+                self.counters[usize::from(page - COUNTER_PAGES.start())]
+            }
+            _ => u32::MAX,
+        };
+        let mut data = self.memory[address..end].to_vec();
+        data.extend(counter.to_le_bytes());
+        data.extend([0; 4]);
+        let crc = !crc16(&[header, &data].concat());
+        self.out.extend(data);
+        self.out.extend(crc.to_le_bytes());
+        end
+    }
+}
+
+impl SimFunction for Ds2423 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => match byte {
+                READ_MEMORY_COUNTER => State::Header(vec![byte]),
+                _ => State::Done,
+            },
+            State::Header(mut header) => {
+                header.push(byte);
+                match header[..] {
+                    [_, lo, hi] => {
+                        let address = usize::from(u16::from_le_bytes([lo, hi]));
+                        match address < self.memory.len() {
+                            true => State::Reading(self.queue_page(address, &header)),
+                            false => State::Done,
+                        }
+                    }
+                    _ => State::Header(header),
+                }
+            }
+            state => state,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::Reading(address) if address < self.memory.len() => {
+                self.state = State::Reading(self.queue_page(address, &[]));
+                self.out.pop_front()
+            }
+            State::Reading(_) | State::Done => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,172 @@
+//! Simulated DS2450 quad A/D converter.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::{
+    crc::crc16,
+    device::ds2450::{
+        ChannelStatus, ALARM_PAGE, CONTROL_PAGE, CONVERT, MEMORY_LEN, PAGE_LEN, READ_MEMORY,
+        WRITE_MEMORY,
+    },
+};
+
+#[derive(Debug, Clone)]
+enum State {
+    Idle,
+    /// Collecting command and parameter bytes
+    Header(Vec<u8>),
+    /// Receiving data for the address, `first` if the command is still
+    /// covered by the next CRC
+    WriteMemory {
+        address: usize,
+        first: bool,
+    },
+    /// Transmitting until reset
+    Done,
+}
+
+pub struct Ds2450 {
+    pub memory: [u8; MEMORY_LEN],
+    /// Voltages applied to the inputs.
+    pub inputs: [f32; 4],
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Default for Ds2450 {
+    fn default() -> Self {
+        let mut memory = [0x00; MEMORY_LEN];
+        for channel in 0..4 {
+            // 8 bits, 2.56 V, alarms off, power-on reset
+            memory[usize::from(CONTROL_PAGE) + 2 * channel] = 0x08;
+            memory[usize::from(CONTROL_PAGE) + 2 * channel + 1] = 0x80;
+            memory[usize::from(ALARM_PAGE) + 2 * channel + 1] = 0xFF;
+        }
+        Self {
+            memory,
+            inputs: [0.0; 4],
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl Ds2450 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn status(&self, channel: usize) -> ChannelStatus {
+        let address = usize::from(CONTROL_PAGE) + 2 * channel;
+        ChannelStatus([self.memory[address], self.memory[address + 1]])
+    }
+
+    fn convert(&mut self, mask: u8) {
+        for channel in (0..4).filter(|c| mask >> c & 1 != 0) {
+            let status = self.status(channel);
+            let scale = self.inputs[channel] / status.range().full_scale() * 65536.0;
+            let raw = scale.clamp(0.0, 65535.0) as u16
+                & !u16::MAX
+                    .checked_shr(status.resolution().into())
+                    .unwrap_or(0);
+            let address = usize::from(CONTROL_PAGE) + 2 * channel;
+            self.memory[2 * channel..2 * channel + 2].copy_from_slice(&raw.to_le_bytes());
+
+            let [low, high] =
+                [0, 1].map(|i| self.memory[usize::from(ALARM_PAGE) + 2 * channel + i]);
+            let msb = (raw >> 8) as u8;
+            let flags = ((msb < low) as u8) << 4 | ((msb > high) as u8) << 5;
+            self.memory[address + 1] = self.memory[address + 1] & !0x30 | flags;
+        }
+    }
+
+    fn header(&mut self, header: Vec<u8>) -> State {
+        match header[..] {
+            [READ_MEMORY, lo, _] => {
+                let mut address = usize::from(lo).min(MEMORY_LEN);
+                let mut crc_data = header.clone();
+                while address < MEMORY_LEN {
+                    crc_data.push(self.memory[address]);
+                    self.out.push_back(self.memory[address]);
+                    address += 1;
+                    if address % PAGE_LEN == 0 {
+                        self.out.extend((!crc16(&crc_data)).to_le_bytes());
+                        crc_data.clear();
+                    }
+                }
+                State::Done
+            }
+            [WRITE_MEMORY, lo, _] => State::WriteMemory {
+                address: lo.into(),
+                first: true,
+            },
+            [CONVERT, mask, _] => {
+                self.out.extend((!crc16(&header)).to_le_bytes());
+                self.convert(mask);
+                State::Done
+            }
+            _ => State::Header(header),
+        }
+    }
+}
+
+impl SimFunction for Ds2450 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => match byte {
+                READ_MEMORY | WRITE_MEMORY | CONVERT => State::Header(vec![byte]),
+                _ => State::Done,
+            },
+            State::Header(mut header) => {
+                header.push(byte);
+                self.header(header)
+            }
+            State::WriteMemory { address, first } if address < MEMORY_LEN => {
+                let [lo, hi] = (address as u16).to_le_bytes();
+                let crc = match first {
+                    true => crc16(&[WRITE_MEMORY, lo, hi, byte]),
+                    false => crc16(&[lo, hi, byte]),
+                };
+                // the conversion page is read-only
+                if address >= usize::from(CONTROL_PAGE) {
+                    self.memory[address] = byte;
+                }
+                self.out.extend((!crc).to_le_bytes());
+                self.out.push_back(self.memory[address]);
+                State::WriteMemory {
+                    address: address + 1,
+                    first: false,
+                }
+            }
+            _ => State::Done,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::Done => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn alarm(&self) -> bool {
+        (0..4).map(|c| self.status(c)).any(|status| {
+            status.power_on_reset()
+                || status.alarm_low() && status.alarm_low_enabled()
+                || status.alarm_high() && status.alarm_high_enabled()
+        })
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
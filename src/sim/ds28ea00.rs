@@ -0,0 +1,126 @@
+//! Simulated DS28EA00 including chain mode.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::{thermometer::SimThermometer, SimFunction};
+use crate::device::ds28ea00::{ChainControl, CHAIN, PIO_ACCESS_READ, PIO_ACCESS_WRITE};
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    PioRead,
+    PioWrite(Option<u8>),
+    Chain(Option<u8>),
+}
+
+pub struct Ds28ea00 {
+    pub thermometer: SimThermometer,
+    /// Levels applied externally to PIOA and PIOB, `true` if pulled up.
+    pub inputs: [bool; 2],
+    latches: [bool; 2],
+    chain: Option<ChainControl>,
+    enabled: bool,
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Ds28ea00 {
+    pub fn new(temperature: f32) -> Self {
+        Self {
+            thermometer: SimThermometer::new(temperature),
+            inputs: [true; 2],
+            latches: [true; 2],
+            chain: None,
+            enabled: false,
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+
+    pub fn latches(&self) -> [bool; 2] {
+        self.latches
+    }
+
+    fn pio_status(&self) -> u8 {
+        let pin = |i: usize| self.latches[i] && self.inputs[i];
+        let status = pin(0) as u8
+            | (self.latches[0] as u8) << 1
+            | (pin(1) as u8) << 2
+            | (self.latches[1] as u8) << 3;
+        status | (!status) << 4
+    }
+}
+
+impl SimFunction for Ds28ea00 {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+        self.thermometer.reset();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match self.state {
+            State::Idle => match byte {
+                PIO_ACCESS_READ => State::PioRead,
+                PIO_ACCESS_WRITE => State::PioWrite(None),
+                CHAIN => State::Chain(None),
+                byte => {
+                    self.thermometer.write(byte);
+                    State::Idle
+                }
+            },
+            State::PioRead => State::PioRead,
+            State::PioWrite(None) => State::PioWrite(Some(byte)),
+            State::PioWrite(Some(value)) => {
+                if value == !byte {
+                    self.latches = [value & 0x01 != 0, value & 0x02 != 0];
+                    self.out.push_back(0xAA);
+                    self.out.push_back(self.pio_status());
+                }
+                State::Idle
+            }
+            State::Chain(None) => State::Chain(Some(byte)),
+            State::Chain(Some(ctrl)) => {
+                let cmd = [ChainControl::Off, ChainControl::On, ChainControl::Done]
+                    .into_iter()
+                    .find(|c| u8::from(*c) == ctrl);
+                if let Some(cmd) = cmd.filter(|_| ctrl == !byte) {
+                    self.chain = match cmd {
+                        ChainControl::Off => None,
+                        ChainControl::On => Some(ChainControl::On),
+                        ChainControl::Done => self.chain.map(|_| ChainControl::Done),
+                    };
+                    self.out.push_back(0xAA);
+                }
+                State::Idle
+            }
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        match self.state {
+            State::PioRead => Some(self.pio_status()),
+            _ => self.out.pop_front().or_else(|| self.thermometer.read()),
+        }
+    }
+
+    fn alarm(&self) -> bool {
+        self.thermometer.alarm()
+    }
+
+    fn conditional(&self) -> bool {
+        self.enabled && self.chain == Some(ChainControl::On)
+    }
+
+    fn chain_in(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn chain_out(&self) -> Option<bool> {
+        Some(self.chain == Some(ChainControl::Done))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
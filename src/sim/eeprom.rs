@@ -0,0 +1,124 @@
+//! Simulated DS2433 and DS28EC20 EEPROMs.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::device::eeprom::{
+    Model, COPY_SCRATCHPAD, PAGE_LEN, READ_MEMORY, READ_SCRATCHPAD, WRITE_SCRATCHPAD,
+};
+
+#[derive(Debug, Clone)]
+enum State {
+    Idle,
+    /// Collecting command and address bytes
+    Header(Vec<u8>),
+    ReadMemory(usize),
+    WriteScratchpad,
+    /// Sending the copy result pattern until reset
+    Done(u8),
+}
+
+pub struct Eeprom {
+    pub memory: Vec<u8>,
+    scratchpad: [u8; PAGE_LEN],
+    address: u16,
+    es: u8,
+    state: State,
+    out: VecDeque<u8>,
+}
+
+impl Eeprom {
+    pub fn new(model: Model) -> Self {
+        Self {
+            memory: vec![0xFF; model.size()],
+            scratchpad: [0xFF; PAGE_LEN],
+            address: 0,
+            es: 0,
+            state: State::Idle,
+            out: VecDeque::new(),
+        }
+    }
+}
+
+impl SimFunction for Eeprom {
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.out.clear();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.state = match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => match byte {
+                READ_MEMORY | WRITE_SCRATCHPAD | COPY_SCRATCHPAD => State::Header(vec![byte]),
+                READ_SCRATCHPAD => {
+                    let [lo, hi] = self.address.to_le_bytes();
+                    self.out.extend([lo, hi, self.es]);
+                    let start = usize::from(self.address) % PAGE_LEN;
+                    let end = usize::from(self.es & 0x1F);
+                    self.out.extend(&self.scratchpad[start..=end.max(start)]);
+                    State::Done(0xFF)
+                }
+                _ => State::Done(0xFF),
+            },
+            State::Header(mut header) => {
+                header.push(byte);
+                match header[..] {
+                    [READ_MEMORY, lo, hi] => State::ReadMemory(u16::from_le_bytes([lo, hi]).into()),
+                    [WRITE_SCRATCHPAD, lo, hi] => {
+                        self.address = u16::from_le_bytes([lo, hi]);
+                        // partial flag set until a full byte arrives
+                        self.es = (self.address % PAGE_LEN as u16) as u8 | 0x20;
+                        State::WriteScratchpad
+                    }
+                    [COPY_SCRATCHPAD, lo, hi, es] => {
+                        let authorized = u16::from_le_bytes([lo, hi]) == self.address
+                            && es == self.es
+                            && es & 0x20 == 0;
+                        if !authorized {
+                            State::Done(0xFF)
+                        } else {
+                            let page = usize::from(self.address) / PAGE_LEN * PAGE_LEN;
+                            let start = usize::from(self.address) % PAGE_LEN;
+                            let end = usize::from(es & 0x1F);
+                            if let Some(mem) = self.memory.get_mut(page + start..=page + end) {
+                                mem.copy_from_slice(&self.scratchpad[start..=end]);
+                            }
+                            self.es |= 0x80;
+                            State::Done(0xAA)
+                        }
+                    }
+                    _ => State::Header(header),
+                }
+            }
+            State::WriteScratchpad => {
+                let offset = (self.es & 0x1F) as usize;
+                let first = self.es & 0x20 != 0;
+                let offset = if first { offset } else { offset + 1 };
+                if offset < PAGE_LEN {
+                    self.scratchpad[offset] = byte;
+                    self.es = offset as u8;
+                }
+                State::WriteScratchpad
+            }
+            state => state,
+        };
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.out.pop_front() {
+            return Some(byte);
+        }
+        match self.state {
+            State::ReadMemory(address) => {
+                self.state = State::ReadMemory(address + 1);
+                Some(self.memory.get(address).copied().unwrap_or(0xFF))
+            }
+            State::Done(pattern) => Some(pattern),
+            _ => None,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
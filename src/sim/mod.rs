@@ -0,0 +1,552 @@
+//! Simulated w1 core for testing drivers without hardware.
+//!
+//! The simulation works on single time slots: every slot is the wired-AND of
+//! the bit written by the master and the bits driven by all slaves. Slaves
+//! implement the ROM function layer here, device specific behaviour is
+//! provided by a [`SimFunction`] operating on whole bytes.
+
+use std::{any::Any, collections::VecDeque, mem};
+
+use crate::{
+    batch::Batch,
+    device::{
+        ds28ea00::CONDITIONAL_READ_ROM,
+        rom::{ALARM_SEARCH, MATCH_ROM, READ_ROM, SEARCH_ROM, SKIP_ROM},
+    },
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        message::{EventKind, W1NetlinkMessage},
+    },
+    transport::{Error, Transport},
+};
+
+pub mod ds2406;
+pub mod ds2408;
+pub mod ds2413;
+pub mod ds2423;
+pub mod ds2450;
+pub mod ds28ea00;
+pub mod eeprom;
+pub mod thermometer;
+
+/// Error status used by the kernel for unknown masters and slaves.
+const ENODEV: u8 = 19;
+/// Error status used by the kernel for malformed requests.
+const EINVAL: u8 = 22;
+/// Error status of a reset within a slave command that saw no presence pulse.
+const NO_PRESENCE: u8 = 1;
+
+/// Function layer of a simulated slave, reached after a ROM command selected it.
+pub trait SimFunction: Any + Send {
+    /// Called on every bus reset.
+    fn reset(&mut self) {}
+
+    /// Called for every byte the master writes while the device is receiving.
+    fn write(&mut self, byte: u8);
+
+    /// Called at the start of every byte. Returns the byte to transmit if the
+    /// device is sending, otherwise the device receives the next byte.
+    fn read(&mut self) -> Option<u8> {
+        None
+    }
+
+    /// Whether the device takes part in an alarm search.
+    fn alarm(&self) -> bool {
+        false
+    }
+
+    /// Whether the device answers a Conditional Read ROM.
+    fn conditional(&self) -> bool {
+        false
+    }
+
+    /// Level of the chain enable input, `true` if enabled by the predecessor.
+    fn chain_in(&mut self, _enabled: bool) {}
+
+    /// Chain output enabling the next device, `None` if not part of a chain.
+    fn chain_out(&self) -> Option<bool> {
+        None
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SlaveState {
+    /// Waiting for a ROM command
+    RomCommand,
+    /// Deselected until the next reset
+    Inactive,
+    ReadRom {
+        bit: usize,
+    },
+    MatchRom {
+        bit: usize,
+    },
+    /// Step 0 sends the id bit, step 1 its complement, step 2 reads the direction
+    Search {
+        bit: usize,
+        step: u8,
+    },
+    Function,
+}
+
+struct SimSlave {
+    id: SlaveId,
+    function: Box<dyn SimFunction>,
+    /// Whether the device is on the wire, it may stay registered without.
+    connected: bool,
+    state: SlaveState,
+    shift: u8,
+    bits: u8,
+    tx: Option<u8>,
+}
+
+impl SimSlave {
+    fn id_bit(&self, bit: usize) -> bool {
+        self.id.bytes()[bit / 8] >> (bit % 8) & 1 == 1
+    }
+
+    fn reset(&mut self) {
+        self.state = SlaveState::RomCommand;
+        self.shift = 0;
+        self.bits = 0;
+        self.tx = None;
+        self.function.reset();
+    }
+
+    /// Level the slave puts on the bus for the current slot.
+    fn drive(&mut self) -> bool {
+        match self.state {
+            SlaveState::ReadRom { bit } => self.id_bit(bit),
+            SlaveState::Search { bit, step: 0 } => self.id_bit(bit),
+            SlaveState::Search { bit, step: 1 } => !self.id_bit(bit),
+            SlaveState::Function => {
+                if self.bits == 0 {
+                    self.tx = self.function.read();
+                }
+                self.tx.map(|tx| tx >> self.bits & 1 == 1).unwrap_or(true)
+            }
+            _ => true,
+        }
+    }
+
+    /// Samples the resulting bus level of the current slot.
+    fn sample(&mut self, level: bool) {
+        self.state = match self.state {
+            SlaveState::RomCommand => match self.shift_in(level) {
+                Some(READ_ROM) => SlaveState::ReadRom { bit: 0 },
+                Some(MATCH_ROM) => SlaveState::MatchRom { bit: 0 },
+                Some(SKIP_ROM) => SlaveState::Function,
+                Some(SEARCH_ROM) => SlaveState::Search { bit: 0, step: 0 },
+                Some(ALARM_SEARCH) if self.function.alarm() => {
+                    SlaveState::Search { bit: 0, step: 0 }
+                }
+                Some(CONDITIONAL_READ_ROM) if self.function.conditional() => {
+                    SlaveState::ReadRom { bit: 0 }
+                }
+                Some(_) => SlaveState::Inactive,
+                None => SlaveState::RomCommand,
+            },
+            SlaveState::Inactive => SlaveState::Inactive,
+            SlaveState::ReadRom { bit: 63 } => SlaveState::Function,
+            SlaveState::ReadRom { bit } => SlaveState::ReadRom { bit: bit + 1 },
+            SlaveState::MatchRom { bit } if level != self.id_bit(bit) => SlaveState::Inactive,
+            SlaveState::MatchRom { bit: 63 } => SlaveState::Function,
+            SlaveState::MatchRom { bit } => SlaveState::MatchRom { bit: bit + 1 },
+            SlaveState::Search { bit, step: 2 } if level != self.id_bit(bit) => {
+                SlaveState::Inactive
+            }
+            SlaveState::Search { bit: 63, step: 2 } => SlaveState::Function,
+            SlaveState::Search { bit, step: 2 } => SlaveState::Search {
+                bit: bit + 1,
+                step: 0,
+            },
+            SlaveState::Search { bit, step } => SlaveState::Search {
+                bit,
+                step: step + 1,
+            },
+            SlaveState::Function => {
+                match self.tx {
+                    Some(_) => {
+                        self.bits += 1;
+                        if self.bits == 8 {
+                            self.bits = 0;
+                            self.tx = None;
+                        }
+                    }
+                    None => {
+                        if let Some(byte) = self.shift_in(level) {
+                            self.function.write(byte);
+                        }
+                    }
+                }
+                SlaveState::Function
+            }
+        };
+    }
+
+    fn shift_in(&mut self, level: bool) -> Option<u8> {
+        self.shift |= (level as u8) << self.bits;
+        self.bits += 1;
+        if self.bits < 8 {
+            return None;
+        }
+        let byte = self.shift;
+        self.shift = 0;
+        self.bits = 0;
+        Some(byte)
+    }
+}
+
+/// A simulated bus master with its attached slaves in physical order.
+pub struct SimMaster {
+    id: u32,
+    slaves: Vec<SimSlave>,
+}
+
+impl SimMaster {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Resets the bus and returns whether any slave signalled presence.
+    pub fn reset(&mut self) -> bool {
+        let mut enabled = true;
+        for slave in self.slaves.iter_mut().filter(|s| s.connected) {
+            slave.reset();
+            slave.function.chain_in(enabled);
+            if let Some(out) = slave.function.chain_out() {
+                enabled = out;
+            }
+        }
+        self.slaves.iter().any(|s| s.connected)
+    }
+
+    /// Resets the bus and addresses `id` with a Match ROM. Returns whether any
+    /// slave signalled presence.
+    fn select(&mut self, id: SlaveId) -> bool {
+        if !self.reset() {
+            return false;
+        }
+        self.write(&[MATCH_ROM]);
+        self.write(&id.bytes());
+        true
+    }
+
+    /// Runs a single time slot.
+    pub fn touch_bit(&mut self, bit: bool) -> bool {
+        let level = self
+            .slaves
+            .iter_mut()
+            .filter(|s| s.connected)
+            .fold(bit, |level, slave| slave.drive() & level);
+        for slave in self.slaves.iter_mut().filter(|s| s.connected) {
+            slave.sample(level);
+        }
+        level
+    }
+
+    pub fn touch_byte(&mut self, byte: u8) -> u8 {
+        (0..8).fold(0, |acc, i| {
+            acc | (self.touch_bit(byte >> i & 1 == 1) as u8) << i
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.touch_byte(*byte);
+        }
+    }
+
+    pub fn read(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.touch_byte(0xFF)).collect()
+    }
+
+    /// Searches the bus like the kernel does, using read-read-write triplets.
+    pub fn search(&mut self, alarm: bool) -> Vec<SlaveId> {
+        let mut found = Vec::new();
+        let mut last = [0u8; 8];
+        let mut last_discrepancy = None;
+        loop {
+            if !self.reset() {
+                break;
+            }
+            self.write(&[if alarm { ALARM_SEARCH } else { SEARCH_ROM }]);
+
+            let mut id = [0u8; 8];
+            let mut last_zero = None;
+            let mut complete = true;
+            for bit in 0..64 {
+                let id_bit = self.touch_bit(true);
+                let cmp_bit = self.touch_bit(true);
+                let direction = match (id_bit, cmp_bit) {
+                    (true, true) => {
+                        complete = false;
+                        break;
+                    }
+                    (true, false) => true,
+                    (false, true) => false,
+                    (false, false) => {
+                        let direction = match last_discrepancy {
+                            Some(d) if bit < d => last[bit / 8] >> (bit % 8) & 1 == 1,
+                            Some(d) => bit == d,
+                            None => false,
+                        };
+                        if !direction {
+                            last_zero = Some(bit);
+                        }
+                        direction
+                    }
+                };
+                self.touch_bit(direction);
+                id[bit / 8] |= (direction as u8) << (bit % 8);
+            }
+            if !complete {
+                break;
+            }
+
+            let id = SlaveId::new(id);
+            if id.is_valid() {
+                found.push(id);
+            }
+            last = id.bytes();
+            if last_zero.is_none() || last_zero == last_discrepancy {
+                break;
+            }
+            last_discrepancy = last_zero;
+        }
+        found
+    }
+
+    /// Runs `cmd` and returns a reply if the command produces data.
+    fn command(&mut self, cmd: W1NetlinkCommand) -> Option<W1NetlinkCommand> {
+        match cmd {
+            W1NetlinkCommand::Write(data) => {
+                self.write(&data);
+                None
+            }
+            W1NetlinkCommand::Read(buf) => {
+                let len = buf.map(|buf| buf.len()).unwrap_or_default();
+                Some(W1NetlinkCommand::Read(Some(self.read(len))))
+            }
+            W1NetlinkCommand::Search(_) => Some(W1NetlinkCommand::Search(Some(self.search(false)))),
+            W1NetlinkCommand::AlarmSearch(_) => {
+                Some(W1NetlinkCommand::AlarmSearch(Some(self.search(true))))
+            }
+            W1NetlinkCommand::Touch(data) => {
+                let data = data.into_iter().map(|byte| self.touch_byte(byte)).collect();
+                Some(W1NetlinkCommand::Touch(data))
+            }
+            W1NetlinkCommand::Reset => {
+                self.reset();
+                None
+            }
+            W1NetlinkCommand::ListSlaves(_) => {
+                let ids = self.slaves.iter().map(|s| s.id).collect();
+                Some(W1NetlinkCommand::ListSlaves(Some(ids)))
+            }
+            // registering slaves is handled by the bus
+            W1NetlinkCommand::SlaveAdd(_) | W1NetlinkCommand::SlaveRemove(_) => None,
+        }
+    }
+}
+
+/// Simulated w1 core with any number of masters.
+#[derive(Default)]
+pub struct SimBus {
+    masters: Vec<SimMaster>,
+    events: VecDeque<W1NetlinkMessage>,
+}
+
+impl SimBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new master and returns its id. Ids start at 1 like in the kernel.
+    pub fn add_master(&mut self) -> u32 {
+        let id = self.masters.last().map(|m| m.id + 1).unwrap_or(1);
+        self.masters.push(SimMaster {
+            id,
+            slaves: Vec::new(),
+        });
+        self.events.push_back(W1NetlinkMessage::MasterEvent {
+            kind: EventKind::Add,
+            target: id,
+        });
+        id
+    }
+
+    pub fn master_mut(&mut self, id: u32) -> Option<&mut SimMaster> {
+        self.masters.iter_mut().find(|m| m.id == id)
+    }
+
+    /// Attaches a device at the end of the bus of `master`.
+    ///
+    /// Panics if the master does not exist.
+    pub fn attach(&mut self, master: u32, id: SlaveId, function: impl SimFunction) {
+        let master = self.master_mut(master).expect("unknown master");
+        master.slaves.push(SimSlave {
+            id,
+            function: Box::new(function),
+            connected: true,
+            state: SlaveState::Inactive,
+            shift: 0,
+            bits: 0,
+            tx: None,
+        });
+        self.events.push_back(W1NetlinkMessage::SlaveEvent {
+            kind: EventKind::Add,
+            target: id.into(),
+        });
+    }
+
+    /// Removes a device from whichever bus it is attached to.
+    pub fn detach(&mut self, id: SlaveId) -> bool {
+        for master in &mut self.masters {
+            if let Some(pos) = master.slaves.iter().position(|s| s.id == id) {
+                master.slaves.remove(pos);
+                self.events.push_back(W1NetlinkMessage::SlaveEvent {
+                    kind: EventKind::Remove,
+                    target: id.into(),
+                });
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Takes a device off the wire or puts it back without the w1 core
+    /// noticing, like unplugging it between two searches. The device stays
+    /// registered, but commands to it see no presence pulse.
+    pub fn set_connected(&mut self, id: SlaveId, connected: bool) -> bool {
+        let Some(slave) = self
+            .masters
+            .iter_mut()
+            .flat_map(|m| m.slaves.iter_mut())
+            .find(|s| s.id == id)
+        else {
+            return false;
+        };
+        slave.connected = connected;
+        slave.state = SlaveState::Inactive;
+        true
+    }
+
+    /// Gives access to the model of a device, e.g. to change its readings.
+    pub fn device_mut<T: SimFunction>(&mut self, id: SlaveId) -> Option<&mut T> {
+        self.masters
+            .iter_mut()
+            .flat_map(|m| m.slaves.iter_mut())
+            .find(|s| s.id == id)
+            .and_then(|s| s.function.as_any_mut().downcast_mut())
+    }
+
+    /// Takes the next hotplug event, as the kernel would multicast it.
+    pub fn next_event(&mut self) -> Option<W1NetlinkMessage> {
+        self.events.pop_front()
+    }
+}
+
+impl Transport for SimBus {
+    /// Splits `msg` the way the client does and processes the parts in order.
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        let mut batch = Batch::new(0, [msg])?;
+        let mut replies = Vec::new();
+        for cmsg in mem::take(&mut batch.requests) {
+            for msg in cmsg.payload {
+                replies.extend(self.process(msg)?);
+            }
+        }
+        Ok(batch.reassemble(replies))
+    }
+}
+
+impl SimBus {
+    fn process(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        match msg {
+            W1NetlinkMessage::ListMasters(_) => {
+                let ids = self.masters.iter().map(|m| m.id).collect();
+                Ok(vec![W1NetlinkMessage::ListMasters(Some(ids))])
+            }
+            W1NetlinkMessage::MasterCommand { target, cmds } => {
+                self.master_mut(target).ok_or(Error::Status(ENODEV))?;
+                let mut replies = Vec::new();
+                for cmd in cmds {
+                    let reply = match cmd {
+                        W1NetlinkCommand::SlaveAdd(id) => {
+                            self.slave_add(target, id)?;
+                            None
+                        }
+                        W1NetlinkCommand::SlaveRemove(id) => {
+                            self.slave_remove(target, id)?;
+                            None
+                        }
+                        cmd => self.master_mut(target).unwrap().command(cmd),
+                    };
+                    replies.extend(reply.map(|reply| W1NetlinkMessage::MasterCommand {
+                        target,
+                        cmds: vec![reply],
+                    }));
+                }
+                Ok(replies)
+            }
+            W1NetlinkMessage::SlaveCommand { target, cmds } => {
+                let id = SlaveId::from(target);
+                let master = self
+                    .masters
+                    .iter_mut()
+                    .find(|m| m.slaves.iter().any(|s| s.id == id))
+                    .ok_or(Error::Status(ENODEV))?;
+                // like the kernel, select the slave before the first command
+                if !master.select(id) {
+                    return Err(Error::Status(ENODEV));
+                }
+                let mut replies = Vec::new();
+                for cmd in cmds {
+                    let reply = match cmd {
+                        W1NetlinkCommand::Reset => {
+                            if !master.select(id) {
+                                return Err(Error::Status(NO_PRESENCE));
+                            }
+                            None
+                        }
+                        W1NetlinkCommand::SlaveAdd(_) | W1NetlinkCommand::SlaveRemove(_) => {
+                            return Err(Error::Status(EINVAL))
+                        }
+                        cmd => master.command(cmd),
+                    };
+                    replies.extend(reply.map(|reply| W1NetlinkMessage::SlaveCommand {
+                        target,
+                        cmds: vec![reply],
+                    }));
+                }
+                Ok(replies)
+            }
+            W1NetlinkMessage::MasterEvent { .. } | W1NetlinkMessage::SlaveEvent { .. } => {
+                Err(Error::Status(EINVAL))
+            }
+        }
+    }
+
+    /// The kernel refuses to add a slave twice. The simulation only knows
+    /// attached devices and cannot register others.
+    fn slave_add(&mut self, master: u32, id: SlaveId) -> Result<(), Error> {
+        let master = self.master_mut(master).ok_or(Error::Status(ENODEV))?;
+        match master.slaves.iter().any(|s| s.id == id) {
+            true => Err(Error::Status(EINVAL)),
+            false => Err(Error::Status(ENODEV)),
+        }
+    }
+
+    /// Detaches a slave of `master` like [`detach`](Self::detach).
+    fn slave_remove(&mut self, master: u32, id: SlaveId) -> Result<(), Error> {
+        let master = self.master_mut(master).ok_or(Error::Status(ENODEV))?;
+        if !master.slaves.iter().any(|s| s.id == id) {
+            return Err(Error::Status(EINVAL));
+        }
+        self.detach(id);
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! Thermometer function commands shared by the simulated DS18B20 style devices.
+
+use std::{any::Any, collections::VecDeque};
+
+use super::SimFunction;
+use crate::{
+    crc::crc8,
+    device::thermometer::{
+        Resolution, Temperature, CONVERT_T, COPY_SCRATCHPAD, READ_POWER_SUPPLY, READ_SCRATCHPAD,
+        RECALL_EEPROM, WRITE_SCRATCHPAD,
+    },
+};
+
+pub struct SimThermometer {
+    /// Temperature measured by the next conversion.
+    pub temperature: f32,
+    scratchpad: [u8; 8],
+    /// TH, TL and configuration
+    eeprom: [u8; 3],
+    /// Index of the next scratchpad byte received by Write Scratchpad
+    writing: Option<usize>,
+    out: VecDeque<u8>,
+}
+
+impl SimThermometer {
+    pub fn new(temperature: f32) -> Self {
+        Self {
+            temperature,
+            // power-on state: 85 °C
+            scratchpad: [0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10],
+            eeprom: [0x4B, 0x46, 0x7F],
+            writing: None,
+            out: VecDeque::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.writing = None;
+        self.out.clear();
+    }
+
+    /// Handles a byte written by the master, returns `false` if it is not a
+    /// thermometer command.
+    pub fn write(&mut self, byte: u8) -> bool {
+        if let Some(i) = self.writing {
+            self.scratchpad[2 + i] = byte;
+            self.writing = Some(i + 1).filter(|i| *i < 3);
+            return true;
+        }
+        match byte {
+            CONVERT_T => self.convert(),
+            READ_SCRATCHPAD => {
+                self.out.extend(self.scratchpad);
+                self.out.push_back(crc8(&self.scratchpad));
+            }
+            WRITE_SCRATCHPAD => self.writing = Some(0),
+            COPY_SCRATCHPAD => self.eeprom.copy_from_slice(&self.scratchpad[2..5]),
+            RECALL_EEPROM => self.scratchpad[2..5].copy_from_slice(&self.eeprom),
+            // externally powered
+            READ_POWER_SUPPLY => self.out.push_back(0xFF),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn read(&mut self) -> Option<u8> {
+        self.out.pop_front()
+    }
+
+    /// Alarm flag as evaluated after the last conversion.
+    pub fn alarm(&self) -> bool {
+        let t = self.converted().raw() >> 4;
+        t >= i16::from(self.scratchpad[2] as i8) || t <= i16::from(self.scratchpad[3] as i8)
+    }
+
+    pub fn converted(&self) -> Temperature {
+        Temperature::from_raw(i16::from_le_bytes([self.scratchpad[0], self.scratchpad[1]]))
+    }
+
+    fn convert(&mut self) {
+        // undefined low bits read as zero with reduced resolution
+        let mask = match Resolution::from_config(self.scratchpad[4]) {
+            Resolution::Bits9 => !0b111,
+            Resolution::Bits10 => !0b11,
+            Resolution::Bits11 => !0b1,
+            Resolution::Bits12 => !0,
+        };
+        let raw = Temperature::from_celsius(self.temperature).raw() & mask;
+        self.scratchpad[..2].copy_from_slice(&raw.to_le_bytes());
+    }
+}
+
+/// Simulated DS18B20 or DS1822, depending on the family code it is attached with.
+pub struct Ds18b20 {
+    pub thermometer: SimThermometer,
+}
+
+impl Ds18b20 {
+    pub fn new(temperature: f32) -> Self {
+        Self {
+            thermometer: SimThermometer::new(temperature),
+        }
+    }
+}
+
+impl SimFunction for Ds18b20 {
+    fn reset(&mut self) {
+        self.thermometer.reset();
+    }
+
+    fn write(&mut self, byte: u8) {
+        self.thermometer.write(byte);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.thermometer.read()
+    }
+
+    fn alarm(&self) -> bool {
+        self.thermometer.alarm()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,376 @@
+//! Outputs for periodic readings, e.g. for long-term logging.
+//!
+//! A [`Sink`] receives the readings of every poll. [`LineProtocol`] writes
+//! InfluxDB line protocol to a file or stdout, [`InfluxHttp`] posts it to an
+//! InfluxDB write endpoint and [`Csv`] writes CSV files rotated by size.
+//! Readings are keyed by ROM, sinks given [`Aliases`] add the human name.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Stdout, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{device::family_name, proto::command::SlaveId, reading::Reading};
+
+/// Human names of devices.
+pub type Aliases = BTreeMap<SlaveId, String>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Write rejected: {0}")]
+    Http(String),
+}
+
+pub trait Sink {
+    /// Writes the readings of one poll.
+    fn write(&mut self, readings: &[Reading]) -> Result<(), Error>;
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn write(&mut self, readings: &[Reading]) -> Result<(), Error> {
+        (**self).write(readings)
+    }
+}
+
+fn family(id: SlaveId) -> String {
+    match family_name(id.family()) {
+        Some(name) => name.to_string(),
+        None => format!("{:02x}", id.family()),
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Escapes a tag value, the measurement names need no escaping.
+fn tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Formats readings as line protocol with nanosecond timestamps, e.g.
+/// `temperature,alias=Kitchen,family=DS18B20,rom=28-0000056c3a1f value=21.5 1760788800000000000`.
+fn lines(readings: &[Reading], aliases: &Aliases) -> String {
+    let mut out = String::new();
+    for reading in readings {
+        // tags sorted by key, as recommended for write performance
+        out.push_str(reading.quantity.name());
+        if let Some(alias) = aliases.get(&reading.rom) {
+            write!(out, ",alias={}", tag(alias)).unwrap();
+        }
+        if let Some(channel) = reading.channel {
+            write!(out, ",channel={channel}").unwrap();
+        }
+        writeln!(
+            out,
+            ",family={},rom={} value={} {}",
+            tag(&family(reading.rom)),
+            reading.rom,
+            reading.value,
+            unix_nanos(reading.timestamp)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Writes line protocol to any writer.
+#[derive(Debug)]
+pub struct LineProtocol<W> {
+    writer: W,
+    aliases: Aliases,
+}
+
+impl<W: Write> LineProtocol<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            aliases: Aliases::new(),
+        }
+    }
+
+    pub fn with_aliases(self, aliases: Aliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl LineProtocol<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl LineProtocol<File> {
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> Sink for LineProtocol<W> {
+    fn write(&mut self, readings: &[Reading]) -> Result<(), Error> {
+        self.writer
+            .write_all(lines(readings, &self.aliases).as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Posts line protocol to the write endpoint of an InfluxDB on the local
+/// network, over plain HTTP.
+#[derive(Debug, Clone)]
+pub struct InfluxHttp {
+    addr: String,
+    path: String,
+    token: Option<String>,
+    timeout: Duration,
+    aliases: Aliases,
+}
+
+impl InfluxHttp {
+    /// Posts to `path` on `addr`, e.g. `localhost:8086` and
+    /// `/api/v2/write?org=home&bucket=w1&precision=ns`.
+    pub fn new(addr: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            path: path.into(),
+            token: None,
+            timeout: Duration::from_secs(10),
+            aliases: Aliases::new(),
+        }
+    }
+
+    /// API token sent in the `Authorization` header.
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        Self {
+            token: Some(token.into()),
+            ..self
+        }
+    }
+
+    /// Limit for connecting and for each read and write.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn with_aliases(self, aliases: Aliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "address resolved to nothing");
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last = err,
+            }
+        }
+        Err(last)
+    }
+}
+
+impl Sink for InfluxHttp {
+    fn write(&mut self, readings: &[Reading]) -> Result<(), Error> {
+        if readings.is_empty() {
+            return Ok(());
+        }
+        let body = lines(readings, &self.aliases);
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n",
+            self.path,
+            self.addr,
+            body.len()
+        );
+        if let Some(token) = &self.token {
+            write!(request, "Authorization: Token {token}\r\n").unwrap();
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+        stream.write_all(request.as_bytes())?;
+
+        let mut status = String::new();
+        BufReader::new(&stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(Error::Http(status.trim_end().to_string())),
+        }
+    }
+}
+
+const CSV_HEADER: &str = "timestamp,rom,alias,quantity,channel,value,unit\n";
+
+/// Quotes a CSV field if needed.
+fn field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Formats a time as RFC 3339 in UTC with milliseconds.
+fn rfc3339(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    // civil date from days since the epoch, after Howard Hinnant
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let secs = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+/// Writes CSV files with a header row. Once the file reaches the maximum
+/// size it is renamed to `<path>.1`, older files move up to `<path>.<n>` and
+/// the oldest beyond the maximum count are deleted.
+#[derive(Debug)]
+pub struct Csv {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    aliases: Aliases,
+    file: Option<File>,
+    size: u64,
+}
+
+impl Csv {
+    /// Writes to `path`, rotating at 10 MiB and keeping 5 old files.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: 10 << 20,
+            max_files: 5,
+            aliases: Aliases::new(),
+            file: None,
+            size: 0,
+        }
+    }
+
+    /// Size in bytes after which the file is rotated.
+    pub fn with_max_size(self, max_size: u64) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Number of rotated files to keep, 0 deletes the file when rotating.
+    pub fn with_max_files(self, max_files: usize) -> Self {
+        Self { max_files, ..self }
+    }
+
+    pub fn with_aliases(self, aliases: Aliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn open(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            self.reopen()?;
+        }
+        // also rotates a full file left behind by an earlier run
+        if self.size >= self.max_size {
+            self.rotate()?;
+            self.reopen()?;
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = file.metadata()?.len();
+        if self.size == 0 {
+            file.write_all(CSV_HEADER.as_bytes())?;
+            self.size = CSV_HEADER.len() as u64;
+        }
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+impl Sink for Csv {
+    fn write(&mut self, readings: &[Reading]) -> Result<(), Error> {
+        if readings.is_empty() {
+            return Ok(());
+        }
+        let mut rows = String::new();
+        for reading in readings {
+            let alias = self.aliases.get(&reading.rom).map(String::as_str);
+            writeln!(
+                rows,
+                "{},{},{},{},{},{},{}",
+                rfc3339(reading.timestamp),
+                reading.rom,
+                field(alias.unwrap_or_default()),
+                reading.quantity.name(),
+                reading.channel.unwrap_or_default(),
+                reading.value,
+                reading.quantity.unit()
+            )
+            .unwrap();
+        }
+        self.open()?.write_all(rows.as_bytes())?;
+        self.size += rows.len() as u64;
+        Ok(())
+    }
+}
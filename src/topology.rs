@@ -0,0 +1,266 @@
+//! Live model of the masters and slaves known to the w1 core.
+//!
+//! [`Topology::seed`] asks the w1 core for its masters and their registered
+//! slaves, [`Topology::handle_event`] follows the hotplug events of a
+//! subscribed client. Events get lost when the socket buffer overflows, which
+//! the next receive reports as `ENOBUFS`, so [`Topology::reconcile`] compares
+//! the model with a full search of every bus from time to time.
+//!
+//! Every method changing the model returns the [`Change`]s it made, which are
+//! also sent to all [subscribers](Topology::subscribe).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    proto::{
+        command::SlaveId,
+        message::{EventKind, W1NetlinkMessage},
+    },
+    transport::{Error, Transport},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Change {
+    MasterAdded(u32),
+    MasterRemoved(u32),
+    SlaveAdded { master: u32, id: SlaveId },
+    SlaveRemoved { master: u32, id: SlaveId },
+}
+
+/// The masters and their slaves at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    masters: BTreeMap<u32, BTreeSet<SlaveId>>,
+}
+
+impl Snapshot {
+    pub fn masters(&self) -> impl Iterator<Item = u32> + '_ {
+        self.masters.keys().copied()
+    }
+
+    /// Slaves of `master` in ROM order.
+    pub fn slaves(&self, master: u32) -> impl Iterator<Item = SlaveId> + '_ {
+        self.masters
+            .get(&master)
+            .into_iter()
+            .flat_map(|slaves| slaves.iter().copied())
+    }
+
+    pub fn master_of(&self, id: SlaveId) -> Option<u32> {
+        self.masters
+            .iter()
+            .find(|(_, slaves)| slaves.contains(&id))
+            .map(|(master, _)| *master)
+    }
+
+    /// Changes turning `self` into `new`. Slaves are added after their master
+    /// and removed before it.
+    fn diff(&self, new: &Snapshot) -> Vec<Change> {
+        let empty = BTreeSet::new();
+        let mut changes = Vec::new();
+        for master in new.masters.keys().filter(|m| !self.masters.contains_key(m)) {
+            changes.push(Change::MasterAdded(*master));
+        }
+        for (master, slaves) in &new.masters {
+            let old = self.masters.get(master).unwrap_or(&empty);
+            for id in slaves.difference(old) {
+                changes.push(Change::SlaveAdded {
+                    master: *master,
+                    id: *id,
+                });
+            }
+        }
+        for (master, slaves) in &self.masters {
+            let new = new.masters.get(master).unwrap_or(&empty);
+            for id in slaves.difference(new) {
+                changes.push(Change::SlaveRemoved {
+                    master: *master,
+                    id: *id,
+                });
+            }
+        }
+        for master in self.masters.keys().filter(|m| !new.masters.contains_key(m)) {
+            changes.push(Change::MasterRemoved(*master));
+        }
+        changes
+    }
+}
+
+#[derive(Debug)]
+pub struct Topology {
+    current: Snapshot,
+    reconcile_interval: Duration,
+    last_reconcile: Option<Instant>,
+    /// Set when events may have been missed.
+    stale: bool,
+    subscribers: Vec<Sender<Change>>,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self {
+            current: Snapshot::default(),
+            reconcile_interval: Duration::from_secs(600),
+            last_reconcile: None,
+            stale: false,
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time between full searches, 10 minutes by default.
+    pub fn with_reconcile_interval(self, reconcile_interval: Duration) -> Self {
+        Self {
+            reconcile_interval,
+            ..self
+        }
+    }
+
+    /// Receives every change from now on.
+    pub fn subscribe(&mut self) -> Receiver<Change> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.current.clone()
+    }
+
+    fn update(&mut self, new: Snapshot) -> Vec<Change> {
+        let changes = self.current.diff(&new);
+        self.current = new;
+        self.notify(&changes);
+        changes
+    }
+
+    fn notify(&mut self, changes: &[Change]) {
+        self.subscribers.retain(|subscriber| {
+            changes
+                .iter()
+                .all(|change| subscriber.send(*change).is_ok())
+        });
+    }
+
+    /// Replaces the model with the masters and slaves registered in the w1
+    /// core, without searching the buses.
+    pub fn seed(&mut self, t: &mut impl Transport) -> Result<Vec<Change>, Error> {
+        let mut new = Snapshot::default();
+        for master in t.list_masters()? {
+            let slaves = t.list_slaves(master)?;
+            new.masters.insert(master, slaves.into_iter().collect());
+        }
+        Ok(self.update(new))
+    }
+
+    /// Applies a hotplug event. Slave events do not tell the master, which
+    /// is looked up in the w1 core.
+    pub fn handle_event(
+        &mut self,
+        t: &mut impl Transport,
+        msg: &W1NetlinkMessage,
+    ) -> Result<Vec<Change>, Error> {
+        let mut new = self.current.clone();
+        match *msg {
+            W1NetlinkMessage::MasterEvent {
+                kind: EventKind::Add,
+                target,
+            } => {
+                new.masters.entry(target).or_default();
+            }
+            W1NetlinkMessage::MasterEvent {
+                kind: EventKind::Remove,
+                target,
+            } => {
+                new.masters.remove(&target);
+            }
+            W1NetlinkMessage::SlaveEvent {
+                kind: EventKind::Add,
+                target,
+            } => {
+                let id = SlaveId::from(target);
+                if self.current.master_of(id).is_none() {
+                    match locate(t, id)? {
+                        Some(master) => {
+                            new.masters.entry(master).or_default().insert(id);
+                        }
+                        // gone again already, or on a master not known yet
+                        None => self.stale = true,
+                    }
+                }
+            }
+            W1NetlinkMessage::SlaveEvent {
+                kind: EventKind::Remove,
+                target,
+            } => {
+                let id = SlaveId::from(target);
+                for slaves in new.masters.values_mut() {
+                    slaves.remove(&id);
+                }
+            }
+            _ => {}
+        }
+        Ok(self.update(new))
+    }
+
+    /// Marks the model as possibly outdated, e.g. after
+    /// [`is_overflow`](crate::transport::is_overflow), so the next
+    /// [`reconcile`](Self::reconcile) is due right away.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Whether a full search is due at `now`.
+    pub fn needs_reconcile(&self, now: Instant) -> bool {
+        match self.last_reconcile {
+            Some(last) => self.stale || now >= last + self.reconcile_interval,
+            None => true,
+        }
+    }
+
+    /// Searches every bus and updates the model to the devices found. The
+    /// slaves of a bus failing to search are kept.
+    pub fn reconcile(
+        &mut self,
+        t: &mut impl Transport,
+        now: Instant,
+    ) -> Result<Vec<Change>, Error> {
+        let mut new = Snapshot::default();
+        for master in t.list_masters()? {
+            let slaves = match t.search(master, false) {
+                Ok(found) => found.into_iter().collect(),
+                Err(_) => self
+                    .current
+                    .masters
+                    .get(&master)
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            new.masters.insert(master, slaves);
+        }
+        self.last_reconcile = Some(now);
+        self.stale = false;
+        Ok(self.update(new))
+    }
+}
+
+/// Finds the master a slave is registered with.
+fn locate(t: &mut impl Transport, id: SlaveId) -> Result<Option<u32>, Error> {
+    for master in t.list_masters()? {
+        if t.list_slaves(master)?.contains(&id) {
+            return Ok(Some(master));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,176 @@
+//! Request/reply abstraction on top of the w1 netlink protocol.
+//!
+//! Device drivers only talk to a [`Transport`], which is implemented by the
+//! netlink [`client`](crate::client) as well as the [simulated w1 core](crate::sim).
+
+use crate::proto::{
+    command::{SlaveId, W1NetlinkCommand},
+    connector, message,
+    message::W1NetlinkMessage,
+    SerializeError,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid netlink message: {0}")]
+    Netlink(#[from] netlink_packet_core::DecodeError),
+
+    #[error("Invalid connector message: {0}")]
+    Connector(#[from] connector::DeserializeError<message::DeserializeError>),
+
+    #[error("Cannot encode request: {0}")]
+    Serialize(#[from] SerializeError),
+
+    #[error("w1 core reported error status {0}")]
+    Status(u8),
+
+    #[error("Unexpected reply from w1 core: {0:?}")]
+    UnexpectedReply(W1NetlinkMessage),
+
+    #[error("No device responded on the bus")]
+    NoDevice,
+
+    #[error("CRC mismatch, expected {expected:#06x}, got {actual:#06x}")]
+    Crc { expected: u16, actual: u16 },
+
+    #[error("Unexpected response from device: {0}")]
+    Device(&'static str),
+}
+
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            err => std::io::Error::other(err),
+        }
+    }
+}
+
+/// Whether `err` tells that the socket dropped messages, e.g. hotplug events
+/// or replies, because its receive buffer overflowed.
+pub fn is_overflow(err: &Error) -> bool {
+    matches!(err, Error::Io(err) if err.raw_os_error() == Some(libc::ENOBUFS))
+}
+
+pub trait Transport {
+    /// Sends a single message to the w1 core and returns all replies carrying
+    /// data, i.e. read, touch, search and list results, in the order the core
+    /// generated them.
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error>;
+
+    fn list_masters(&mut self) -> Result<Vec<u32>, Error> {
+        let mut masters = Vec::new();
+        for reply in self.transact(W1NetlinkMessage::ListMasters(None))? {
+            match reply {
+                W1NetlinkMessage::ListMasters(ids) => masters.extend(ids.into_iter().flatten()),
+                other => return Err(Error::UnexpectedReply(other)),
+            }
+        }
+        Ok(masters)
+    }
+
+    /// Runs a search or alarm search in the w1 core and returns the ids found.
+    fn search(&mut self, master: u32, alarm: bool) -> Result<Vec<SlaveId>, Error> {
+        let cmd = match alarm {
+            true => W1NetlinkCommand::AlarmSearch(None),
+            false => W1NetlinkCommand::Search(None),
+        };
+        let mut ids = Vec::new();
+        for reply in self.master_command(master, vec![cmd])? {
+            if let W1NetlinkCommand::Search(found) | W1NetlinkCommand::AlarmSearch(found) = reply {
+                ids.extend(found.into_iter().flatten());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Lists the slaves the w1 core has registered for `master`, without
+    /// touching the bus.
+    fn list_slaves(&mut self, master: u32) -> Result<Vec<SlaveId>, Error> {
+        let mut ids = Vec::new();
+        for reply in self.master_command(master, vec![W1NetlinkCommand::ListSlaves(None)])? {
+            if let W1NetlinkCommand::ListSlaves(found) = reply {
+                ids.extend(found.into_iter().flatten());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Runs `cmds` on the bus of `master` without holding any slave selected.
+    fn master_command(
+        &mut self,
+        master: u32,
+        cmds: Vec<W1NetlinkCommand>,
+    ) -> Result<Vec<W1NetlinkCommand>, Error> {
+        let msg = W1NetlinkMessage::MasterCommand {
+            target: master,
+            cmds,
+        };
+        command_replies(self.transact(msg)?)
+    }
+
+    /// Runs `cmds` for a single slave. The w1 core resets the bus and selects
+    /// the slave with a Match ROM before the first command, failing with
+    /// `ENODEV` without presence pulse, and [`W1NetlinkCommand::Reset`] does
+    /// the same again within `cmds`.
+    fn slave_command(
+        &mut self,
+        slave: SlaveId,
+        cmds: Vec<W1NetlinkCommand>,
+    ) -> Result<Vec<W1NetlinkCommand>, Error> {
+        let msg = W1NetlinkMessage::SlaveCommand {
+            target: slave.into(),
+            cmds,
+        };
+        command_replies(self.transact(msg)?)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        (**self).transact(msg)
+    }
+}
+
+fn command_replies(replies: Vec<W1NetlinkMessage>) -> Result<Vec<W1NetlinkCommand>, Error> {
+    let mut cmds = Vec::new();
+    for reply in replies {
+        match reply {
+            W1NetlinkMessage::MasterCommand { cmds: c, .. }
+            | W1NetlinkMessage::SlaveCommand { cmds: c, .. } => cmds.extend(c),
+            other => return Err(Error::UnexpectedReply(other)),
+        }
+    }
+    Ok(cmds)
+}
+
+/// Collects the data of all read and touch replies in order.
+pub fn read_data(replies: Vec<W1NetlinkCommand>) -> Vec<Vec<u8>> {
+    replies
+        .into_iter()
+        .filter_map(|cmd| match cmd {
+            W1NetlinkCommand::Read(data) => data,
+            W1NetlinkCommand::Touch(data) => Some(data),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Shorthand for a read command of `len` bytes.
+pub fn read(len: usize) -> W1NetlinkCommand {
+    W1NetlinkCommand::Read(Some(vec![0; len]))
+}
+
+/// Shorthand for a touch command, writing `data` and sampling the bus in the
+/// same time slots.
+pub fn touch(data: impl Into<Vec<u8>>) -> W1NetlinkCommand {
+    W1NetlinkCommand::Touch(data.into())
+}
+
+/// Shorthand for a write command.
+pub fn write(data: impl Into<Vec<u8>>) -> W1NetlinkCommand {
+    W1NetlinkCommand::Write(data.into())
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+use std::time::Duration;
+
+use w1_netlink::{
+    alarm::{AlarmConfig, AlarmReport, AlarmScan, Bound, DeviceLimits, ThermometerLimits},
+    device::{
+        ds2450::{self, AlarmLimits, Channel},
+        thermometer::DS18B20_FAMILY,
+    },
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+struct Bus {
+    bus: SimBus,
+    scan: AlarmScan,
+    cold: SlaveId,
+    normal: SlaveId,
+    hot: SlaveId,
+    adc: SlaveId,
+}
+
+fn bus() -> Bus {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let thermometer = |serial| SlaveId::from_parts(DS18B20_FAMILY, serial);
+    let (cold, normal, hot) = (thermometer(1), thermometer(2), thermometer(3));
+    bus.attach(master, cold, sim::thermometer::Ds18b20::new(-5.5));
+    bus.attach(master, normal, sim::thermometer::Ds18b20::new(21.0));
+    bus.attach(master, hot, sim::thermometer::Ds18b20::new(30.0));
+
+    let adc = SlaveId::from_parts(ds2450::FAMILY, 4);
+    bus.attach(master, adc, sim::ds2450::Ds2450::new());
+    bus.device_mut::<sim::ds2450::Ds2450>(adc).unwrap().inputs = [0.5, 1.0, 2.0, 0.0];
+
+    let scan = AlarmScan::new(master).with_conversion_time(Duration::ZERO);
+    Bus {
+        bus,
+        scan,
+        cold,
+        normal,
+        hot,
+        adc,
+    }
+}
+
+fn adc_limits() -> [AlarmLimits; 4] {
+    let limit = |low, high| AlarmLimits { low, high };
+    [
+        limit(Some(0x40), None),
+        limit(Some(0x40), Some(0xC0)),
+        limit(None, Some(0xC0)),
+        AlarmLimits::default(),
+    ]
+}
+
+#[test]
+fn configure_and_scan() {
+    let mut b = bus();
+    let config = AlarmConfig::new()
+        .with_thermometers(0, 25)
+        .with_adc(adc_limits());
+
+    let mut configured = b.scan.configure(&mut b.bus, &config).unwrap();
+    configured.sort();
+    assert_eq!(configured, [b.adc, b.cold, b.normal, b.hot]);
+
+    let reports = b.scan.scan(&mut b.bus).unwrap();
+    assert_eq!(reports.len(), 4);
+    let temperature = |id| {
+        reports.iter().find_map(|r| match r {
+            AlarmReport::Temperature {
+                id: i,
+                temperature,
+                bound,
+                ..
+            } if *i == id => Some((temperature.celsius(), *bound)),
+            _ => None,
+        })
+    };
+    assert_eq!(temperature(b.cold), Some((-5.5, Bound::Low)));
+    assert_eq!(temperature(b.hot), Some((30.0, Bound::High)));
+    assert_eq!(temperature(b.normal), None);
+
+    let voltages: Vec<_> = reports
+        .iter()
+        .filter_map(|r| match r {
+            AlarmReport::Voltage {
+                id, channel, bound, ..
+            } if *id == b.adc => Some((*channel, *bound)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        voltages,
+        [(Channel::A, Bound::Low), (Channel::C, Bound::High)]
+    );
+}
+
+#[test]
+fn device_override() {
+    let mut b = bus();
+    let config = AlarmConfig::new().with_thermometers(0, 25).with_device(
+        b.hot,
+        DeviceLimits::Thermometer(ThermometerLimits { low: 0, high: 40 }),
+    );
+    b.scan.configure(&mut b.bus, &config).unwrap();
+
+    // an unconfigured DS2450 answers alarm searches until its status is
+    // written after power-up, but has nothing to report
+    let reports = b.scan.scan(&mut b.bus).unwrap();
+    let ids: Vec<_> = reports.iter().map(AlarmReport::id).collect();
+    assert_eq!(ids, [b.cold]);
+}
+
+#[test]
+fn in_range() {
+    let mut b = bus();
+    b.bus
+        .device_mut::<sim::ds2450::Ds2450>(b.adc)
+        .unwrap()
+        .inputs = [1.0; 4];
+    let config = AlarmConfig::new()
+        .with_thermometers(-10, 50)
+        .with_adc(adc_limits());
+    b.scan.configure(&mut b.bus, &config).unwrap();
+
+    assert!(b.scan.scan(&mut b.bus).unwrap().is_empty());
+}
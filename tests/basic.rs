@@ -1,14 +1,17 @@
+#![cfg(feature = "std")]
+
 use netlink_packet_core::NetlinkMessage;
 use w1_netlink::proto::{
-    command::W1NetlinkCommand,
-    connector::NlConnectorMessage,
-    message::{TargetId, W1MessageType, W1NetlinkMessage},
+    command::W1NetlinkCommand, connector::NlConnectorMessage, message::W1NetlinkMessage,
 };
 
 #[test]
 fn serialize() {
     let cmd = W1NetlinkCommand::Search(None);
-    let msg = W1NetlinkMessage::new(W1MessageType::MasterCmd, TargetId::master_id(0), [cmd]);
+    let msg = W1NetlinkMessage::MasterCommand {
+        target: 0,
+        cmds: vec![cmd],
+    };
     let cmsg = NlConnectorMessage::new(0, [msg]);
 
     let mut packet = NetlinkMessage::from(cmsg);
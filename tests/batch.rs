@@ -0,0 +1,111 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    batch::{Batch, MAX_DATA_LEN, MAX_PAYLOAD_LEN},
+    device::{
+        eeprom::{Model, DS2433_FAMILY},
+        rom::MATCH_ROM,
+    },
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        message::W1NetlinkMessage,
+        Serializable, SerializeError,
+    },
+    sim::{self, SimBus},
+    transport::{read, read_data, write, Error, Transport},
+};
+
+#[test]
+fn split_write() {
+    let data: Vec<u8> = (0..40000).map(|i| i as u8).collect();
+    let msgs = [
+        W1NetlinkMessage::MasterCommand {
+            target: 1,
+            cmds: vec![W1NetlinkCommand::Reset, write(data.clone())],
+        },
+        W1NetlinkMessage::ListMasters(None),
+    ];
+    let batch = Batch::new(7, msgs).unwrap();
+    let seqs: Vec<_> = batch.requests.iter().map(|r| r.header.seq).collect();
+    assert_eq!(seqs, [7, 8, 9]);
+
+    let mut written: Vec<u8> = Vec::new();
+    for request in &batch.requests {
+        request.validate().unwrap();
+        for msg in &request.payload {
+            if let W1NetlinkMessage::MasterCommand { cmds, .. } = msg {
+                for cmd in cmds {
+                    if let W1NetlinkCommand::Write(chunk) = cmd {
+                        assert!(chunk.len() <= MAX_DATA_LEN);
+                        written.extend(chunk);
+                    }
+                }
+            }
+        }
+    }
+    assert_eq!(written, data);
+    // the master list shares the last connector message
+    assert!(matches!(
+        batch.requests[2].payload.last(),
+        Some(W1NetlinkMessage::ListMasters(None))
+    ));
+}
+
+#[test]
+fn reassemble_reads() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(DS2433_FAMILY, 0x1234);
+    let mut eeprom = sim::eeprom::Eeprom::new(Model::Ds2433);
+    for (i, byte) in eeprom.memory.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    bus.attach(master, id, eeprom);
+
+    // reads past the end of the memory in several messages, as a master
+    // command since the w1 core selects the slave again for every slave
+    // command
+    let mut select = vec![MATCH_ROM];
+    select.extend(id.bytes());
+    select.extend([0xf0, 0x00, 0x00]);
+    let cmds = vec![W1NetlinkCommand::Reset, write(select), read(40000), read(2)];
+    let data = read_data(bus.master_command(master, cmds).unwrap());
+    assert_eq!(data.len(), 2);
+    let memory = &bus.device_mut::<sim::eeprom::Eeprom>(id).unwrap().memory;
+    let mut expected = memory.clone();
+    expected.resize(40000, 0xFF);
+    assert_eq!(data[0], expected);
+    assert_eq!(data[1], [0xFF, 0xFF]);
+}
+
+#[test]
+fn unsplit_slave_command() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(DS2433_FAMILY, 0x1234);
+    bus.attach(master, id, sim::eeprom::Eeprom::new(Model::Ds2433));
+
+    let cmds = vec![
+        W1NetlinkCommand::Reset,
+        write([0xf0, 0x00, 0x00]),
+        read(40000),
+    ];
+    assert!(matches!(
+        bus.slave_command(id, cmds),
+        Err(Error::Serialize(SerializeError::TooLong { .. }))
+    ));
+
+    // fitting a single connector message
+    let cmds = vec![
+        W1NetlinkCommand::Reset,
+        write([0xf0, 0x00, 0x00]),
+        read(MAX_PAYLOAD_LEN - 100),
+    ];
+    let request = W1NetlinkMessage::SlaveCommand {
+        target: id.into(),
+        cmds: cmds.clone(),
+    };
+    assert_eq!(Batch::new(0, [request]).unwrap().requests.len(), 1);
+    let data = read_data(bus.slave_command(id, cmds).unwrap());
+    assert_eq!(data[0][..512], [0xFF; 512]);
+}
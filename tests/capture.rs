@@ -0,0 +1,67 @@
+// The sample captures were taken on a little-endian host.
+#![cfg(all(feature = "std", target_endian = "little"))]
+
+use std::time::Duration;
+
+use w1_netlink::{
+    capture::{self, Direction, Error},
+    proto::{command::W1NetlinkCommand, message::W1NetlinkMessage},
+};
+
+#[test]
+fn timeline() {
+    let pcap = capture::read("tests/data/w1.pcap").unwrap();
+    let pcapng = capture::read("tests/data/w1.pcapng").unwrap();
+
+    // routing and process connector traffic is skipped
+    assert_eq!(pcap.len(), 6);
+    assert_eq!(pcapng.len(), 6);
+    for (a, b) in pcap.iter().zip(&pcapng) {
+        assert_eq!(a.timestamp, b.timestamp);
+        assert_eq!(a.direction, b.direction);
+        assert_eq!(
+            a.messages.as_ref().unwrap()[0].payload,
+            b.messages.as_ref().unwrap()[0].payload
+        );
+    }
+
+    let read = &pcapng[3];
+    assert_eq!(
+        read.timestamp,
+        Duration::from_secs(1_700_000_000) + Duration::from_micros(12_400)
+    );
+    assert_eq!(read.direction, Direction::ToUser);
+    let cmsg = &read.messages.as_ref().unwrap()[0];
+    assert_eq!(cmsg.header.seq, 2);
+    let W1NetlinkMessage::SlaveCommand { cmds, .. } = &cmsg.payload[0] else {
+        panic!("unexpected message {:?}", cmsg.payload[0]);
+    };
+    assert_eq!(
+        cmds,
+        &[W1NetlinkCommand::Read(Some(vec![
+            0x50, 0x05, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0x1c
+        ]))]
+    );
+    assert_eq!(pcapng[2].direction, Direction::ToKernel);
+}
+
+#[test]
+fn invalid_captures() {
+    let pcap = std::fs::read("tests/data/w1.pcap").unwrap();
+    assert!(matches!(
+        capture::timeline(&pcap[..pcap.len() - 3]),
+        Err(Error::Truncated(_))
+    ));
+    assert!(matches!(
+        capture::timeline(b"not a capture"),
+        Err(Error::UnknownFormat)
+    ));
+
+    // an Ethernet capture
+    let mut ethernet = pcap.clone();
+    ethernet[20] = 1;
+    assert!(matches!(
+        capture::timeline(&ethernet),
+        Err(Error::LinkType(1))
+    ));
+}
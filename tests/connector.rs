@@ -0,0 +1,196 @@
+//! Replies laid out byte for byte as the kernel's w1 core sends them,
+//! captured on a little-endian host.
+#![cfg(all(feature = "std", target_endian = "little"))]
+
+use netlink_packet_core::{NetlinkDeserializable, NetlinkHeader, NLMSG_DONE};
+use w1_netlink::{
+    client::{decode_datagram, Datagram},
+    proto::{
+        command::{SlaveId, W1NetlinkCommand, W1NetlinkCommandRef},
+        connector::NlConnectorMessage,
+        message::{W1NetlinkMessage, W1NetlinkMessageRef},
+    },
+};
+
+fn hex(s: &str) -> Vec<u8> {
+    s.split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).unwrap())
+        .collect()
+}
+
+/// W1_LIST_MASTERS reply with masters 1 and 2.
+const LIST_MASTERS: &str = "
+    38 00 00 00  03 00  00 00  01 00 00 00  00 00 00 00
+    03 00 00 00  01 00 00 00  01 00 00 00  02 00 00 00  14 00  00 00
+    06 00 08 00  00 00 00 00 00 00 00 00
+    01 00 00 00  02 00 00 00";
+
+/// Bundled (W1_CN_BUNDLE) replies to a slave command with ack set:
+/// reset, write BE, read 9.
+/// - status replies for reset and write sharing one cn_msg with ack 1
+/// - the read data in a cn_msg with ack seq + 1
+/// - the status reply for the read in another cn_msg with ack 1
+const BUNDLED_READ: &str = "
+    95 00 00 00  03 00  00 00  05 00 00 00  00 00 00 00
+
+    03 00 00 00  01 00 00 00  05 00 00 00  01 00 00 00  20 00  01 00
+    05 00 04 00  28 1f 3a 6c 05 00 00 ef  05 00 00 00
+    05 00 04 00  28 1f 3a 6c 05 00 00 ef  01 00 00 00
+
+    03 00 00 00  01 00 00 00  05 00 00 00  06 00 00 00  19 00  01 00
+    05 00 0d 00  28 1f 3a 6c 05 00 00 ef  00 00 09 00
+    50 05 4b 46 7f ff 0c 10 1c
+
+    03 00 00 00  01 00 00 00  05 00 00 00  01 00 00 00  10 00  01 00
+    05 00 04 00  28 1f 3a 6c 05 00 00 ef  00 00 00 00";
+
+fn slave() -> u64 {
+    "28-0000056c3a1f".parse::<SlaveId>().unwrap().into()
+}
+
+#[test]
+fn list_masters() {
+    let msgs = decode_datagram(&hex(LIST_MASTERS)).unwrap();
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].header.seq, 1);
+    assert_eq!(msgs[0].header.ack, 2);
+    match &msgs[0].payload[..] {
+        [W1NetlinkMessage::ListMasters(Some(ids))] => assert_eq!(ids, &[1, 2]),
+        other => panic!("unexpected payload {other:?}"),
+    }
+}
+
+#[test]
+fn netlink_deserializable() {
+    let bytes = hex(LIST_MASTERS);
+    let header = NetlinkHeader {
+        length: bytes.len() as u32,
+        message_type: NLMSG_DONE,
+        ..Default::default()
+    };
+    let msg = NlConnectorMessage::<W1NetlinkMessage>::deserialize(&header, &bytes[16..]).unwrap();
+    assert_eq!(msg.payload.len(), 1);
+
+    // bundled messages do not fit into a single connector message
+    let bytes = hex(BUNDLED_READ);
+    assert!(NlConnectorMessage::<W1NetlinkMessage>::deserialize(&header, &bytes[16..]).is_err());
+}
+
+#[test]
+fn bundled_replies() {
+    let msgs = decode_datagram(&hex(BUNDLED_READ)).unwrap();
+    let acks: Vec<_> = msgs.iter().map(|m| m.header.ack).collect();
+    assert_eq!(acks, [1, 6, 1]);
+    assert!(msgs.iter().all(|m| m.header.seq == 5));
+
+    // two status replies in the first connector message
+    assert_eq!(msgs[0].payload.len(), 2);
+    for (msg, expected) in msgs[0].payload.iter().zip([5, 1]) {
+        match msg {
+            W1NetlinkMessage::SlaveCommand { target, cmds } => {
+                assert_eq!(*target, slave());
+                assert_eq!(cmds.len(), 1);
+                assert_eq!(u8::from(cmds[0].cmd_type()), expected);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    match &msgs[1].payload[..] {
+        [W1NetlinkMessage::SlaveCommand { target, cmds }] => {
+            assert_eq!(*target, slave());
+            match &cmds[..] {
+                [W1NetlinkCommand::Read(Some(data))] => {
+                    assert_eq!(data, &hex("50 05 4b 46 7f ff 0c 10 1c"))
+                }
+                other => panic!("unexpected commands {other:?}"),
+            }
+        }
+        other => panic!("unexpected payload {other:?}"),
+    }
+    assert_eq!(msgs[2].payload.len(), 1);
+}
+
+#[test]
+fn several_netlink_messages() {
+    // the second netlink message starts at the next four byte boundary
+    let mut datagram = hex(BUNDLED_READ);
+    datagram.extend([0; 3]);
+    datagram.extend(hex(LIST_MASTERS));
+    let msgs = decode_datagram(&datagram).unwrap();
+    assert_eq!(msgs.len(), 4);
+    assert!(matches!(
+        msgs[3].payload[..],
+        [W1NetlinkMessage::ListMasters(_)]
+    ));
+}
+
+#[test]
+fn truncated_bundle() {
+    let mut bytes = hex(BUNDLED_READ);
+    // the netlink length claims the last status reply that is cut off
+    bytes.truncate(bytes.len() - 4);
+    bytes[0] -= 4;
+    assert!(decode_datagram(&bytes).is_err());
+}
+
+#[test]
+fn borrowed_views() {
+    let bytes = hex(BUNDLED_READ);
+    let cmsgs: Vec<_> = Datagram::new(&bytes).collect::<Result<_, _>>().unwrap();
+    assert_eq!(cmsgs.len(), 3);
+
+    let msgs: Vec<_> = cmsgs[1].messages().collect::<Result<_, _>>().unwrap();
+    let cmds = match msgs[..] {
+        [W1NetlinkMessageRef::SlaveCommand { target, cmds }] => {
+            assert_eq!(target, slave());
+            cmds
+        }
+        ref other => panic!("unexpected payload {other:?}"),
+    };
+    match cmds.collect::<Result<Vec<_>, _>>().unwrap()[..] {
+        [W1NetlinkCommandRef::Read(data)] => {
+            // the data is not copied out of the datagram
+            assert!(bytes.as_ptr_range().contains(&data.as_ptr()));
+            assert_eq!(data, hex("50 05 4b 46 7f ff 0c 10 1c"));
+        }
+        ref other => panic!("unexpected commands {other:?}"),
+    }
+
+    let owned: Vec<_> = cmsgs
+        .iter()
+        .map(|cmsg| {
+            cmsg.messages()
+                .map(|msg| msg.unwrap().try_into_owned().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let decoded: Vec<_> = decode_datagram(&bytes)
+        .unwrap()
+        .into_iter()
+        .map(|cmsg| cmsg.payload)
+        .collect();
+    assert_eq!(owned, decoded);
+}
+
+#[test]
+fn lazy_commands() {
+    let mut bytes = hex(BUNDLED_READ);
+    // corrupt the type of the read command
+    let offset = bytes.len() - 36 - 9 - 4;
+    bytes[offset] = 0xee;
+    assert!(decode_datagram(&bytes).is_err());
+
+    // only decoding the commands of the second message fails
+    let cmsgs: Vec<_> = Datagram::new(&bytes).collect::<Result<_, _>>().unwrap();
+    let msgs: Vec<_> = cmsgs[1].messages().collect::<Result<_, _>>().unwrap();
+    match msgs[..] {
+        [W1NetlinkMessageRef::SlaveCommand { cmds, .. }] => {
+            let results: Vec<_> = cmds.collect();
+            assert_eq!(results.len(), 1);
+            assert!(results[0].is_err());
+        }
+        ref other => panic!("unexpected payload {other:?}"),
+    }
+    assert!(cmsgs[0].messages().all(|msg| msg.is_ok()));
+}
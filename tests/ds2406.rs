@@ -0,0 +1,115 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::ds2406::{
+        ChannelControl, Channels, ConditionalSearch, CrcInterval, Ds2406, SearchSource, FAMILY,
+    },
+    proto::command::{SlaveId, W1NetlinkCommand},
+    sim::{self, SimBus},
+    transport::{Error, Transport},
+};
+
+fn bus() -> (SimBus, u32, Ds2406) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(FAMILY, 0x2406);
+    let mut device = sim::ds2406::Ds2406::new();
+    for (i, byte) in device.memory.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    bus.attach(master, id, device);
+    (bus, master, Ds2406::new(id))
+}
+
+#[test]
+fn memory() {
+    let (mut bus, _, dev) = bus();
+    let data = dev.read_memory(&mut bus, 0).unwrap();
+    assert_eq!(data, (0..128).collect::<Vec<u8>>());
+    let data = dev.read_memory(&mut bus, 120).unwrap();
+    assert_eq!(data, (120..128).collect::<Vec<u8>>());
+    assert!(dev.read_memory(&mut bus, 128).is_err());
+}
+
+#[test]
+fn corrupted_memory_crc() {
+    struct Corrupt<T>(T);
+    impl<T: Transport> Transport for Corrupt<T> {
+        fn transact(
+            &mut self,
+            msg: w1_netlink::proto::message::W1NetlinkMessage,
+        ) -> Result<Vec<w1_netlink::proto::message::W1NetlinkMessage>, Error> {
+            let mut replies = self.0.transact(msg)?;
+            if let Some(w1_netlink::proto::message::W1NetlinkMessage::SlaveCommand {
+                cmds, ..
+            }) = replies.first_mut()
+            {
+                if let Some(W1NetlinkCommand::Read(Some(data))) = cmds.first_mut() {
+                    data[3] ^= 0x10;
+                }
+            }
+            Ok(replies)
+        }
+    }
+
+    let (bus, _, dev) = bus();
+    let mut bus = Corrupt(bus);
+    assert!(matches!(
+        dev.read_memory(&mut bus, 0),
+        Err(Error::Crc { .. })
+    ));
+}
+
+#[test]
+fn channel_access() {
+    let (mut bus, _, dev) = bus();
+    let info = dev.channel_info(&mut bus).unwrap();
+    assert!(info.flip_flop_a() && info.flip_flop_b());
+    assert!(info.level_a() && info.level_b());
+
+    // turn on the transistor of PIO-A, verifying the CRC after every byte
+    let control = ChannelControl::write(Channels::A).with_crc(CrcInterval::Byte);
+    dev.channel_access(&mut bus, control, &[0x00, 0x00])
+        .unwrap();
+    let info = dev.channel_info(&mut bus).unwrap();
+    assert!(!info.flip_flop_a() && !info.level_a());
+    assert!(info.activity_a());
+
+    let control = ChannelControl::read(Channels::Both).with_crc(CrcInterval::Bytes8);
+    let access = dev.channel_access(&mut bus, control, &[0; 16]).unwrap();
+    // even bits sample PIO-A, odd bits PIO-B
+    assert_eq!(access.data, vec![0xAA; 16]);
+
+    dev.set_flip_flops(&mut bus, true, false).unwrap();
+    let status = dev.read_status(&mut bus).unwrap();
+    assert!(status.flip_flop_a() && !status.flip_flop_b());
+}
+
+#[test]
+fn conditional_search() {
+    let (mut bus, master, dev) = bus();
+    let search = ConditionalSearch {
+        channels: Some(Channels::B),
+        source: SearchSource::Level,
+        high: false,
+    };
+    dev.set_conditional_search(&mut bus, search).unwrap();
+    let status = dev.read_status(&mut bus).unwrap();
+    assert_eq!(status.conditional_search(), Some(search));
+
+    let alarm_search = |bus: &mut SimBus| {
+        let replies = bus
+            .master_command(master, vec![W1NetlinkCommand::AlarmSearch(None)])
+            .unwrap();
+        match &replies[..] {
+            [W1NetlinkCommand::AlarmSearch(Some(ids))] => ids.clone(),
+            other => panic!("unexpected reply {:?}", other),
+        }
+    };
+    assert!(alarm_search(&mut bus).is_empty());
+
+    bus.device_mut::<sim::ds2406::Ds2406>(dev.id())
+        .unwrap()
+        .set_input(1, false);
+    assert_eq!(alarm_search(&mut bus), vec![dev.id()]);
+}
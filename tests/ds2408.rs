@@ -0,0 +1,32 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::ds2408::{Ds2408, FAMILY},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+#[test]
+fn channels() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(FAMILY, 1);
+    bus.attach(master, id, sim::ds2408::Ds2408::new());
+    let dev = Ds2408::new(id);
+
+    let registers = dev.read_registers(&mut bus).unwrap();
+    assert_eq!(registers.levels(), 0xFF);
+    assert_eq!(registers.latches(), 0xFF);
+    assert_eq!(registers.activity(), 0);
+    assert!(registers.supply());
+
+    assert_eq!(dev.write_latches(&mut bus, 0xF0).unwrap(), 0xF0);
+    assert_eq!(dev.set_latch(&mut bus, 7, false).unwrap(), 0x70);
+    assert_eq!(dev.set_latch(&mut bus, 0, true).unwrap(), 0x71);
+
+    bus.device_mut::<sim::ds2408::Ds2408>(id).unwrap().inputs = 0xFE;
+    let registers = dev.read_registers(&mut bus).unwrap();
+    assert_eq!(registers.levels(), 0x70);
+    assert_eq!(registers.latches(), 0x71);
+    assert_eq!(registers.activity(), 0x8F);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::ds2413::{Ds2413, FAMILY},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+#[test]
+fn pio() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(FAMILY, 1);
+    bus.attach(master, id, sim::ds2413::Ds2413::new());
+    let dev = Ds2413::new(id);
+
+    let state = dev.read_pio(&mut bus).unwrap();
+    assert!(state.a() && state.b() && state.a_latch() && state.b_latch());
+
+    let state = dev.write_pio(&mut bus, true, false).unwrap();
+    assert!(state.a() && state.a_latch());
+    assert!(!state.b() && !state.b_latch());
+    let sim = bus.device_mut::<sim::ds2413::Ds2413>(id).unwrap();
+    assert_eq!(sim.latches(), [true, false]);
+
+    sim.inputs[0] = false;
+    let state = dev.read_pio(&mut bus).unwrap();
+    assert!(!state.a() && state.a_latch());
+}
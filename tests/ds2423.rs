@@ -0,0 +1,27 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::ds2423::{Ds2423, FAMILY},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+    transport::Error,
+};
+
+#[test]
+fn counters() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(FAMILY, 1);
+    bus.attach(master, id, sim::ds2423::Ds2423::new());
+    let dev = Ds2423::new(id);
+
+    assert_eq!(dev.read_counters(&mut bus).unwrap(), [0, 0]);
+    let sim = bus.device_mut::<sim::ds2423::Ds2423>(id).unwrap();
+    sim.counters = [1, 2, 1234, 0x0102_0304];
+    assert_eq!(dev.read_counters(&mut bus).unwrap(), [1234, 0x0102_0304]);
+    assert_eq!(dev.read_counter(&mut bus, 12).unwrap(), 1);
+    assert!(matches!(
+        dev.read_counter(&mut bus, 11),
+        Err(Error::Device(_))
+    ));
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::ds2450::{AlarmLimits, Channel, Ds2450, InputRange, CONTROL_PAGE, FAMILY},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+fn bus() -> (SimBus, Ds2450) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(FAMILY, 0x1a2b);
+    bus.attach(master, id, sim::ds2450::Ds2450::new());
+    (bus, Ds2450::new(id))
+}
+
+#[test]
+fn memory() {
+    let (mut bus, adc) = bus();
+
+    // spans the control and alarm pages
+    let data = adc.read_memory(&mut bus, CONTROL_PAGE + 6, 4).unwrap();
+    assert_eq!(data, [0x08, 0x80, 0x00, 0xFF]);
+
+    adc.write_memory(&mut bus, 0x18, &[1, 2, 3]).unwrap();
+    assert_eq!(adc.read_memory(&mut bus, 0x18, 3).unwrap(), [1, 2, 3]);
+
+    // conversion results are read-only
+    assert!(adc.write_memory(&mut bus, 0x00, &[1]).is_err());
+}
+
+#[test]
+fn convert() {
+    let (mut bus, adc) = bus();
+    bus.device_mut::<sim::ds2450::Ds2450>(adc.id())
+        .unwrap()
+        .inputs = [1.28, 3.0, 5.0, 0.0];
+
+    adc.set_input(&mut bus, Channel::A, 16, InputRange::V2_56)
+        .unwrap();
+    adc.set_input(&mut bus, Channel::B, 12, InputRange::V5_12)
+        .unwrap();
+    adc.convert(&mut bus, &[Channel::A, Channel::B]).unwrap();
+
+    let raw = adc.read_conversions(&mut bus).unwrap();
+    assert_eq!(raw[0], 0x8000);
+    assert_eq!(raw[1] & 0x000F, 0);
+    assert!((InputRange::V5_12.voltage(raw[1]) - 3.0).abs() < 0.01);
+    // not converted
+    assert_eq!(raw[2], 0);
+
+    let status = adc.read_status(&mut bus).unwrap();
+    assert_eq!(status[0].resolution(), 16);
+    assert_eq!(status[1].range(), InputRange::V5_12);
+    assert!(!status[0].power_on_reset());
+    assert!(status[2].power_on_reset());
+}
+
+#[test]
+fn alarm_flags() {
+    let (mut bus, adc) = bus();
+    bus.device_mut::<sim::ds2450::Ds2450>(adc.id())
+        .unwrap()
+        .inputs[3] = 2.0;
+
+    let limits = AlarmLimits {
+        low: None,
+        high: Some(InputRange::V2_56.threshold(1.5)),
+    };
+    adc.set_alarm(&mut bus, Channel::D, limits).unwrap();
+    adc.convert(&mut bus, &Channel::ALL).unwrap();
+
+    let status = adc.read_status(&mut bus).unwrap()[3];
+    assert!(status.alarm_high_enabled() && !status.alarm_low_enabled());
+    assert!(status.alarm_high() && !status.alarm_low());
+}
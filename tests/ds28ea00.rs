@@ -0,0 +1,95 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::{
+        ds28ea00::{discover_chain, Ds28ea00, FAMILY},
+        thermometer::Resolution,
+    },
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+    transport::Error,
+};
+
+fn bus(serials: &[u64]) -> (SimBus, u32, Vec<SlaveId>) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let ids: Vec<_> = serials
+        .iter()
+        .map(|serial| SlaveId::from_parts(FAMILY, *serial))
+        .collect();
+    for (i, id) in ids.iter().enumerate() {
+        bus.attach(master, *id, sim::ds28ea00::Ds28ea00::new(20.0 + i as f32));
+    }
+    (bus, master, ids)
+}
+
+#[test]
+fn chain_order() {
+    // physical order differs from ROM id order
+    let (mut bus, master, ids) = bus(&[0x30, 0x10, 0x20]);
+
+    let order = discover_chain(&mut bus, master).unwrap();
+    assert_eq!(order, ids);
+
+    // chain mode is turned off again, discovery can be repeated
+    let order = discover_chain(&mut bus, master).unwrap();
+    assert_eq!(order, ids);
+}
+
+#[test]
+fn pio() {
+    let (mut bus, _, ids) = bus(&[1]);
+    let dev = Ds28ea00::new(ids[0]);
+
+    let state = dev.read_pio(&mut bus).unwrap();
+    assert!(state.a() && state.b() && state.a_latch() && state.b_latch());
+
+    let state = dev.write_pio(&mut bus, false, true).unwrap();
+    assert!(!state.a() && !state.a_latch());
+    assert!(state.b() && state.b_latch());
+
+    bus.device_mut::<sim::ds28ea00::Ds28ea00>(ids[0])
+        .unwrap()
+        .inputs[1] = false;
+    let state = dev.read_pio(&mut bus).unwrap();
+    assert!(!state.b() && state.b_latch());
+}
+
+#[test]
+fn temperature() {
+    let (mut bus, _, ids) = bus(&[1]);
+    let dev = Ds28ea00::new(ids[0]);
+    bus.device_mut::<sim::ds28ea00::Ds28ea00>(ids[0])
+        .unwrap()
+        .thermometer
+        .temperature = -10.1875;
+
+    let pad = dev.read_scratchpad(&mut bus).unwrap();
+    assert_eq!(pad.temperature().celsius(), 85.0);
+
+    dev.convert_temperature(&mut bus).unwrap();
+    let pad = dev.read_scratchpad(&mut bus).unwrap();
+    assert_eq!(pad.temperature().celsius(), -10.1875);
+
+    dev.set_resolution(&mut bus, Resolution::Bits9).unwrap();
+    dev.convert_temperature(&mut bus).unwrap();
+    let pad = dev.read_scratchpad(&mut bus).unwrap();
+    assert_eq!(pad.resolution(), Resolution::Bits9);
+    assert_eq!(pad.temperature().celsius(), -10.5);
+}
+
+#[test]
+fn unplugged() {
+    let (mut bus, _, ids) = bus(&[1]);
+    let dev = Ds28ea00::new(ids[0]);
+
+    // still registered, but nothing answers the reset
+    assert!(bus.set_connected(ids[0], false));
+    assert!(matches!(dev.read_pio(&mut bus), Err(Error::Status(19))));
+    assert!(bus.set_connected(ids[0], true));
+    assert!(dev.read_pio(&mut bus).is_ok());
+
+    // gone from the w1 core
+    assert!(bus.detach(ids[0]));
+    assert!(matches!(dev.read_pio(&mut bus), Err(Error::Status(19))));
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "std")]
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use w1_netlink::{
+    device::eeprom::{Eeprom, Model},
+    proto::{command::SlaveId, message::W1NetlinkMessage},
+    sim::{self, SimBus},
+    transport::{Error, Transport},
+};
+
+/// Counts the requests sent to the w1 core.
+struct Counting<T>(T, usize);
+
+impl<T: Transport> Transport for Counting<T> {
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        self.1 += 1;
+        self.0.transact(msg)
+    }
+}
+
+fn bus(model: Model) -> (Counting<SimBus>, Eeprom) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(model.family(), 0x2433);
+    let mut device = sim::eeprom::Eeprom::new(model);
+    for (i, byte) in device.memory.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    bus.attach(master, id, device);
+    (Counting(bus, 0), Eeprom::from_id(id).unwrap())
+}
+
+#[test]
+fn read_whole_memory() {
+    let (mut bus, eeprom) = bus(Model::Ds28ec20);
+    let expected: Vec<u8> = (0..2560).map(|i| (i % 251) as u8).collect();
+
+    let mut buf = vec![0; 2560];
+    eeprom.read_memory(&mut bus, 0, &mut buf).unwrap();
+    assert_eq!(buf, expected);
+    assert_eq!(bus.1, 1);
+
+    bus.1 = 0;
+    let chunked = eeprom.with_max_read(1000);
+    chunked.read_memory(&mut bus, 0, &mut buf).unwrap();
+    assert_eq!(buf, expected);
+    assert_eq!(bus.1, 3);
+
+    assert!(eeprom.read_memory(&mut bus, 2000, &mut buf).is_err());
+}
+
+#[test]
+fn file() {
+    let (bus, eeprom) = bus(Model::Ds2433);
+    let mut file = eeprom.file(bus);
+
+    // unaligned write across three pages
+    let data: Vec<u8> = (0..70).map(|i| 0xFF - i).collect();
+    file.seek(SeekFrom::Start(20)).unwrap();
+    file.write_all(&data).unwrap();
+    assert_eq!(file.stream_position().unwrap(), 90);
+
+    file.seek(SeekFrom::Current(-72)).unwrap();
+    let mut buf = vec![0; 74];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf[0], 18);
+    assert_eq!(buf[1], 19);
+    assert_eq!(&buf[2..72], &data[..]);
+    assert_eq!(buf[72], 90);
+
+    file.seek(SeekFrom::End(-4)).unwrap();
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest.len(), 4);
+    assert!(file.write_all(&[0]).is_err());
+    assert!(file.seek(SeekFrom::Current(-1000)).is_err());
+}
@@ -0,0 +1,147 @@
+//! Frames built the way the kernel lays out its structures, in host byte
+//! order. Run on a big-endian target with
+//! `cross test --target s390x-unknown-linux-gnu`.
+
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    client::decode_datagram,
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        connector::NlConnectorMessage,
+        message::{EventKind, W1NetlinkMessage},
+        Serializable,
+    },
+};
+
+const NLMSG_DONE: u16 = 3;
+
+fn rom() -> SlaveId {
+    "28-0000056c3a1f".parse().unwrap()
+}
+
+/// `struct w1_reg_num` as the kernel fills it from the ROM.
+fn reg_num(id: SlaveId) -> u64 {
+    id.bytes()
+        .iter()
+        .rev()
+        .fold(0, |value, byte| value << 8 | u64::from(*byte))
+}
+
+/// A netlink message holding one `cn_msg` with one `w1_netlink_msg`.
+fn frame(msg_type: u8, id: [u8; 8], data: &[u8]) -> Vec<u8> {
+    let mut w1 = vec![msg_type, 0];
+    w1.extend((data.len() as u16).to_ne_bytes());
+    w1.extend(id);
+    w1.extend(data);
+
+    let mut cn = Vec::new();
+    for word in [3u32, 1, 9, 10] {
+        cn.extend(word.to_ne_bytes());
+    }
+    cn.extend((w1.len() as u16).to_ne_bytes());
+    cn.extend(0u16.to_ne_bytes());
+    cn.extend(w1);
+
+    let mut nl = Vec::new();
+    nl.extend((16 + cn.len() as u32).to_ne_bytes());
+    nl.extend(NLMSG_DONE.to_ne_bytes());
+    nl.extend(0u16.to_ne_bytes());
+    nl.extend(9u32.to_ne_bytes());
+    nl.extend(0u32.to_ne_bytes());
+    nl.extend(cn);
+    nl
+}
+
+fn decode(datagram: &[u8]) -> W1NetlinkMessage {
+    let mut msgs = decode_datagram(datagram).unwrap();
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(msgs[0].header.seq, 9);
+    assert_eq!(msgs[0].header.ack, 10);
+    msgs[0].payload.remove(0)
+}
+
+#[test]
+fn slave_id() {
+    assert_eq!(reg_num(rom()), u64::from(rom()));
+    assert_eq!(SlaveId::from(reg_num(rom())), rom());
+}
+
+#[test]
+fn slave_event() {
+    let msg = decode(&frame(0, reg_num(rom()).to_ne_bytes(), &[]));
+    assert_eq!(
+        msg,
+        W1NetlinkMessage::SlaveEvent {
+            kind: EventKind::Add,
+            target: rom().into(),
+        }
+    );
+}
+
+#[test]
+fn master_ids() {
+    let mut id = [0; 8];
+    id[..4].copy_from_slice(&0x0102_0304u32.to_ne_bytes());
+    let msg = decode(&frame(2, id, &[]));
+    assert_eq!(
+        msg,
+        W1NetlinkMessage::MasterEvent {
+            kind: EventKind::Add,
+            target: 0x0102_0304,
+        }
+    );
+
+    let data: Vec<u8> = [1u32, 0x100]
+        .iter()
+        .flat_map(|id| id.to_ne_bytes())
+        .collect();
+    let msg = decode(&frame(6, [0; 8], &data));
+    assert_eq!(msg, W1NetlinkMessage::ListMasters(Some(vec![1, 0x100])));
+}
+
+#[test]
+fn search_reply() {
+    let other = SlaveId::from_parts(0x2d, 0x1234);
+    let mut cmd = vec![2, 0];
+    cmd.extend(16u16.to_ne_bytes());
+    cmd.extend(reg_num(rom()).to_ne_bytes());
+    cmd.extend(reg_num(other).to_ne_bytes());
+    let mut id = [0; 8];
+    id[..4].copy_from_slice(&1u32.to_ne_bytes());
+
+    let msg = decode(&frame(4, id, &cmd));
+    assert_eq!(
+        msg,
+        W1NetlinkMessage::MasterCommand {
+            target: 1,
+            cmds: vec![W1NetlinkCommand::Search(Some(vec![rom(), other]))],
+        }
+    );
+}
+
+#[test]
+fn serialize() {
+    let msg = W1NetlinkMessage::SlaveCommand {
+        target: rom().into(),
+        cmds: vec![W1NetlinkCommand::Write(vec![0x44])],
+    };
+    let cmsg = NlConnectorMessage::new(9, [msg]);
+    let mut buf = vec![0; 20 + cmsg.payload.buffer_len()];
+    netlink_packet_core::NetlinkSerializable::serialize(&cmsg, &mut buf);
+
+    assert_eq!(buf[8..12], 9u32.to_ne_bytes());
+    assert_eq!(buf[16..18], 17u16.to_ne_bytes());
+    assert_eq!(buf[22..24], 5u16.to_ne_bytes());
+    assert_eq!(buf[24..32], reg_num(rom()).to_ne_bytes());
+    assert_eq!(buf[34..36], 1u16.to_ne_bytes());
+}
+
+/// The id field holds the ROM in reverse on big-endian hosts.
+#[test]
+#[cfg(target_endian = "big")]
+fn big_endian_layout() {
+    let mut reversed = rom().bytes();
+    reversed.reverse();
+    assert_eq!(reg_num(rom()).to_ne_bytes(), reversed);
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "std")]
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use w1_netlink::{
+    device::{ds2406, ds2423, ds2450, ds28ea00, thermometer::DS18B20_FAMILY},
+    exporter::{self, Collector},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+fn get(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn metrics() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let thermometer = SlaveId::from_parts(DS18B20_FAMILY, 1);
+    let adc = SlaveId::from_parts(ds2450::FAMILY, 2);
+    let switch = SlaveId::from_parts(ds2406::FAMILY, 3);
+    let chain = SlaveId::from_parts(ds28ea00::FAMILY, 4);
+    let counter = SlaveId::from_parts(ds2423::FAMILY, 5);
+    bus.attach(master, thermometer, sim::thermometer::Ds18b20::new(21.5));
+    bus.attach(master, adc, sim::ds2450::Ds2450::new());
+    bus.attach(master, switch, sim::ds2406::Ds2406::new());
+    bus.attach(master, chain, sim::ds28ea00::Ds28ea00::new(-3.25));
+    bus.attach(master, counter, sim::ds2423::Ds2423::new());
+    bus.device_mut::<sim::ds2450::Ds2450>(adc).unwrap().inputs = [1.28, 0.0, 0.0, 0.0];
+    bus.device_mut::<sim::ds2423::Ds2423>(counter)
+        .unwrap()
+        .counters = [0, 0, 1234, 5];
+
+    let mut collector = Collector::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_aliases([(switch, "Pump \"A\"".to_string())].into());
+    collector.scrape(&mut bus).unwrap();
+    collector.scrape(&mut bus).unwrap();
+    let page = collector.render();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || exporter::serve(&listener, || page.clone(), |_| {}));
+
+    let response = get(&addr, "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    for line in [
+        "# TYPE w1_temperature_celsius gauge",
+        "w1_temperature_celsius{master=\"1\",rom=\"28-000000000001\",family=\"DS18B20\"} 21.5",
+        "w1_temperature_celsius{master=\"1\",rom=\"42-000000000004\",family=\"DS28EA00\"} -3.25",
+        "w1_voltage_volts{master=\"1\",rom=\"20-000000000002\",family=\"DS2450\",channel=\"A\"} 1.28",
+        "w1_switch_state{master=\"1\",rom=\"12-000000000003\",family=\"DS2406\",name=\"Pump \\\"A\\\"\",channel=\"A\"} 1",
+        "w1_switch_state{master=\"1\",rom=\"42-000000000004\",family=\"DS28EA00\",channel=\"B\"} 1",
+        "# TYPE w1_counter_total counter",
+        "w1_counter_total{master=\"1\",rom=\"1d-000000000005\",family=\"DS2423\",channel=\"A\"} 1234",
+        "w1_counter_total{master=\"1\",rom=\"1d-000000000005\",family=\"DS2423\",channel=\"B\"} 5",
+        "w1_devices{master=\"1\"} 5",
+        "# TYPE w1_scrapes_total counter",
+        "w1_scrapes_total 2",
+    ] {
+        assert!(body.lines().any(|l| l == line), "missing {line} in\n{body}");
+    }
+    assert!(body.contains("w1_search_duration_seconds{master=\"1\"} "));
+    assert!(!body.contains("errors_total"), "{body}");
+
+    assert!(get(&addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
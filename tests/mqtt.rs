@@ -0,0 +1,157 @@
+#![cfg(feature = "mqtt")]
+
+use std::time::Duration;
+
+use serde_json::Value;
+use w1_netlink::{
+    device::{ds2406, ds2408, ds2413, thermometer::DS18B20_FAMILY},
+    mqtt::{Bridge, Error, Publish},
+    proto::command::SlaveId,
+    sim::{self, SimBus},
+};
+
+fn find<'a>(msgs: &'a [Publish], topic: &str) -> &'a Publish {
+    msgs.iter()
+        .find(|msg| msg.topic == topic)
+        .unwrap_or_else(|| panic!("nothing published to {topic}"))
+}
+
+#[test]
+fn bridge() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let thermometer = SlaveId::from_parts(DS18B20_FAMILY, 1);
+    let switch = SlaveId::from_parts(ds2413::FAMILY, 2);
+    let unknown = SlaveId::from_parts(0x01, 3);
+    bus.attach(master, thermometer, sim::thermometer::Ds18b20::new(21.5));
+    bus.attach(master, switch, sim::ds2413::Ds2413::new());
+    bus.attach(master, unknown, sim::ds2413::Ds2413::new());
+
+    let mut bridge = Bridge::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_aliases([(thermometer, "Kitchen".to_string())].into());
+    let msgs = bridge.poll(&mut bus).unwrap();
+    assert_eq!(bridge.devices().collect::<Vec<_>>(), [thermometer, switch]);
+
+    let config = find(
+        &msgs,
+        "homeassistant/sensor/w1_28-000000000001/temperature/config",
+    );
+    assert!(config.retain);
+    let config: Value = serde_json::from_str(&config.payload).unwrap();
+    assert_eq!(config["unique_id"], "w1_28-000000000001_temperature");
+    assert_eq!(config["state_topic"], "w1/28-000000000001/temperature");
+    assert_eq!(config["device_class"], "temperature");
+    assert_eq!(config["device"]["model"], "DS18B20");
+    assert_eq!(config["device"]["name"], "Kitchen");
+    assert_eq!(config["availability"][0]["topic"], "w1/status");
+
+    let config = find(
+        &msgs,
+        "homeassistant/switch/w1_3a-000000000002/output_b/config",
+    );
+    let config: Value = serde_json::from_str(&config.payload).unwrap();
+    assert_eq!(config["command_topic"], "w1/3a-000000000002/output_b/set");
+    assert_eq!(config["device"]["name"], "DS2413 3a-000000000002");
+
+    assert_eq!(
+        find(&msgs, "w1/28-000000000001/temperature").payload,
+        "21.5"
+    );
+    assert_eq!(find(&msgs, "w1/28-000000000001/status").payload, "online");
+    assert_eq!(find(&msgs, "w1/3a-000000000002/output_a").payload, "OFF");
+    assert!(!msgs.iter().any(|msg| msg.topic.contains("01-000000000003")));
+
+    // announced once only
+    let msgs = bridge.poll(&mut bus).unwrap();
+    assert!(!msgs.iter().any(|msg| msg.topic.ends_with("/config")));
+    assert_eq!(bridge.announcements().len(), 3);
+
+    let msgs = bridge
+        .command(&mut bus, "w1/3a-000000000002/output_b/set", b"ON")
+        .unwrap();
+    assert_eq!(msgs[0].topic, "w1/3a-000000000002/output_b");
+    assert_eq!(msgs[0].payload, "ON");
+    let sim = bus.device_mut::<sim::ds2413::Ds2413>(switch).unwrap();
+    assert_eq!(sim.latches(), [true, false]);
+
+    assert!(matches!(
+        bridge.command(&mut bus, "w1/3a-000000000002/output_b/set", b"on"),
+        Err(Error::Payload(_))
+    ));
+    assert!(matches!(
+        bridge.command(&mut bus, "w1/28-000000000001/temperature/set", b"ON"),
+        Err(Error::Topic(_))
+    ));
+}
+
+#[test]
+fn hotplug() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(ds2408::FAMILY, 1);
+    bus.attach(master, id, sim::ds2408::Ds2408::new());
+
+    let mut bridge = Bridge::new()
+        .with_base_topic("home/w1")
+        .with_discovery_prefix("ha");
+    let mut msgs = Vec::new();
+    while let Some(event) = bus.next_event() {
+        msgs.extend(bridge.handle_event(&event));
+    }
+    assert_eq!(msgs.len(), 8);
+    find(&msgs, "ha/switch/w1_29-000000000001/output_7/config");
+
+    let msgs = bridge
+        .command(&mut bus, "home/w1/29-000000000001/output_3/set", b"ON")
+        .unwrap();
+    assert_eq!(msgs[0].topic, "home/w1/29-000000000001/output_3");
+    let sim = bus.device_mut::<sim::ds2408::Ds2408>(id).unwrap();
+    assert_eq!(sim.latches(), 0xF7);
+
+    bus.detach(id);
+    let event = bus.next_event().unwrap();
+    let msgs = bridge.handle_event(&event);
+    assert!(msgs.iter().all(|msg| msg.payload.is_empty() && msg.retain));
+    find(&msgs, "ha/switch/w1_29-000000000001/output_7/config");
+    find(&msgs, "home/w1/29-000000000001/output_0");
+    assert_eq!(bridge.devices().count(), 0);
+    assert!(bridge.handle_event(&event).is_empty());
+}
+
+#[test]
+fn ds2406_channels() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let single = SlaveId::from_parts(ds2406::FAMILY, 1);
+    let dual = SlaveId::from_parts(ds2406::FAMILY, 2);
+    let mut device = sim::ds2406::Ds2406::new();
+    device.has_b = false;
+    bus.attach(master, single, device);
+    bus.attach(master, dual, sim::ds2406::Ds2406::new());
+
+    let mut bridge = Bridge::new().with_conversion_time(Duration::ZERO);
+    let msgs = bridge.poll(&mut bus).unwrap();
+    let mut configs: Vec<_> = msgs
+        .iter()
+        .filter(|msg| msg.topic.ends_with("/config"))
+        .map(|msg| msg.topic.as_str())
+        .collect();
+    configs.sort();
+    assert_eq!(
+        configs,
+        [
+            "homeassistant/binary_sensor/w1_12-000000000001/input_a/config",
+            "homeassistant/binary_sensor/w1_12-000000000002/input_a/config",
+            "homeassistant/binary_sensor/w1_12-000000000002/input_b/config",
+        ]
+    );
+    assert_eq!(find(&msgs, "w1/12-000000000002/input_b").payload, "ON");
+    assert!(!msgs
+        .iter()
+        .any(|msg| msg.topic == "w1/12-000000000001/input_b"));
+
+    let msgs = bridge.poll(&mut bus).unwrap();
+    assert!(!msgs.iter().any(|msg| msg.topic.ends_with("/config")));
+    assert_eq!(bridge.announcements().len(), 3);
+}
@@ -0,0 +1,240 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    proto::{
+        command::{self, SlaveId, W1NetlinkCommand},
+        connector::{NlConnectorMessage, CONNECTOR_MAX_MSG_SIZE},
+        message::{self, Exchange, W1NetlinkMessage},
+        Deserializable, Serializable, SerializeError, Truncated,
+    },
+    sim::SimBus,
+    transport::{Error, Transport},
+};
+
+fn encode<T: Serializable>(item: &T) -> Vec<u8> {
+    let mut buf = vec![0; item.buffer_len()];
+    item.serialize(&mut buf);
+    buf
+}
+
+fn slave_command() -> W1NetlinkMessage {
+    W1NetlinkMessage::SlaveCommand {
+        target: 0x1f3a_6c05_0000_0028,
+        cmds: vec![
+            W1NetlinkCommand::Reset,
+            W1NetlinkCommand::Write(vec![0xcc, 0x44]),
+            W1NetlinkCommand::Read(Some(vec![0x50, 0x05])),
+        ],
+    }
+}
+
+#[test]
+fn command_prefixes() {
+    let buf = encode(&W1NetlinkCommand::Write(vec![1, 2, 3]));
+    for len in 0..buf.len() {
+        assert!(W1NetlinkCommand::deserialize(&buf[..len]).is_err());
+    }
+    let (cmd, read) = W1NetlinkCommand::deserialize(&buf).unwrap();
+    assert!(matches!(cmd, W1NetlinkCommand::Write(data) if data == [1, 2, 3]));
+    assert_eq!(read, buf.len());
+}
+
+#[test]
+fn command_len_overrun() {
+    let mut buf = encode(&W1NetlinkCommand::Write(vec![1, 2, 3]));
+    buf[2] = 200;
+    match W1NetlinkCommand::deserialize(&buf) {
+        Err(command::DeserializeError::Truncated(t)) => assert_eq!(
+            t,
+            Truncated {
+                offset: W1NetlinkCommand::HEADER_LEN,
+                needed: 200,
+                available: 3,
+            }
+        ),
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn message_prefixes() {
+    let buf = encode(&slave_command());
+    for len in 0..buf.len() {
+        assert!(W1NetlinkMessage::deserialize(&buf[..len]).is_err());
+    }
+    let (msg, read) = W1NetlinkMessage::deserialize(&buf).unwrap();
+    assert_eq!(read, buf.len());
+    match msg {
+        W1NetlinkMessage::SlaveCommand { cmds, .. } => assert_eq!(cmds.len(), 3),
+        other => panic!("unexpected message {other:?}"),
+    }
+}
+
+#[test]
+fn message_command_offset() {
+    let mut buf = encode(&slave_command());
+    // the write command after the reset claims more data than the message holds
+    let write = W1NetlinkMessage::HEADER_LEN + W1NetlinkCommand::HEADER_LEN;
+    buf[write + 2] = 0xff;
+    match W1NetlinkMessage::deserialize(&buf) {
+        Err(message::DeserializeError::Command {
+            offset,
+            source: command::DeserializeError::Truncated(t),
+        }) => {
+            assert_eq!(offset, write);
+            assert_eq!(t.offset, W1NetlinkCommand::HEADER_LEN);
+            assert_eq!(t.needed, 0xff);
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+}
+
+#[test]
+fn message_trailing_data() {
+    // bytes after the announced length belong to the next message
+    let mut buf = encode(&W1NetlinkMessage::ListMasters(Some(vec![1])));
+    buf.extend([0xde, 0xad]);
+    let (msg, read) = W1NetlinkMessage::deserialize(&buf).unwrap();
+    assert_eq!(read, buf.len() - 2);
+    assert!(matches!(msg, W1NetlinkMessage::ListMasters(Some(ids)) if ids == [1]));
+}
+
+#[test]
+fn slave_add_command() {
+    let id = SlaveId::from_parts(0x28, 0x056c3a1f);
+    // W1_CMD_SLAVE_ADD with a slave id
+    let mut buf = vec![6, 0, 8, 0];
+    buf.extend(u64::from(id).to_ne_bytes());
+    let (cmd, len) = W1NetlinkCommand::deserialize(&buf).unwrap();
+    assert_eq!(cmd, W1NetlinkCommand::SlaveAdd(id));
+    assert_eq!(len, buf.len());
+    assert_eq!(encode(&cmd), buf);
+
+    buf[2] = 7;
+    assert!(matches!(
+        W1NetlinkCommand::deserialize(&buf[..11]),
+        Err(command::DeserializeError::InvalidLength(_))
+    ));
+
+    // W1_CMD_SEARCH with one and a half ids
+    let mut search = vec![2, 0, 12, 0];
+    search.extend([0; 12]);
+    let err = W1NetlinkCommand::deserialize(&search).unwrap_err();
+    assert_eq!(err.to_string(), "Cannot read slave id: Invalid length: 12");
+
+    let mut sim = SimBus::new();
+    let master = sim.add_master();
+    sim.attach(master, id, w1_netlink::sim::thermometer::Ds18b20::new(21.5));
+    while sim.next_event().is_some() {}
+    sim.master_command(master, vec![W1NetlinkCommand::SlaveRemove(id)])
+        .unwrap();
+    assert_eq!(
+        sim.next_event(),
+        Some(W1NetlinkMessage::SlaveEvent {
+            kind: w1_netlink::proto::message::EventKind::Remove,
+            target: id.into(),
+        })
+    );
+    assert!(matches!(
+        sim.master_command(master, vec![W1NetlinkCommand::SlaveRemove(id)]),
+        Err(Error::Status(22))
+    ));
+}
+
+#[test]
+fn size_limits() {
+    let write = W1NetlinkCommand::Write(vec![0; 70000]);
+    assert!(matches!(
+        write.to_bytes(),
+        Err(SerializeError::TooLong { len: 70000, .. })
+    ));
+
+    // fits the length fields but not into a connector message
+    let msg = W1NetlinkMessage::MasterCommand {
+        target: 1,
+        cmds: vec![W1NetlinkCommand::Write(vec![0; 10000]); 2],
+    };
+    assert!(msg.validate().is_ok());
+    let cmsg = NlConnectorMessage::new(1, [msg.clone()]);
+    assert!(matches!(
+        cmsg.validate(),
+        Err(SerializeError::TooLong {
+            max: CONNECTOR_MAX_MSG_SIZE,
+            ..
+        })
+    ));
+
+    // a search list cannot be split
+    let search = W1NetlinkMessage::MasterCommand {
+        target: 1,
+        cmds: vec![W1NetlinkCommand::Search(Some(vec![
+            SlaveId::new([0; 8]);
+            3000
+        ]))],
+    };
+    let mut bus = SimBus::new();
+    bus.add_master();
+    assert!(matches!(bus.transact(search), Err(Error::Serialize(_))));
+}
+
+#[test]
+fn buffer_size() {
+    let msg = slave_command();
+    let mut buf = vec![0; msg.buffer_len() + 1];
+    assert_eq!(
+        msg.try_serialize(&mut buf),
+        Err(SerializeError::BufferSize {
+            expected: msg.buffer_len(),
+            actual: msg.buffer_len() + 1,
+        })
+    );
+    assert_eq!(msg.to_bytes().unwrap(), encode(&msg));
+}
+
+#[test]
+fn display() {
+    let scratchpad = vec![0x50, 0x05, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0x1c];
+    let id = SlaveId::from_parts(0x28, 0x056c3a1f);
+    let request = W1NetlinkMessage::SlaveCommand {
+        target: id.into(),
+        cmds: vec![
+            W1NetlinkCommand::Reset,
+            W1NetlinkCommand::Write(vec![0xcc, 0x44]),
+            W1NetlinkCommand::Read(Some(vec![0; 9])),
+        ],
+    };
+    let request = NlConnectorMessage::new(3, [request]);
+    assert_eq!(
+        format!("{request:#}"),
+        "seq=3 SLAVE_CMD 28-0000056c3a1f: RESET; WRITE cc 44; READ 9"
+    );
+
+    let replies = [W1NetlinkMessage::SlaveCommand {
+        target: id.into(),
+        cmds: vec![W1NetlinkCommand::Read(Some(scratchpad.clone()))],
+    }];
+    assert_eq!(
+        Exchange::new(&request, &replies).to_string(),
+        "seq=3 SLAVE_CMD 28-0000056c3a1f: RESET; WRITE cc 44; \
+         READ 9 -> 50 05 4b 46 7f ff 0c 10 1c [crc ok]"
+    );
+
+    let mut garbled = scratchpad;
+    garbled[8] ^= 1;
+    assert_eq!(
+        W1NetlinkCommand::Read(Some(garbled)).to_string(),
+        "READ 9 -> 50 05 4b 46 7f ff 0c 10 1d"
+    );
+    let search = W1NetlinkMessage::MasterCommand {
+        target: 1,
+        cmds: vec![W1NetlinkCommand::Search(Some(vec![id]))],
+    };
+    assert_eq!(
+        search.to_string(),
+        "MASTER_CMD 1: SEARCH -> 28-0000056c3a1f"
+    );
+    assert_eq!(
+        W1NetlinkMessage::ListMasters(Some(vec![1, 2])).to_string(),
+        "LIST_MASTERS -> 1, 2"
+    );
+}
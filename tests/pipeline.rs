@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+use std::time::{Duration, Instant, SystemTime};
+
+use w1_netlink::{
+    device::{ds2413, thermometer::DS18B20_FAMILY},
+    pipeline::{Calibration, Pipeline, Reason, Smoothing},
+    proto::command::SlaveId,
+    reading::{Quantity, Reading},
+    scheduler::Scheduler,
+    sim::{self, SimBus},
+};
+
+fn id(serial: u64) -> SlaveId {
+    SlaveId::from_parts(DS18B20_FAMILY, serial)
+}
+
+fn temperature(rom: SlaveId, value: f64) -> Reading {
+    Reading {
+        timestamp: SystemTime::now(),
+        rom,
+        quantity: Quantity::Temperature,
+        channel: None,
+        value,
+    }
+}
+
+fn values(pipeline: &mut Pipeline, rom: SlaveId, raw: &[f64]) -> Vec<f64> {
+    let readings = raw.iter().map(|value| temperature(rom, *value));
+    let processed = pipeline.process(readings);
+    processed.readings.iter().map(|r| r.value).collect()
+}
+
+#[test]
+fn plausibility() {
+    let mut pipeline = Pipeline::new();
+    let processed = pipeline.process([
+        temperature(id(1), 85.0),
+        temperature(id(1), -127.0),
+        temperature(id(1), 130.0),
+        temperature(id(1), f64::NAN),
+        temperature(id(1), 21.5),
+    ]);
+    let accepted: Vec<_> = processed.readings.iter().map(|r| r.value).collect();
+    assert_eq!(accepted, [21.5]);
+    let reasons: Vec<_> = processed.rejected.iter().map(|r| r.reason).collect();
+    assert_eq!(
+        reasons,
+        [
+            Reason::PowerOn,
+            Reason::Disconnected,
+            Reason::OutOfRange(130.0),
+            Reason::NotANumber,
+        ]
+    );
+    assert_eq!(processed.rejected[0].reading.value, 85.0);
+
+    let mut pipeline = Pipeline::new()
+        .with_reject_markers(false)
+        .with_range(Quantity::Temperature, -200.0..=200.0);
+    assert_eq!(
+        values(&mut pipeline, id(1), &[85.0, -127.0]),
+        [85.0, -127.0]
+    );
+}
+
+#[test]
+fn calibration() {
+    let mut pipeline = Pipeline::new();
+    pipeline.set_calibration(id(1), Calibration::offset(-0.5).with_gain(2.0));
+    assert_eq!(values(&mut pipeline, id(1), &[20.0]), [39.5]);
+    assert_eq!(values(&mut pipeline, id(2), &[20.0]), [20.0]);
+    // the range applies to the calibrated value
+    assert!(values(&mut pipeline, id(1), &[70.0]).is_empty());
+
+    let level = Reading {
+        quantity: Quantity::Level,
+        value: 1.0,
+        ..temperature(id(1), 0.0)
+    };
+    assert_eq!(pipeline.process_one(level.clone()), Ok(level));
+}
+
+#[test]
+fn smoothing() {
+    let mut pipeline = Pipeline::new().with_smoothing(vec![Smoothing::Median(3)]);
+    assert_eq!(
+        values(&mut pipeline, id(1), &[20.0, 30.0, 21.0, 85.0, 22.0, 23.0]),
+        [20.0, 25.0, 21.0, 22.0, 22.0]
+    );
+
+    pipeline.set_smoothing(id(2), vec![Smoothing::Ema(0.5)]);
+    assert_eq!(
+        values(&mut pipeline, id(2), &[20.0, 22.0, 22.0]),
+        [20.0, 21.0, 21.5]
+    );
+    pipeline.forget(id(2));
+    assert_eq!(values(&mut pipeline, id(2), &[24.0]), [24.0]);
+
+    // stages run in order
+    let mut pipeline =
+        Pipeline::new().with_smoothing(vec![Smoothing::Median(3), Smoothing::Ema(0.5)]);
+    assert_eq!(
+        values(&mut pipeline, id(1), &[20.0, 40.0, 20.0]),
+        [20.0, 25.0, 22.5]
+    );
+}
+
+#[test]
+fn scheduler() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let fresh = id(1);
+    let switch = SlaveId::from_parts(ds2413::FAMILY, 2);
+    bus.attach(master, fresh, sim::thermometer::Ds18b20::new(85.0));
+    bus.attach(master, switch, sim::ds2413::Ds2413::new());
+
+    let mut pipeline = Pipeline::new();
+    pipeline.set_calibration(fresh, Calibration::offset(1.0));
+    let mut scheduler = Scheduler::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_pipeline(pipeline);
+    let now = Instant::now();
+    scheduler.discover(&mut bus, now).unwrap();
+
+    let report = scheduler.poll(&mut bus, now);
+    assert!(report.readings.iter().all(|r| r.rom == switch));
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].reason, Reason::PowerOn);
+}
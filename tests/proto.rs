@@ -0,0 +1,49 @@
+//! Codecs as available without the default `std` feature, run with
+//! `cargo test --no-default-features --test proto`.
+
+use w1_netlink::{
+    crc::crc8,
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        connector::NlConnectorMessage,
+        message::{W1NetlinkMessage, W1NetlinkMessageRef},
+        Deserializable, Serializable,
+    },
+};
+
+#[test]
+fn round_trip() {
+    let id: SlaveId = "28-0000056c3a1f".parse().unwrap();
+    assert_eq!(crc8(&id.bytes()[..7]), id.bytes()[7]);
+
+    let msg = W1NetlinkMessage::SlaveCommand {
+        target: id.into(),
+        cmds: vec![
+            W1NetlinkCommand::Reset,
+            W1NetlinkCommand::Write(vec![0xCC, 0x44]),
+        ],
+    };
+    let cmsg = NlConnectorMessage::new(7, [msg.clone()]);
+    let buf = cmsg.to_bytes().unwrap();
+    assert_eq!(buf.len(), cmsg.buffer_len());
+
+    let (decoded, len) = NlConnectorMessage::<W1NetlinkMessage>::deserialize(&buf).unwrap();
+    assert_eq!(len, buf.len());
+    assert_eq!(decoded.header.seq, 7);
+    assert_eq!(decoded.payload.len(), 1);
+    assert_eq!(decoded.payload[0], msg);
+
+    let (view, _) =
+        W1NetlinkMessageRef::parse(&buf[NlConnectorMessage::<W1NetlinkMessage>::HEADER_LEN..])
+            .unwrap();
+    assert_eq!(view.try_into_owned().unwrap(), msg);
+}
+
+#[test]
+fn truncated() {
+    let cmsg = NlConnectorMessage::new(1, [W1NetlinkMessage::ListMasters(None)]);
+    let buf = cmsg.to_bytes().unwrap();
+    for len in 0..buf.len() {
+        assert!(NlConnectorMessage::<W1NetlinkMessage>::deserialize(&buf[..len]).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "registry")]
+
+use std::time::Instant;
+
+use w1_netlink::{
+    device::thermometer::DS18B20_FAMILY,
+    proto::command::SlaveId,
+    registry::{DeviceInfo, Error, Finding, Registry},
+    sim::{thermometer::Ds18b20, SimBus},
+    topology::{Change, Topology},
+};
+
+fn id(serial: u64) -> SlaveId {
+    SlaveId::from_parts(DS18B20_FAMILY, serial)
+}
+
+const TOML: &str = r#"
+[devices.28-000000000001]
+name = "Kitchen"
+location = "Ground floor"
+offset = -0.25
+master = 1
+
+[devices.28-000000000002]
+name = "Attic"
+"#;
+
+const YAML: &str = "
+devices:
+  28-000000000001:
+    name: Kitchen
+    location: Ground floor
+    offset: -0.25
+    master: 1
+  28-000000000002:
+    name: Attic
+";
+
+#[test]
+fn formats() {
+    let registry = Registry::from_toml(TOML).unwrap();
+    assert_eq!(
+        registry.get(id(1)),
+        Some(&DeviceInfo {
+            name: Some("Kitchen".to_string()),
+            location: Some("Ground floor".to_string()),
+            offset: Some(-0.25),
+            gain: None,
+            master: Some(1),
+        })
+    );
+    assert_eq!(registry.name(id(2)), Some("Attic"));
+    assert_eq!(registry.get(id(3)), None);
+    assert_eq!(Registry::from_yaml(YAML).unwrap(), registry);
+
+    assert_eq!(
+        Registry::from_toml(&registry.to_toml().unwrap()).unwrap(),
+        registry
+    );
+    assert_eq!(
+        Registry::from_yaml(&registry.to_yaml().unwrap()).unwrap(),
+        registry
+    );
+
+    let dir = std::env::temp_dir().join(format!("w1-registry-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["devices.toml", "devices.yml"] {
+        registry.save(dir.join(file)).unwrap();
+        assert_eq!(Registry::load(dir.join(file)).unwrap(), registry);
+    }
+    assert!(matches!(
+        registry.save(dir.join("devices.json")),
+        Err(Error::Format(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(Registry::from_toml("[devices.28-000000000001]\nnmae = \"typo\"").is_err());
+    assert!(Registry::from_toml("[devices.not-a-rom]").is_err());
+}
+
+#[test]
+fn aliases() {
+    let mut registry = Registry::from_toml(TOML).unwrap();
+    registry.insert(id(3), DeviceInfo::default());
+    let aliases = registry.aliases();
+    assert_eq!(aliases.len(), 2);
+    assert_eq!(aliases[&id(1)], "Kitchen");
+}
+
+#[test]
+fn check() {
+    let mut bus = SimBus::new();
+    let first = bus.add_master();
+    let second = bus.add_master();
+    assert_eq!(first, 1);
+    bus.attach(second, id(1), Ds18b20::new(20.0));
+    bus.attach(first, id(3), Ds18b20::new(20.0));
+    while bus.next_event().is_some() {}
+
+    let registry = Registry::from_toml(TOML).unwrap();
+    let mut topology = Topology::new();
+    topology.reconcile(&mut bus, Instant::now()).unwrap();
+    assert_eq!(
+        registry.check(&topology.snapshot()),
+        [
+            Finding::Unknown { id: id(3) },
+            Finding::Misplaced {
+                id: id(1),
+                expected: first,
+                actual: second
+            },
+            Finding::Missing { id: id(2) },
+        ]
+    );
+    assert_eq!(
+        Finding::Misplaced {
+            id: id(1),
+            expected: 1,
+            actual: 2
+        }
+        .to_string(),
+        "device 28-000000000001 on master 2, expected 1"
+    );
+
+    let added = |id| Change::SlaveAdded { master: first, id };
+    let removed = |id| Change::SlaveRemoved { master: first, id };
+    assert_eq!(registry.check_change(&added(id(1))), None);
+    assert_eq!(
+        registry.check_change(&added(id(4))),
+        Some(Finding::Unknown { id: id(4) })
+    );
+    assert_eq!(
+        registry.check_change(&removed(id(2))),
+        Some(Finding::Missing { id: id(2) })
+    );
+    assert_eq!(registry.check_change(&removed(id(4))), None);
+    assert_eq!(registry.check_change(&Change::MasterAdded(3)), None);
+}
@@ -0,0 +1,189 @@
+#![cfg(feature = "std")]
+
+use std::{
+    collections::VecDeque,
+    io,
+    time::{Duration, Instant},
+};
+
+use w1_netlink::{
+    device::thermometer::{self, DS18B20_FAMILY},
+    proto::{command::SlaveId, message::W1NetlinkMessage},
+    retry::{classify, classify_slave, ErrorClass, Retry, RetryPolicy},
+    scheduler::Scheduler,
+    sim::{thermometer::Ds18b20, SimBus},
+    transport::{Error, Transport},
+};
+
+/// Fails the messages after the first `pass` with the queued errors.
+struct Flaky {
+    bus: SimBus,
+    pass: usize,
+    errors: VecDeque<Error>,
+    messages: usize,
+}
+
+impl Flaky {
+    fn new(bus: SimBus, errors: impl IntoIterator<Item = Error>) -> Self {
+        Self {
+            bus,
+            pass: 0,
+            errors: errors.into_iter().collect(),
+            messages: 0,
+        }
+    }
+}
+
+impl Transport for Flaky {
+    fn transact(&mut self, msg: W1NetlinkMessage) -> Result<Vec<W1NetlinkMessage>, Error> {
+        self.messages += 1;
+        if self.pass > 0 {
+            self.pass -= 1;
+            return self.bus.transact(msg);
+        }
+        match self.errors.pop_front() {
+            Some(err) => Err(err),
+            None => self.bus.transact(msg),
+        }
+    }
+}
+
+fn sim() -> (SimBus, SlaveId) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(DS18B20_FAMILY, 1);
+    bus.attach(master, id, Ds18b20::new(21.5));
+    (bus, id)
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new().with_backoff(Duration::ZERO)
+}
+
+#[test]
+fn classification() {
+    use ErrorClass::*;
+    let cases = [
+        (
+            Error::Crc {
+                expected: 1,
+                actual: 2,
+            },
+            Transient,
+        ),
+        (Error::NoDevice, Transient),
+        (Error::Status(5), Transient),
+        // no presence pulse on a slave command and on a master reset
+        (Error::Status(1), Transient),
+        (Error::Status(0xFF), Transient),
+        (Error::Status(19), Permanent),
+        (Error::Status(22), Permanent),
+        (Error::Device("not a thermometer"), Permanent),
+        (io::Error::from(io::ErrorKind::WouldBlock).into(), Transient),
+        (
+            io::Error::from(io::ErrorKind::PermissionDenied).into(),
+            Permanent,
+        ),
+    ];
+    for (err, class) in cases {
+        assert_eq!(classify(&err), class, "{err}");
+    }
+}
+
+#[test]
+fn backoff() {
+    let policy = RetryPolicy::new()
+        .with_backoff(Duration::from_millis(10))
+        .with_max_backoff(Duration::from_millis(50));
+    let delays: Vec<_> = (0..5)
+        .map(|retry| policy.delay(retry).as_millis())
+        .collect();
+    assert_eq!(delays, [10, 20, 40, 50, 50]);
+    assert_eq!(RetryPolicy::new().with_attempts(0).attempts(), 1);
+}
+
+#[test]
+fn run() {
+    let (bus, id) = sim();
+    let mut t = Flaky::new(bus, [Error::NoDevice, Error::Status(5)]);
+    let pad = policy().run(|| thermometer::read_scratchpad(&mut t, id));
+    assert!(pad.is_ok());
+    assert_eq!(t.messages, 3);
+
+    // permanent errors are returned right away
+    let (bus, id) = sim();
+    let mut t = Flaky::new(bus, [Error::Status(19)]);
+    let pad = policy().run(|| thermometer::read_scratchpad(&mut t, id));
+    assert!(matches!(pad, Err(Error::Status(19))));
+    assert_eq!(t.messages, 1);
+
+    // the last error once all attempts are used up
+    let (bus, id) = sim();
+    let mut t = Flaky::new(bus, [Error::NoDevice, Error::NoDevice, Error::Status(5)]);
+    let pad = policy().run(|| thermometer::read_scratchpad(&mut t, id));
+    assert!(matches!(pad, Err(Error::Status(5))));
+    assert_eq!(t.messages, 3);
+}
+
+#[test]
+fn unplugged() {
+    let (mut bus, id) = sim();
+    bus.set_connected(id, false);
+    let err = thermometer::read_scratchpad(&mut bus, id).unwrap_err();
+    let class = classify_slave(&mut bus, id, &err);
+    assert_eq!(class, ErrorClass::Transient, "{err}");
+
+    // gone from the w1 core
+    bus.detach(id);
+    let err = thermometer::read_scratchpad(&mut bus, id).unwrap_err();
+    let class = classify_slave(&mut bus, id, &err);
+    assert_eq!(class, ErrorClass::Permanent, "{err}");
+}
+
+#[test]
+fn missed_presence() {
+    // a registered slave missing the presence pulse when selected
+    let (bus, id) = sim();
+    let mut t = Flaky::new(bus, [Error::Status(19)]);
+    let pad = policy().run_slave(&mut t, id, |t| thermometer::read_scratchpad(t, id));
+    assert!(pad.is_ok());
+    // the failed read, listing masters and slaves and the read again
+    assert_eq!(t.messages, 4);
+
+    let (bus, id) = sim();
+    let mut t = Retry::new(Flaky::new(bus, [Error::Status(19)]), policy());
+    assert!(thermometer::read_scratchpad(&mut t, id).is_ok());
+
+    let (bus, _) = sim();
+    let other = SlaveId::from_parts(DS18B20_FAMILY, 2);
+    let mut t = Flaky::new(bus, [Error::Status(19)]);
+    let pad = policy().run_slave(&mut t, other, |t| thermometer::read_scratchpad(t, other));
+    assert!(matches!(pad, Err(Error::Status(19))));
+}
+
+#[test]
+fn transport() {
+    let (bus, _) = sim();
+    let mut t = Retry::new(Flaky::new(bus, [Error::Status(16)]), policy());
+    assert_eq!(t.list_masters().unwrap(), [1]);
+    assert_eq!(t.into_inner().messages, 2);
+}
+
+#[test]
+fn scheduler() {
+    let (bus, id) = sim();
+    let mut t = Flaky::new(bus, []);
+    let now = Instant::now();
+    let mut scheduler = Scheduler::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_retry(policy());
+    scheduler.discover(&mut t, now).unwrap();
+
+    // the conversion passes, the first two reads fail
+    t.pass = 1;
+    t.errors.extend([Error::NoDevice, Error::Status(5)]);
+    let report = scheduler.poll(&mut t, now);
+    assert!(report.failures.is_empty(), "{:?}", report.failures);
+    assert_eq!(report.readings.len(), 1);
+    assert_eq!(report.readings[0].rom, id);
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "std")]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use w1_netlink::{
+    device::{ds2413, thermometer::DS18B20_FAMILY},
+    proto::command::SlaveId,
+    reading::Reading,
+    scheduler::{Schedule, Scheduler},
+    sim::{self, SimBus},
+    sink::{self, Sink},
+};
+
+const SECOND: Duration = Duration::from_secs(1);
+
+/// Keeps the readings of every poll.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<Vec<Reading>>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, readings: &[Reading]) -> Result<(), sink::Error> {
+        self.0.lock().unwrap().push(readings.to_vec());
+        Ok(())
+    }
+}
+
+fn roms(readings: &[Reading]) -> Vec<SlaveId> {
+    let mut roms: Vec<_> = readings.iter().map(|r| r.rom).collect();
+    roms.dedup();
+    roms
+}
+
+#[test]
+fn intervals() {
+    let mut bus = SimBus::new();
+    let first = bus.add_master();
+    let second = bus.add_master();
+    let fast = SlaveId::from_parts(DS18B20_FAMILY, 1);
+    let slow = SlaveId::from_parts(DS18B20_FAMILY, 2);
+    let switch = SlaveId::from_parts(ds2413::FAMILY, 3);
+    let unknown = SlaveId::from_parts(0x01, 4);
+    bus.attach(first, fast, sim::thermometer::Ds18b20::new(20.0));
+    bus.attach(first, slow, sim::thermometer::Ds18b20::new(21.0));
+    bus.attach(first, unknown, sim::ds2413::Ds2413::new());
+    bus.attach(second, switch, sim::ds2413::Ds2413::new());
+
+    let sink = Collect::default();
+    let mut scheduler = Scheduler::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_batch_window(2 * SECOND)
+        .with_default_schedule(Schedule::every(30 * SECOND))
+        .with_sink(sink.clone());
+    scheduler.set_schedule(fast, Schedule::every(10 * SECOND));
+    scheduler.set_schedule(slow, Schedule::every(11 * SECOND));
+    scheduler.set_schedule(switch, Schedule::every(5 * SECOND));
+
+    let t0 = Instant::now();
+    scheduler.discover(&mut bus, t0).unwrap();
+    assert_eq!(scheduler.devices(first).collect::<Vec<_>>(), [fast, slow]);
+    assert_eq!(scheduler.devices(second).collect::<Vec<_>>(), [switch]);
+    assert_eq!(scheduler.next_due(), Some(t0));
+
+    let report = scheduler.poll(&mut bus, t0);
+    assert!(report.failures.is_empty() && report.bus_failures.is_empty());
+    assert_eq!(roms(&report.readings), [fast, slow, switch]);
+    assert_eq!(report.readings[0].value, 20.0);
+    assert_eq!(scheduler.next_due(), Some(t0 + 5 * SECOND));
+
+    let report = scheduler.poll(&mut bus, t0 + 5 * SECOND);
+    assert_eq!(roms(&report.readings), [switch]);
+
+    // nothing due yet
+    let report = scheduler.poll(&mut bus, t0 + 8 * SECOND);
+    assert!(report.readings.is_empty());
+
+    // the thermometer due at 11 s shares the conversion
+    let report = scheduler.poll(&mut bus, t0 + 10 * SECOND);
+    assert_eq!(roms(&report.readings), [fast, slow, switch]);
+    assert_eq!(scheduler.next_due(), Some(t0 + 15 * SECOND));
+
+    let polls = sink.0.lock().unwrap();
+    assert_eq!(polls.len(), 4);
+    assert_eq!(roms(&polls[0]), [fast, slow, switch]);
+
+    let stats = scheduler.stats(t0 + 10 * SECOND);
+    let first = stats[&first];
+    assert_eq!(first.searches, 1);
+    assert_eq!(first.conversions, 2);
+    assert_eq!(first.reads, 4);
+    assert_eq!((first.errors, first.missed_deadlines), (0, 0));
+    assert_eq!(first.elapsed, 10 * SECOND);
+    assert!(first.utilization() > 0.0 && first.utilization() < 1.0);
+    assert_eq!(stats[&second].conversions, 0);
+    assert_eq!(stats[&second].reads, 3);
+}
+
+#[test]
+fn deadlines_and_failures() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let a = SlaveId::from_parts(DS18B20_FAMILY, 1);
+    let b = SlaveId::from_parts(DS18B20_FAMILY, 2);
+    bus.attach(master, a, sim::thermometer::Ds18b20::new(20.0));
+    bus.attach(master, b, sim::thermometer::Ds18b20::new(21.0));
+
+    let mut scheduler = Scheduler::new()
+        .with_conversion_time(Duration::ZERO)
+        .with_batch_window(Duration::ZERO)
+        .with_default_schedule(Schedule::every(10 * SECOND).with_deadline(SECOND));
+    let t0 = Instant::now();
+    scheduler.discover(&mut bus, t0).unwrap();
+    scheduler.poll(&mut bus, t0);
+
+    // late by more than the deadline, next poll keeps no stale phase
+    bus.detach(b);
+    let report = scheduler.poll(&mut bus, t0 + 25 * SECOND);
+    assert_eq!(roms(&report.readings), [a]);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].0, b);
+    assert_eq!(scheduler.next_due(), Some(t0 + 35 * SECOND));
+    let stats = scheduler.stats(t0 + 25 * SECOND)[&master];
+    assert_eq!((stats.errors, stats.missed_deadlines), (1, 2));
+
+    scheduler.discover(&mut bus, t0 + 25 * SECOND).unwrap();
+    assert_eq!(scheduler.devices(master).collect::<Vec<_>>(), [a]);
+    scheduler.reset_stats(t0 + 25 * SECOND);
+    assert_eq!(scheduler.stats(t0 + 25 * SECOND)[&master].reads, 0);
+}
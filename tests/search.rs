@@ -0,0 +1,125 @@
+#![cfg(feature = "std")]
+
+use w1_netlink::{
+    device::{
+        ds28ea00,
+        eeprom::{Model, DS2433_FAMILY},
+    },
+    proto::command::{SlaveId, W1NetlinkCommand},
+    search::{verify, Search},
+    sim::{self, SimBus},
+    transport::Transport,
+};
+
+fn bus() -> (SimBus, u32, Vec<SlaveId>) {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let thermometers = [0x5c3a1f, 0x5c3a1e, 0xa00001];
+    let eeproms = [0x1234, 0x8000_0000_0001];
+    let mut ids = Vec::new();
+    for serial in thermometers {
+        let id = SlaveId::from_parts(ds28ea00::FAMILY, serial);
+        bus.attach(master, id, sim::ds28ea00::Ds28ea00::new(20.0));
+        ids.push(id);
+    }
+    for serial in eeproms {
+        let id = SlaveId::from_parts(DS2433_FAMILY, serial);
+        bus.attach(master, id, sim::eeprom::Eeprom::new(Model::Ds2433));
+        ids.push(id);
+    }
+    (bus, master, ids)
+}
+
+fn kernel_search(bus: &mut SimBus, master: u32) -> Vec<SlaveId> {
+    let replies = bus
+        .master_command(master, vec![W1NetlinkCommand::Search(None)])
+        .unwrap();
+    match &replies[..] {
+        [W1NetlinkCommand::Search(Some(ids))] => ids.clone(),
+        other => panic!("unexpected replies {other:?}"),
+    }
+}
+
+#[test]
+fn matches_kernel_search() {
+    let (mut bus, master, mut ids) = bus();
+
+    let found = Search::new(master).collect(&mut bus).unwrap();
+    assert_eq!(found, kernel_search(&mut bus, master));
+
+    let mut sorted = found.clone();
+    sorted.sort();
+    ids.sort();
+    assert_eq!(sorted, ids);
+}
+
+#[test]
+fn family() {
+    let (mut bus, master, ids) = bus();
+
+    let found = Search::family(master, DS2433_FAMILY)
+        .collect(&mut bus)
+        .unwrap();
+    assert_eq!(found.len(), 2);
+    assert!(found.iter().all(|id| id.family() == DS2433_FAMILY));
+    assert!(found.iter().all(|id| ids.contains(id)));
+
+    let found = Search::family(master, 0x10).collect(&mut bus).unwrap();
+    assert!(found.is_empty());
+}
+
+#[test]
+fn skip_family() {
+    let (mut bus, master, _) = bus();
+
+    let mut search = Search::new(master);
+    let first = search.next(&mut bus).unwrap().unwrap();
+    search.skip_family();
+    while let Some(id) = search.next(&mut bus).unwrap() {
+        assert_ne!(id.family(), first.family());
+    }
+}
+
+#[test]
+fn verify_rom() {
+    let (mut bus, master, ids) = bus();
+
+    for id in &ids {
+        assert!(verify(&mut bus, master, *id).unwrap());
+    }
+    let absent = SlaveId::from_parts(ds28ea00::FAMILY, 0x5c3a1d);
+    assert!(!verify(&mut bus, master, absent).unwrap());
+
+    bus.detach(ids[0]);
+    assert!(!verify(&mut bus, master, ids[0]).unwrap());
+}
+
+#[test]
+fn resume() {
+    let (mut bus, master, _) = bus();
+    let all = Search::new(master).collect(&mut bus).unwrap();
+
+    let mut search = Search::new(master);
+    search.next(&mut bus).unwrap();
+    search.next(&mut bus).unwrap();
+    assert_eq!(search.last_rom(), all[1]);
+    assert!(search.last_discrepancy() > 0);
+
+    let resumed = Search::resume(master, search.last_rom(), search.last_discrepancy());
+    assert_eq!(resumed.collect(&mut bus).unwrap(), all[2..]);
+
+    let mut last = Search::new(master);
+    while last.next(&mut bus).unwrap().is_some() {}
+    assert!(last.is_done());
+    assert_eq!(last.last_discrepancy(), 0);
+}
+
+#[test]
+fn empty_bus() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+
+    let mut search = Search::new(master);
+    assert_eq!(search.next(&mut bus).unwrap(), None);
+    assert!(search.is_done());
+}
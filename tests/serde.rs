@@ -0,0 +1,130 @@
+#![cfg(all(feature = "std", feature = "serde"))]
+
+use w1_netlink::{
+    alarm::{AlarmReport, Bound, ThermometerLimits},
+    device::{
+        ds2450::{AlarmLimits, Channel},
+        thermometer::{Scratchpad, Temperature},
+    },
+    proto::{
+        command::{SlaveId, W1NetlinkCommand},
+        connector::NlConnectorMessage,
+        message::{EventKind, W1NetlinkMessage},
+    },
+};
+
+fn rom() -> SlaveId {
+    "28-0000056c3a1f".parse().unwrap()
+}
+
+#[test]
+fn message_json() {
+    let msg = W1NetlinkMessage::SlaveCommand {
+        target: rom().into(),
+        cmds: vec![
+            W1NetlinkCommand::Reset,
+            W1NetlinkCommand::Write(vec![0xbe]),
+            W1NetlinkCommand::Read(Some(vec![0x50, 0x05])),
+            W1NetlinkCommand::Search(None),
+        ],
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    assert_eq!(
+        json,
+        r#"{"SlaveCommand":{"target":"28-0000056c3a1f","cmds":["Reset",{"Write":[190]},{"Read":[80,5]},{"Search":null}]}}"#
+    );
+    let decoded: W1NetlinkMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, msg);
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+}
+
+#[test]
+fn connector_json() {
+    let events = [
+        W1NetlinkMessage::SlaveEvent {
+            kind: EventKind::Add,
+            target: rom().into(),
+        },
+        W1NetlinkMessage::MasterCommand {
+            target: 1,
+            cmds: vec![W1NetlinkCommand::Search(Some(vec![rom()]))],
+        },
+    ];
+    let cmsg = NlConnectorMessage::new(3, events.clone());
+    let json = serde_json::to_string(&cmsg).unwrap();
+    let decoded: NlConnectorMessage<W1NetlinkMessage> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.header, cmsg.header);
+    assert_eq!(decoded.payload, events);
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+}
+
+#[test]
+fn slave_id() {
+    assert_eq!(
+        serde_json::to_string(&rom()).unwrap(),
+        r#""28-0000056c3a1f""#
+    );
+    assert!(serde_json::from_str::<SlaveId>(r#""28-56c3a1f""#).is_err());
+    assert!(serde_json::from_str::<SlaveId>("1").is_err());
+    assert!(serde_json::from_str::<SlaveId>(r#""28-0000056c3a1f.a""#).is_err());
+
+    // a wrong CRC is kept, also in message targets
+    let mut bytes = rom().bytes();
+    bytes[7] ^= 0xFF;
+    let bad = SlaveId::new(bytes);
+    let json = serde_json::to_string(&bad).unwrap();
+    assert_eq!(json, format!(r#""28-0000056c3a1f.{:02x}""#, bad.crc()));
+    assert_eq!(serde_json::from_str::<SlaveId>(&json).unwrap(), bad);
+    let msg = W1NetlinkMessage::SlaveCommand {
+        target: bad.into(),
+        cmds: vec![W1NetlinkCommand::Reset],
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    assert_eq!(
+        serde_json::from_str::<W1NetlinkMessage>(&json).unwrap(),
+        msg
+    );
+}
+
+#[test]
+fn readings() {
+    let temperature = Temperature::from_raw(-0x1b1);
+    let json = serde_json::to_string(&temperature).unwrap();
+    assert_eq!(json, "-27.0625");
+    assert_eq!(
+        serde_json::from_str::<Temperature>(&json).unwrap(),
+        temperature
+    );
+
+    let pad = Scratchpad([0x50, 0x05, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0x1c]);
+    let json = serde_json::to_string(&pad).unwrap();
+    assert_eq!(serde_json::from_str::<Scratchpad>(&json).unwrap(), pad);
+
+    let reports = vec![
+        AlarmReport::Temperature {
+            id: rom(),
+            temperature: Temperature::from_celsius(85.0),
+            limits: ThermometerLimits { low: -10, high: 70 },
+            bound: Bound::High,
+        },
+        AlarmReport::Voltage {
+            id: SlaveId::from_parts(0x20, 1),
+            channel: Channel::C,
+            raw: 0x8000,
+            volts: 2.56,
+            bound: Bound::Low,
+        },
+    ];
+    let json = serde_json::to_string(&reports).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Vec<AlarmReport>>(&json).unwrap(),
+        reports
+    );
+
+    let limits = AlarmLimits {
+        low: Some(0x10),
+        high: None,
+    };
+    let json = serde_json::to_string(&limits).unwrap();
+    assert_eq!(json, r#"{"low":16,"high":null}"#);
+}
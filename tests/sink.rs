@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use w1_netlink::{
+    device::{ds2413, thermometer::DS18B20_FAMILY},
+    proto::command::SlaveId,
+    reading::{read_device, Quantity, Reading},
+    sim::{self, SimBus},
+    sink::{Aliases, Csv, Error, InfluxHttp, LineProtocol, Sink},
+};
+
+fn readings() -> Vec<Reading> {
+    let timestamp = UNIX_EPOCH + Duration::from_millis(1_760_788_800_250);
+    let thermometer = SlaveId::from_parts(DS18B20_FAMILY, 0x056c3a1f);
+    let switch = SlaveId::from_parts(ds2413::FAMILY, 1);
+    vec![
+        Reading {
+            timestamp,
+            rom: thermometer,
+            quantity: Quantity::Temperature,
+            channel: None,
+            value: 21.5,
+        },
+        Reading {
+            timestamp,
+            rom: switch,
+            quantity: Quantity::Level,
+            channel: Some("B"),
+            value: 0.0,
+        },
+    ]
+}
+
+fn aliases() -> Aliases {
+    let id = SlaveId::from_parts(DS18B20_FAMILY, 0x056c3a1f);
+    Aliases::from([(id, "Living room, north".to_string())])
+}
+
+#[test]
+fn line_protocol() {
+    let mut sink = LineProtocol::new(Vec::new()).with_aliases(aliases());
+    sink.write(&readings()).unwrap();
+    assert_eq!(
+        String::from_utf8(sink.into_inner()).unwrap(),
+        "temperature,alias=Living\\ room\\,\\ north,family=DS18B20,rom=28-0000056c3a1f \
+         value=21.5 1760788800250000000\n\
+         level,channel=B,family=DS2413,rom=3a-000000000001 value=0 1760788800250000000\n"
+    );
+}
+
+#[test]
+fn from_devices() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    let id = SlaveId::from_parts(ds2413::FAMILY, 1);
+    bus.attach(master, id, sim::ds2413::Ds2413::new());
+    bus.device_mut::<sim::ds2413::Ds2413>(id).unwrap().inputs[1] = false;
+
+    let start = SystemTime::now();
+    let readings = read_device(&mut bus, id).unwrap();
+    assert_eq!(readings.len(), 4);
+    assert!(readings.iter().all(|r| r.rom == id && r.timestamp >= start));
+    assert_eq!(readings[0].channel, Some("A"));
+    assert_eq!(readings[0].value, 1.0);
+    assert_eq!(readings[1].channel, Some("B"));
+    assert_eq!(readings[1].value, 0.0);
+    // both output transistors off
+    assert_eq!(readings[3].quantity, Quantity::Output);
+    assert_eq!(readings[3].channel, Some("B"));
+    assert_eq!(readings[3].value, 0.0);
+}
+
+#[test]
+fn csv_rotation() {
+    let dir = std::env::temp_dir().join(format!("w1-sink-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("w1.csv");
+
+    let mut sink = Csv::new(&path)
+        .with_max_size(100)
+        .with_max_files(2)
+        .with_aliases(aliases());
+    for _ in 0..4 {
+        sink.write(&readings()).unwrap();
+    }
+    let current = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        current,
+        "timestamp,rom,alias,quantity,channel,value,unit\n\
+         2025-10-18T12:00:00.250Z,28-0000056c3a1f,\"Living room, north\",temperature,,21.5,°C\n\
+         2025-10-18T12:00:00.250Z,3a-000000000001,,level,B,0,\n"
+    );
+    assert_eq!(fs::read_to_string(dir.join("w1.csv.1")).unwrap(), current);
+    assert!(dir.join("w1.csv.2").exists());
+    assert!(!dir.join("w1.csv.3").exists());
+
+    // after a restart the full file is rotated before writing to it
+    drop(sink);
+    let mut sink = Csv::new(&path).with_max_size(100).with_max_files(2);
+    sink.write(&readings()[1..]).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("w1.csv.1")).unwrap(), current);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "timestamp,rom,alias,quantity,channel,value,unit\n\
+         2025-10-18T12:00:00.250Z,3a-000000000001,,level,B,0,\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn influx_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for status in ["204 No Content", "401 Unauthorized"] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    len = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").unwrap();
+            requests.push((head, String::from_utf8(body).unwrap()));
+        }
+        requests
+    });
+
+    let mut sink = InfluxHttp::new(&addr, "/api/v2/write?bucket=w1").with_token("secret");
+    sink.write(&readings()).unwrap();
+    assert!(matches!(
+        sink.write(&readings()),
+        Err(Error::Http(status)) if status == "HTTP/1.1 401 Unauthorized"
+    ));
+    // nothing to send
+    sink.write(&[]).unwrap();
+
+    let requests = server.join().unwrap();
+    let (head, body) = &requests[0];
+    assert!(head.starts_with("POST /api/v2/write?bucket=w1 HTTP/1.1\r\n"));
+    assert!(head.contains("Authorization: Token secret\r\n"));
+    assert_eq!(body.lines().count(), 2);
+    assert!(body.starts_with("temperature,family=DS18B20,rom=28-0000056c3a1f value=21.5 "));
+}
@@ -0,0 +1,75 @@
+//! Requests to a kernel without the w1 core loaded are never answered. These
+//! tests pass trivially where the w1 core answers or netlink sockets cannot
+//! be opened.
+
+#![cfg(feature = "std")]
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use w1_netlink::{client::W1Client, transport::Error, transport::Transport};
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+fn is_timeout<T>(result: &Result<T, Error>) -> bool {
+    matches!(result, Err(Error::Io(err)) if err.kind() == io::ErrorKind::TimedOut)
+}
+
+#[test]
+fn blocking() {
+    let Ok(client) = W1Client::new() else {
+        return;
+    };
+    let mut client = client.with_timeout(TIMEOUT);
+    let start = Instant::now();
+    let result = client.list_masters();
+    if result.is_ok() {
+        return;
+    }
+    assert!(is_timeout(&result), "{result:?}");
+    assert!(start.elapsed() >= TIMEOUT);
+    assert!(start.elapsed() < TIMEOUT * 10);
+
+    // the next request waits again instead of failing right away
+    let start = Instant::now();
+    assert!(is_timeout(&client.list_masters()));
+    assert!(start.elapsed() >= TIMEOUT);
+}
+
+#[cfg(feature = "tokio")]
+mod async_client {
+    use w1_netlink::{async_client::AsyncW1Client, proto::message::W1NetlinkMessage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn timeout() {
+        let Ok(client) = AsyncW1Client::new() else {
+            return;
+        };
+        let client = client.with_timeout(TIMEOUT);
+        let start = Instant::now();
+        let result = client.transact(W1NetlinkMessage::ListMasters(None)).await;
+        if result.is_ok() {
+            return;
+        }
+        assert!(is_timeout(&result), "{result:?}");
+        assert!(start.elapsed() >= TIMEOUT);
+        assert_eq!(client.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancel() {
+        let Ok(client) = AsyncW1Client::new() else {
+            return;
+        };
+        let request = client.transact(W1NetlinkMessage::ListMasters(None));
+        if tokio::time::timeout(TIMEOUT, request).await.is_ok() {
+            return;
+        }
+        // dropping the request unregistered it
+        assert_eq!(client.pending(), 0);
+    }
+}
@@ -0,0 +1,133 @@
+#![cfg(feature = "std")]
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use w1_netlink::{
+    device::thermometer::DS18B20_FAMILY,
+    proto::{command::SlaveId, message::EventKind, message::W1NetlinkMessage},
+    scheduler::Scheduler,
+    sim::{thermometer::Ds18b20, SimBus},
+    topology::{Change, Topology},
+    transport::{is_overflow, Error},
+};
+
+fn id(serial: u64) -> SlaveId {
+    SlaveId::from_parts(DS18B20_FAMILY, serial)
+}
+
+/// Applies all pending events of the simulation.
+fn follow(bus: &mut SimBus, topology: &mut Topology) -> Vec<Change> {
+    let mut changes = Vec::new();
+    while let Some(event) = bus.next_event() {
+        changes.extend(topology.handle_event(bus, &event).unwrap());
+    }
+    changes
+}
+
+#[test]
+fn seed_and_events() {
+    let mut bus = SimBus::new();
+    let first = bus.add_master();
+    bus.attach(first, id(1), Ds18b20::new(20.0));
+    while bus.next_event().is_some() {}
+
+    let mut topology = Topology::new();
+    let changes = topology.subscribe();
+    assert_eq!(
+        topology.seed(&mut bus).unwrap(),
+        [
+            Change::MasterAdded(first),
+            Change::SlaveAdded {
+                master: first,
+                id: id(1)
+            },
+        ]
+    );
+
+    let second = bus.add_master();
+    bus.attach(second, id(2), Ds18b20::new(20.0));
+    bus.detach(id(1));
+    assert_eq!(
+        follow(&mut bus, &mut topology),
+        [
+            Change::MasterAdded(second),
+            Change::SlaveAdded {
+                master: second,
+                id: id(2)
+            },
+            Change::SlaveRemoved {
+                master: first,
+                id: id(1)
+            },
+        ]
+    );
+    let snapshot = topology.snapshot();
+    assert_eq!(snapshot.masters().collect::<Vec<_>>(), [first, second]);
+    assert_eq!(snapshot.slaves(first).count(), 0);
+    assert_eq!(snapshot.master_of(id(2)), Some(second));
+
+    // known devices and removed masters
+    let event = W1NetlinkMessage::SlaveEvent {
+        kind: EventKind::Add,
+        target: id(2).into(),
+    };
+    assert!(topology.handle_event(&mut bus, &event).unwrap().is_empty());
+    let event = W1NetlinkMessage::MasterEvent {
+        kind: EventKind::Remove,
+        target: second,
+    };
+    assert_eq!(
+        topology.handle_event(&mut bus, &event).unwrap(),
+        [
+            Change::SlaveRemoved {
+                master: second,
+                id: id(2)
+            },
+            Change::MasterRemoved(second),
+        ]
+    );
+    assert_eq!(changes.try_iter().count(), 7);
+}
+
+#[test]
+fn reconcile() {
+    let mut bus = SimBus::new();
+    let master = bus.add_master();
+    bus.attach(master, id(1), Ds18b20::new(20.0));
+    bus.attach(master, id(2), Ds18b20::new(20.0));
+    while bus.next_event().is_some() {}
+
+    let t0 = Instant::now();
+    let mut topology = Topology::new().with_reconcile_interval(Duration::from_secs(60));
+    let mut scheduler = Scheduler::new();
+    assert!(topology.needs_reconcile(t0));
+    for change in topology.reconcile(&mut bus, t0).unwrap() {
+        scheduler.apply(&change, t0);
+    }
+    assert_eq!(
+        scheduler.devices(master).collect::<Vec<_>>(),
+        [id(1), id(2)]
+    );
+    assert!(!topology.needs_reconcile(t0 + Duration::from_secs(59)));
+    assert!(topology.needs_reconcile(t0 + Duration::from_secs(60)));
+
+    // the removal event is lost
+    bus.detach(id(1));
+    bus.next_event().unwrap();
+    let overflow = Error::Io(io::Error::from_raw_os_error(105));
+    assert!(is_overflow(&overflow));
+    assert!(!is_overflow(&Error::NoDevice));
+    topology.invalidate();
+    assert!(topology.needs_reconcile(t0));
+
+    let changes = topology.reconcile(&mut bus, t0).unwrap();
+    assert_eq!(changes, [Change::SlaveRemoved { master, id: id(1) }]);
+    for change in &changes {
+        scheduler.apply(change, t0);
+    }
+    assert_eq!(scheduler.devices(master).collect::<Vec<_>>(), [id(2)]);
+    assert!(!topology.needs_reconcile(t0));
+}